@@ -0,0 +1,184 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+use nego::core::{
+    game::{find_territory, State},
+    r#move::{HasMoves, Move, MoveAccumulator},
+    ray::Rays,
+    rules::Connection,
+};
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+// Every position is reached by random play from a fixed seed, so that runs
+// can be compared against each other.
+const SEED: u64 = 0x6e65676f;
+
+const PLIES: [(&str, usize); 3] = [("opening", 2), ("midgame", 10), ("endgame", 16)];
+
+// Play `plies` random moves from the start, stopping early if the game ends
+fn seeded_position(seed: u64, plies: usize) -> State {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut state = State::new();
+    let mut moves = Vec::new();
+    for _ in 0..plies {
+        moves.clear();
+        state.get_moves(&mut moves);
+        match moves.choose(&mut rng) {
+            Some(m) => state.apply(*m),
+            None => break,
+        }
+    }
+    state
+}
+
+// Play random moves until the game is over, returning the number of moves
+fn playout(state: &mut State, rng: &mut StdRng) -> usize {
+    let mut moves = Vec::new();
+    let mut n = 0;
+    loop {
+        moves.clear();
+        state.get_moves(&mut moves);
+        match moves.choose(rng) {
+            Some(m) => state.apply(*m),
+            None => return n,
+        }
+        n += 1;
+    }
+}
+
+// The first seeded position with a legal move which does (or doesn't) capture
+fn find_apply(captures: bool) -> (State, Move) {
+    for seed in SEED.. {
+        for plies in 8..24 {
+            let mut state = seeded_position(seed, plies);
+            let mut moves = Vec::new();
+            state.get_moves(&mut moves);
+            for m in moves {
+                let undo = state.make(m);
+                let captured = undo.captured().next().is_some();
+                state.undo(undo);
+                if captured == captures {
+                    return (state, m);
+                }
+            }
+        }
+    }
+    unreachable!()
+}
+
+fn bench_generate_moves(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generate_moves");
+    for (name, plies) in PLIES {
+        let state = seeded_position(SEED, plies);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut ma = MoveAccumulator::new();
+                state
+                    .board
+                    .generate_moves(state.current, &state.rules, &mut ma);
+                black_box(ma.0.len())
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_has_moves(c: &mut Criterion) {
+    let mut group = c.benchmark_group("has_moves");
+    for (name, plies) in PLIES {
+        let state = seeded_position(SEED, plies);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut hm = HasMoves(false);
+                state
+                    .board
+                    .generate_moves(state.current, &state.rules, &mut hm);
+                black_box(hm.0)
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_apply(c: &mut Criterion) {
+    let mut group = c.benchmark_group("apply");
+    for (name, captures) in [("quiet", false), ("capture", true)] {
+        let (state, m) = find_apply(captures);
+        group.bench_function(name, |b| {
+            b.iter_batched_ref(
+                || state.clone(),
+                |state| state.apply(black_box(m)),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_territory(c: &mut Criterion) {
+    let state = seeded_position(SEED, PLIES[1].1);
+    let occupied = state.board.black.occupied;
+    let start = occupied.to_square();
+    let group = Connection::Orthogonal.group(occupied, start);
+
+    c.bench_function("find_territory", |b| {
+        b.iter(|| {
+            find_territory(
+                black_box(occupied),
+                black_box(group),
+                Connection::Orthogonal,
+            )
+        })
+    });
+}
+
+fn bench_bitboard(c: &mut Criterion) {
+    let state = seeded_position(SEED, PLIES[1].1);
+    let occupied = state.board.occupied();
+    let start = occupied.to_square();
+    let empty = !occupied;
+    let empty_start = empty.to_square();
+
+    let mut group = c.benchmark_group("bitboard");
+    group.bench_function("floodfill4", |b| {
+        b.iter(|| black_box(occupied).floodfill4(black_box(start)))
+    });
+    group.bench_function("floodfill8", |b| {
+        b.iter(|| black_box(occupied).floodfill8(black_box(start)))
+    });
+    group.bench_function("floodfill8_empty", |b| {
+        b.iter(|| black_box(empty).floodfill8(black_box(empty_start)))
+    });
+    group.bench_function("has_opposite_connection", |b| {
+        b.iter(|| black_box(occupied).has_opposite_connection(black_box(start)))
+    });
+    group.finish();
+}
+
+fn bench_playout(c: &mut Criterion) {
+    let mut group = c.benchmark_group("playout");
+    group.throughput(Throughput::Elements(1));
+    group.sample_size(20);
+    group.bench_function("random", |b| {
+        let mut rng = StdRng::seed_from_u64(SEED);
+        b.iter_batched_ref(
+            State::new,
+            |state| playout(state, &mut rng),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+fn benches(c: &mut Criterion) {
+    Rays::build_lut();
+    bench_generate_moves(c);
+    bench_has_moves(c);
+    bench_apply(c);
+    bench_territory(c);
+    bench_bitboard(c);
+    bench_playout(c);
+}
+
+criterion_group!(engine, benches);
+criterion_main!(engine);
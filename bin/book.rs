@@ -0,0 +1,128 @@
+use nego::{
+    agent::{
+        book::{self, Book},
+        spec::AgentSpec,
+        step,
+    },
+    core::{game::State, ray::Rays},
+};
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use std::time::Instant;
+
+#[macro_use]
+extern crate log;
+
+const USAGE: &str = "usage: book [games] [--output <path>] [--agent <agent>] [--plies <plies>] \
+                     [--explore <probability>] [--min-games <games>] [--seed <seed>]";
+
+fn fail(message: String) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    std::process::exit(1);
+}
+
+// Builds or extends an opening book from self-play. Within the book depth, a
+// random move is sometimes played instead of the agent's so that the games
+// cover more than one line. The book is saved after every game, so a long run
+// can be interrupted.
+fn main() {
+    pretty_env_logger::init();
+
+    let mut games = 100;
+    let mut output = book::DEFAULT_PATH.to_string();
+    let mut agent = "mcts2:time=30s".parse::<AgentSpec>().unwrap();
+    let mut plies = 6;
+    let mut explore = 0.25;
+    let mut min_games = 2;
+    let mut seed = 0;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| fail(format!("missing {}", arg)))
+        };
+        let parsed = match arg.as_str() {
+            "--output" => Some(value()).map(|path| output = path),
+            "--agent" => value().parse().ok().map(|spec| agent = spec),
+            "--plies" => value().parse().ok().map(|p| plies = p),
+            "--explore" => value()
+                .parse()
+                .ok()
+                .filter(|p| (0. ..=1.).contains(p))
+                .map(|p| explore = p),
+            "--min-games" => value().parse().ok().map(|n| min_games = n),
+            "--seed" => value().parse().ok().map(|s| seed = s),
+            _ => arg.parse().ok().map(|n| games = n),
+        };
+        if parsed.is_none() {
+            fail(format!("invalid argument: {}", arg));
+        }
+    }
+    if agent.is_human() {
+        fail("invalid agent".into());
+    }
+    let budget = agent.agent.budget();
+
+    info!("initializing ray LUT");
+    Rays::build_lut();
+
+    let initial = State::new();
+    let mut book = match Book::load(&output) {
+        Ok(book) => {
+            println!("extending {} ({} positions)", output, book.len());
+            book
+        }
+        Err(_) => Book::new(initial.rules.clone()),
+    };
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    for game in 1..=games {
+        let start = Instant::now();
+        let mut players = [agent.player().unwrap(), agent.player().unwrap()];
+        players
+            .iter_mut()
+            .for_each(|player| player.new_game(&initial));
+
+        let mut state = initial.clone();
+        let mut moves = Vec::new();
+        loop {
+            let s = moves.len() % 2;
+            let mut legal = Vec::new();
+            state.get_moves(&mut legal);
+            let m = match moves.len() < plies && rng.gen_bool(explore) {
+                true => legal.choose(&mut rng).copied().inspect(|m| state.apply(*m)),
+                false => step(players[s].as_mut(), &mut state, budget),
+            };
+            let Some(m) = m else {
+                break;
+            };
+            players[1 - s].opponent_moved(&state, m);
+            moves.push(m);
+        }
+        players.iter_mut().for_each(|player| player.stop());
+
+        let result = state.result().expect("the game is over");
+        book.add_game(&initial, &moves, result.winner, plies);
+        book.reweight(min_games);
+        if let Err(e) = book.save(&output) {
+            fail(format!("couldn't write {}: {}", output, e));
+        }
+
+        let opening = moves
+            .iter()
+            .take(plies)
+            .map(|m| m.notation())
+            .collect::<Vec<_>>();
+        println!(
+            "game {}/{}: {} {} ({:.0?}, {} positions)",
+            game,
+            games,
+            opening.join(" "),
+            result,
+            start.elapsed(),
+            book.len()
+        );
+    }
+}
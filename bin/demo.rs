@@ -1,42 +1,111 @@
-use nego::{agent, core::game, core::ray::Rays};
+use nego::{
+    agent::{
+        self,
+        book::Book,
+        spec::{self, AgentSpec},
+        time::{self, Clock, TimeManager},
+    },
+    core::game,
+    core::ray::Rays,
+    core::record::GameRecord,
+};
 
-use minimax::Game;
+use std::sync::Arc;
 
 #[macro_use]
 extern crate log;
 
-pub fn demo_minimax() {
+const USAGE: &str = "usage: demo [--config <path>] [--black <agent>] [--white <agent>]";
+
+fn fail(message: String) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    std::process::exit(1);
+}
+
+pub fn demo_minimax(agents: [AgentSpec; 2]) {
     let mut state = game::State::new();
     let mut s = 0;
 
-    let timeout = std::time::Duration::from_secs(60);
+    let book = Book::load(agent::book::DEFAULT_PATH).ok().map(Arc::new);
+    let mut players = agents.map(|agent| {
+        agent
+            .player_with_book(book.as_ref())
+            .unwrap_or_else(|| fail("the demo has no human players".into()))
+    });
+    let mut clocks = agents.map(|agent| Clock::new(agent.agent.time_control()));
+    let manager = TimeManager::default();
+
+    let mut record = GameRecord::new();
+    record.black = players[0].name();
+    record.white = players[1].name();
+    record.set_rules(&state.rules);
+
+    players
+        .iter_mut()
+        .for_each(|player| player.new_game(&state));
     loop {
         state.dump();
-        let m = if s == 0 {
-            agent::Agent::Random.step(&mut state)
-        } else {
-            agent::Agent::Iterative(timeout).step(&mut state)
-        };
+        let m = time::step_clocked(players[s].as_mut(), &mut state, &mut clocks[s], &manager);
+        if clocks[s].is_flagged() {
+            println!("{} lost on time", players[s].name());
+            break;
+        }
         s = 1 - s;
 
-        if m.is_none() {
-            break;
+        match m {
+            Some(m) => {
+                players[s].opponent_moved(&state, m);
+                record.push(m)
+            }
+            None => break,
         }
     }
-    println!(
-        "Winner: {:?} (b={}, w={})",
-        agent::Nego::get_winner(&state),
-        state.board.black.points(),
-        state.board.white.points()
-    );
+    players.iter_mut().for_each(|player| player.stop());
+    if let Some(result) = state.result() {
+        println!("Winner: {:?} ({})", result.winner, result.score);
+        record.result = result.to_string();
+    }
     state.dump();
+    println!("{}", record);
 }
 
+// Plays one game between the agents for `black` and `white` in the config,
+// random against negamax by default. Either can be given on the command line
+// instead.
 fn main() {
     pretty_env_logger::init();
 
+    let mut config = spec::DEFAULT_PATH.to_string();
+    let mut black = None;
+    let mut white = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| fail(format!("missing {}", arg)))
+        };
+        let parsed = match arg.as_str() {
+            "--config" => Some(value()).map(|path| config = path),
+            "--black" => value().parse().ok().map(|spec| black = Some(spec)),
+            "--white" => value().parse().ok().map(|spec| white = Some(spec)),
+            _ => None,
+        };
+        if parsed.is_none() {
+            fail(format!("invalid argument: {}", arg));
+        }
+    }
+    let defaults = [
+        agent::Agent::Random.into(),
+        agent::Agent::Iterative(std::time::Duration::from_secs(60)).into(),
+    ];
+    let [default_black, default_white] = spec::load_players(&config, defaults);
+
     info!("initializing ray LUT");
     Rays::build_lut();
 
-    demo_minimax();
+    demo_minimax([
+        black.unwrap_or(default_black),
+        white.unwrap_or(default_white),
+    ]);
 }
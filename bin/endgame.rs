@@ -0,0 +1,81 @@
+use nego::{
+    agent::endgame::{pieces_in_hand, solve},
+    core::{game::State, ray::Rays},
+};
+
+use std::time::{Duration, Instant};
+
+#[macro_use]
+extern crate log;
+
+const USAGE: &str = "usage: endgame <position> [--budget <seconds>] [--rules <rules>]";
+
+fn fail(message: String) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    std::process::exit(1);
+}
+
+// Solves a position given in the notation of `State::to_notation`, and
+// prints the margin and the line of best play.
+fn main() {
+    pretty_env_logger::init();
+
+    let mut position = None;
+    let mut budget = Duration::from_secs(60);
+    let mut rules = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--budget" => {
+                let seconds = args.next().and_then(|s| s.parse::<f64>().ok());
+                budget = match seconds {
+                    Some(seconds) if seconds >= 0. => Duration::from_secs_f64(seconds),
+                    _ => fail("invalid budget".into()),
+                };
+            }
+            "--rules" => {
+                let parsed = args.next().and_then(|s| s.parse().ok());
+                rules = Some(parsed.unwrap_or_else(|| fail("invalid rules".into())));
+            }
+            _ => position = Some(arg),
+        }
+    }
+
+    let position = position.unwrap_or_else(|| fail("missing position".into()));
+
+    // Parsing draws the rays of the pieces on the board
+    info!("initializing ray LUT");
+    Rays::build_lut();
+
+    let mut state = State::from_notation(&position)
+        .unwrap_or_else(|e| fail(format!("invalid position: {:?}", e)));
+    if let Some(rules) = rules {
+        state.rules = rules;
+    }
+
+    println!("{} pieces in hand", pieces_in_hand(&state));
+    let start = Instant::now();
+    let Some(solution) = solve(&state, budget) else {
+        println!("not solved within {:?}", budget);
+        std::process::exit(2);
+    };
+
+    let line = solution
+        .line
+        .iter()
+        .map(|m| m.notation())
+        .collect::<Vec<_>>();
+    println!("{}", line.join(" "));
+
+    // Play the line out to show how the result is reached
+    solution.line.iter().for_each(|m| state.apply(*m));
+    let result = state.result().expect("the line ends the game");
+    println!(
+        "{} ({} nodes in {:.2?})",
+        result,
+        solution.nodes,
+        start.elapsed()
+    );
+    println!("{}", result.score);
+}
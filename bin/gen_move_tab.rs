@@ -93,6 +93,21 @@ fn make_placement_tab() {
             count += 1;
         }
     }
+
+    // The center boss is only legal with `Rules::boss_on_center`, so it goes
+    // last where it isn't part of the boss moves.
+    let center = Placement::prototype(PieceTypeId::Boss, Orientation::S).shift(3, 3);
+    println!("    // Boss on the center, only allowed by `Rules::boss_on_center`");
+    println!(
+        "    ({}, 0x{:02x}, 0x{:016x}, 0x{:016x}), // {}",
+        center.orientation as usize,
+        center.position.to_int(),
+        center.mask.0,
+        center.gaze.0,
+        center.notation()
+    );
+    count += 1;
+
     println!("];");
     println!("count = {}", count);
 }
@@ -0,0 +1,104 @@
+use nego::core::{
+    game::State,
+    perft::{perft, perft_divide, PerftStats},
+    ray::Rays,
+    rules::Rules,
+};
+
+use std::time::Instant;
+
+#[macro_use]
+extern crate log;
+
+const USAGE: &str = "usage: perft <depth> [--divide] [--position <notation>] [--rules <rules>]";
+
+struct Options {
+    depth: usize,
+    divide: bool,
+    position: Option<String>,
+    rules: Option<Rules>,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut args = std::env::args().skip(1);
+    let mut options = Options {
+        depth: 0,
+        divide: false,
+        position: None,
+        rules: None,
+    };
+    let mut depth = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--divide" => options.divide = true,
+            "--position" => {
+                options.position = Some(args.next().ok_or("missing position")?);
+            }
+            "--rules" => {
+                let rules = args.next().ok_or("missing rules")?;
+                options.rules = Some(rules.parse().map_err(|e| format!("{:?}", e))?);
+            }
+            _ => depth = Some(arg.parse().map_err(|_| format!("invalid depth: {}", arg))?),
+        }
+    }
+    options.depth = depth.ok_or("missing depth")?;
+    Ok(options)
+}
+
+fn print_stats(label: &str, stats: PerftStats) {
+    println!(
+        "{:>8} {:>12} {:>10} {:>10} {:>10}",
+        label, stats.nodes, stats.captures, stats.territory, stats.terminal
+    );
+}
+
+fn main() {
+    pretty_env_logger::init();
+
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    info!("initializing ray LUT");
+    Rays::build_lut();
+
+    let mut state = match &options.position {
+        Some(notation) => match State::from_notation(notation) {
+            Ok(state) => state,
+            Err(e) => {
+                eprintln!("invalid position: {:?}", e);
+                std::process::exit(1);
+            }
+        },
+        None => State::new(),
+    };
+    if let Some(rules) = options.rules {
+        state.rules = rules;
+    }
+
+    println!(
+        "{:>8} {:>12} {:>10} {:>10} {:>10}",
+        "depth", "nodes", "captures", "territory", "terminal"
+    );
+    for depth in 1..=options.depth {
+        let start = Instant::now();
+        let stats = perft(&mut state, depth);
+        print_stats(&depth.to_string(), stats);
+        info!("depth {} took {:?}", depth, start.elapsed());
+    }
+
+    if options.divide && options.depth > 0 {
+        println!();
+        let mut total = PerftStats::default();
+        for (m, stats) in perft_divide(&mut state, options.depth) {
+            print_stats(&m.notation(), stats);
+            total += stats;
+        }
+        print_stats("total", total);
+    }
+}
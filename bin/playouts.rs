@@ -0,0 +1,63 @@
+use nego::{
+    agent::playout::{play_match, Playout},
+    core::{game::State, ray::Rays},
+};
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use std::time::Instant;
+
+#[macro_use]
+extern crate log;
+
+const USAGE: &str = "usage: playouts [games] [--seed <seed>] [--epsilon <epsilon>]";
+
+// Plays the weighted playout policy against uniformly random playouts and
+// reports its win rate. Colors alternate between games.
+fn main() {
+    pretty_env_logger::init();
+
+    let mut games = 1000;
+    let mut seed = 0;
+    let mut policy = Playout::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let parsed = match arg.as_str() {
+            "--seed" => args.next().and_then(|s| s.parse().ok()).map(|s| seed = s),
+            "--epsilon" => args
+                .next()
+                .and_then(|s| s.parse().ok())
+                .filter(|e| (0. ..=1.).contains(e))
+                .map(|e| policy.epsilon = e),
+            _ => arg.parse().ok().map(|n| games = n),
+        };
+        if parsed.is_none() {
+            eprintln!("invalid argument: {}\n{}", arg, USAGE);
+            std::process::exit(1);
+        }
+    }
+
+    info!("initializing ray LUT");
+    Rays::build_lut();
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let start = Instant::now();
+    let result = play_match(&policy, &Playout::uniform(), &State::new(), games, &mut rng);
+    let elapsed = start.elapsed();
+
+    println!("{:?}", policy);
+    println!(
+        "vs uniform: {} wins, {} losses, {} draws ({:.1}%)",
+        result.wins,
+        result.losses,
+        result.draws,
+        result.win_rate() * 100.
+    );
+    println!(
+        "{} games in {:.2?} ({:.0} games/sec)",
+        games,
+        elapsed,
+        games as f64 / elapsed.as_secs_f64()
+    );
+}
@@ -0,0 +1,243 @@
+use nego::{
+    agent::{
+        spec::{self, AgentSpec},
+        tournament::{self, Format, Sprt, SprtResult, Tally, TournamentOptions},
+    },
+    core::{
+        game::{Color, State},
+        ray::Rays,
+    },
+};
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::time::Instant;
+
+#[macro_use]
+extern crate log;
+
+const USAGE: &str =
+    "usage: tournament [rounds] --agent <agent> --agent <agent> ... [--config <path>] [--gauntlet] \
+                     [--threads <threads>] [--openings <path>] [--output <path>] \
+                     [--iterations <iterations>] [--seed <seed>] [--sprt <elo0> <elo1>] \
+                     [--alpha <alpha>] [--beta <beta>]";
+
+fn fail(message: String) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    std::process::exit(1);
+}
+
+// One position in position notation per line. Blank lines and lines starting
+// with `#` are skipped.
+fn read_openings(path: &str) -> Vec<State> {
+    let text =
+        fs::read_to_string(path).unwrap_or_else(|e| fail(format!("couldn't read {}: {}", path, e)));
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            State::from_notation(line)
+                .unwrap_or_else(|e| fail(format!("invalid opening {}: {:?}", line, e)))
+        })
+        .collect()
+}
+
+fn print_tally(name: &str, tally: &Tally) {
+    println!(
+        "{:<28} {:>5} games  +{} -{} ={}  {:5.1}%  Elo {}",
+        name,
+        tally.games(),
+        tally.wins,
+        tally.losses,
+        tally.draws,
+        tally.score() * 100.,
+        tally.elo()
+    );
+}
+
+// Plays a round robin or a gauntlet between agents. Every pairing plays each
+// opening with both colors in every round. With `--sprt`, the first of two
+// agents is tested against the second, and the tournament stops as soon as
+// the test accepts or rejects it.
+fn main() {
+    pretty_env_logger::init();
+
+    let mut rounds = 10;
+    let mut entrants = Vec::<(String, AgentSpec)>::new();
+    let mut format = Format::RoundRobin;
+    let mut threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut openings = None;
+    let mut output = None;
+    let mut options = TournamentOptions::default();
+    let mut sprt = None::<Sprt>;
+    let mut alpha = 0.05;
+    let mut beta = 0.05;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| fail(format!("missing {}", arg)))
+        };
+        let parsed = match arg.as_str() {
+            "--agent" => {
+                let name = value();
+                name.parse().ok().map(|agent| entrants.push((name, agent)))
+            }
+            "--config" => {
+                let path = value();
+                let config = spec::load(&path)
+                    .unwrap_or_else(|e| fail(format!("couldn't load {}: {:?}", path, e)));
+                entrants.extend(config);
+                Some(())
+            }
+            "--gauntlet" => {
+                format = Format::Gauntlet;
+                Some(())
+            }
+            "--threads" => value().parse().ok().filter(|n| *n > 0).map(|n| threads = n),
+            "--openings" => Some(value()).map(|path| openings = Some(path)),
+            "--output" => Some(value()).map(|path| output = Some(path)),
+            "--iterations" => value().parse().ok().map(|n| options.iterations = Some(n)),
+            "--seed" => value().parse().ok().map(|s| options.seed = Some(s)),
+            "--sprt" => {
+                let elo0 = value().parse().ok();
+                let elo1 = value().parse().ok();
+                elo0.zip(elo1).map(|(elo0, elo1)| {
+                    sprt = Some(Sprt {
+                        elo0,
+                        elo1,
+                        ..Sprt::default()
+                    })
+                })
+            }
+            "--alpha" => value()
+                .parse()
+                .ok()
+                .filter(|p| (0. ..1.).contains(p))
+                .map(|p| alpha = p),
+            "--beta" => value()
+                .parse()
+                .ok()
+                .filter(|p| (0. ..1.).contains(p))
+                .map(|p| beta = p),
+            _ => arg.parse().ok().map(|n| rounds = n),
+        };
+        if parsed.is_none() {
+            fail(format!("invalid argument: {}", arg));
+        }
+    }
+    let (names, agents): (Vec<_>, Vec<_>) = entrants.into_iter().unzip();
+    if agents.iter().any(|agent| agent.is_human()) {
+        fail("humans can't play in tournaments".into());
+    }
+    if agents.len() < 2 {
+        fail("at least two agents are needed".into());
+    }
+    let sprt = sprt.map(|sprt| Sprt {
+        alpha,
+        beta,
+        ..sprt
+    });
+    if sprt.is_some() && agents.len() != 2 {
+        fail("an SPRT needs exactly two agents".into());
+    }
+    options.threads = threads;
+
+    info!("initializing ray LUT");
+    Rays::build_lut();
+
+    let openings = match &openings {
+        Some(path) => read_openings(path),
+        None => vec![State::new()],
+    };
+    if openings.is_empty() {
+        fail("no openings".into());
+    }
+    let mut output = output.map(|path| {
+        let file = File::options()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .unwrap_or_else(|e| fail(format!("couldn't open {}: {}", path, e)));
+        (path, BufWriter::new(file))
+    });
+
+    let games = tournament::schedule(format, agents.len(), openings.len(), rounds);
+    println!(
+        "{} games between {} agents from {} openings on {} threads",
+        games.len(),
+        agents.len(),
+        openings.len(),
+        threads
+    );
+
+    // The tally of every agent against every other, from the first's side
+    let mut tallies = vec![vec![Tally::default(); agents.len()]; agents.len()];
+    let mut played = 0;
+    let mut decision = SprtResult::Continue;
+    let start = Instant::now();
+    tournament::run(&agents, &games, &openings, &options, |pairing, outcome| {
+        played += 1;
+        let (black, white) = (pairing.black, pairing.white);
+        tallies[black][white].add(outcome.winner, Color::Black);
+        tallies[white][black].add(outcome.winner, Color::White);
+        println!(
+            "game {}/{}: {} vs {} {} (round {}, opening {})",
+            played,
+            games.len(),
+            names[black],
+            names[white],
+            outcome.record.result,
+            pairing.round,
+            pairing.opening + 1
+        );
+        if let Some((path, w)) = &mut output {
+            if let Err(e) = writeln!(w, "{}", outcome.record).and_then(|_| w.flush()) {
+                fail(format!("couldn't write {}: {}", path, e));
+            }
+        }
+
+        match &sprt {
+            Some(sprt) => {
+                let tally = &tallies[0][1];
+                let (lower, upper) = sprt.bounds();
+                println!("LLR {:.2} ({:.2}, {:.2})", sprt.llr(tally), lower, upper);
+                decision = sprt.test(tally);
+                decision == SprtResult::Continue
+            }
+            None => true,
+        }
+    });
+
+    println!("\n{} games in {:.0?}", played, start.elapsed());
+    for a in 0..agents.len() {
+        for b in a + 1..agents.len() {
+            if tallies[a][b].games() > 0 {
+                print_tally(&format!("{} vs {}", names[a], names[b]), &tallies[a][b]);
+            }
+        }
+    }
+    if format == Format::RoundRobin && agents.len() > 2 {
+        println!();
+        for (name, row) in names.iter().zip(&tallies) {
+            let total = row.iter().fold(Tally::default(), |total, tally| Tally {
+                wins: total.wins + tally.wins,
+                losses: total.losses + tally.losses,
+                draws: total.draws + tally.draws,
+            });
+            print_tally(name, &total);
+        }
+    }
+    if let Some(sprt) = sprt {
+        let verdict = match decision {
+            SprtResult::Accept => "accepted",
+            SprtResult::Reject => "rejected",
+            SprtResult::Continue => "inconclusive",
+        };
+        println!(
+            "\nSPRT [{}, {}]: {} is {}",
+            sprt.elo0, sprt.elo1, names[0], verdict
+        );
+    }
+}
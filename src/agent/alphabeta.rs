@@ -0,0 +1,223 @@
+use crate::{
+    agent::playout::Playout,
+    core::{bitboard::BitBoard, game::State, r#move::Move},
+};
+
+use std::cmp::Reverse;
+
+// Alpha-beta
+//
+// Negamax with alpha-beta and a transposition table, shared by the endgame
+// solver and analysis. They differ in what they do at the leaves and when
+// they give up, which is left to `Leaves`. Moves are tried best first from
+// the table, then by playout weight, so that captures and territory come
+// early.
+//
+// The transposition table has a fixed number of entries, so that long
+// searches don't run out of memory. Each position has one slot, and a new
+// entry only replaces one searched at least as deep, unless it is for the same
+// position.
+
+/// Searching to this depth never reaches the horizon, since every game ends
+/// long before.
+pub const TO_THE_END: u32 = u32::MAX;
+
+/// The number of entries in the transposition table unless given another,
+/// which take about 10 MB.
+pub const DEFAULT_TABLE_ENTRIES: usize = 1 << 18;
+
+/// Values at the leaves of a search, and when to give up.
+pub trait Leaves {
+    /// The value of a finished game for the player to move.
+    fn terminal(&self, state: &State) -> i32;
+
+    /// The value for the player to move where the search stops short of the
+    /// end of the game.
+    fn evaluate(&self, state: &State) -> i32;
+
+    /// Checked at every node, with the number of nodes searched so far.
+    fn out_of_time(&mut self, nodes: u64) -> bool;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    depth: u32,
+    value: i32,
+    bound: Bound,
+    best: Option<Move>,
+}
+
+// `State::hash` covers the pieces and the turn, but not the territory. That
+// depends on the order the pieces were played in, and changes both the moves
+// and the score.
+type Key = (u64, BitBoard, BitBoard);
+
+#[inline]
+fn key(state: &State) -> Key {
+    let board = &state.board;
+    (state.hash, board.black.owned, board.white.owned)
+}
+
+struct Table {
+    slots: Vec<Option<(Key, Entry)>>,
+}
+
+impl Table {
+    fn new(entries: usize) -> Table {
+        Table {
+            slots: vec![None; entries.max(1).next_power_of_two()],
+        }
+    }
+
+    #[inline]
+    fn index(&self, key: &Key) -> usize {
+        let (hash, black, white) = key;
+        let owned = (black.0 ^ white.0.rotate_left(32)).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        (hash ^ owned) as usize & (self.slots.len() - 1)
+    }
+
+    fn get(&self, key: &Key) -> Option<&Entry> {
+        match &self.slots[self.index(key)] {
+            Some((k, entry)) if k == key => Some(entry),
+            _ => None,
+        }
+    }
+
+    fn insert(&mut self, key: Key, entry: Entry) {
+        let index = self.index(&key);
+        let slot = &mut self.slots[index];
+        let replace = match slot {
+            Some((k, old)) => *k == key || old.depth <= entry.depth,
+            None => true,
+        };
+        if replace {
+            *slot = Some((key, entry));
+        }
+    }
+}
+
+pub struct AlphaBeta<L> {
+    pub leaves: L,
+    pub nodes: u64,
+    table: Table,
+    ordering: Playout,
+}
+
+impl<L: Leaves> AlphaBeta<L> {
+    pub fn new(leaves: L) -> Self {
+        Self::with_table_entries(leaves, DEFAULT_TABLE_ENTRIES)
+    }
+
+    /// With a transposition table of `entries`, rounded up to a power of two.
+    pub fn with_table_entries(leaves: L, entries: usize) -> Self {
+        AlphaBeta {
+            leaves,
+            nodes: 0,
+            table: Table::new(entries),
+            ordering: Playout::default(),
+        }
+    }
+
+    /// The best move found for `state` by an earlier search, if any.
+    pub fn best(&self, state: &State) -> Option<Move> {
+        self.table.get(&key(state)).and_then(|entry| entry.best)
+    }
+
+    /// The moves of `state`, `best` first and then by playout weight.
+    pub fn ordered_moves(&self, state: &State, best: Option<Move>) -> Vec<Move> {
+        let mut moves = Vec::new();
+        state.get_moves(&mut moves);
+        let mut weighted = moves
+            .into_iter()
+            .map(|m| {
+                let weight = match Some(m) == best {
+                    true => u32::MAX,
+                    false => self.ordering.weight(state, m),
+                };
+                (weight, m)
+            })
+            .collect::<Vec<_>>();
+        weighted.sort_by_key(|(weight, _)| Reverse(*weight));
+        weighted.into_iter().map(|(_, m)| m).collect()
+    }
+
+    /// The value of `state` for the player to move, searched to `depth`
+    /// within the window. Returns `None` if the search gave up.
+    pub fn search(
+        &mut self,
+        state: &mut State,
+        depth: u32,
+        mut alpha: i32,
+        mut beta: i32,
+    ) -> Option<i32> {
+        self.nodes += 1;
+        if self.leaves.out_of_time(self.nodes) {
+            return None;
+        }
+        if depth == 0 {
+            return Some(match state.has_moves() {
+                true => self.leaves.evaluate(state),
+                false => self.leaves.terminal(state),
+            });
+        }
+
+        let entry = self.table.get(&key(state)).copied();
+        if let Some(entry) = entry.filter(|entry| entry.depth >= depth) {
+            match entry.bound {
+                Bound::Exact => return Some(entry.value),
+                Bound::Lower => alpha = alpha.max(entry.value),
+                Bound::Upper => beta = beta.min(entry.value),
+            }
+            if alpha >= beta {
+                return Some(entry.value);
+            }
+        }
+
+        let moves = self.ordered_moves(state, entry.and_then(|entry| entry.best));
+        if moves.is_empty() {
+            return Some(self.leaves.terminal(state));
+        }
+
+        let original_alpha = alpha;
+        let mut best = (i32::MIN, None);
+        for m in moves {
+            let undo = state.make(m);
+            let value = self
+                .search(state, depth - 1, -beta, -alpha)
+                .map(|value| -value);
+            state.undo(undo);
+
+            let value = value?;
+            if value > best.0 {
+                best = (value, Some(m));
+            }
+            alpha = alpha.max(value);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = match best.0 {
+            value if value <= original_alpha => Bound::Upper,
+            value if value >= beta => Bound::Lower,
+            _ => Bound::Exact,
+        };
+        self.table.insert(
+            key(state),
+            Entry {
+                depth,
+                value: best.0,
+                bound,
+                best: best.1,
+            },
+        );
+        Some(best.0)
+    }
+}
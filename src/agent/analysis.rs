@@ -0,0 +1,124 @@
+use crate::{
+    agent::{solver::Proof, Player},
+    core::{game::State, r#move::Move},
+};
+
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+// Analysis
+//
+// `Player::choose_move` only gives the move, which is all a game needs. For
+// reviewing games and giving hints, an analysis shows the best few moves at
+// the root with their scores and the lines expected to follow, and how far
+// the search got. It is reported every `interval` while the search runs, so
+// that a display can follow it, and once more at the end.
+//
+// The MCTS agents read the lines off their trees: the most visited moves at
+// the root, each followed by the most visited replies. Negamax has a search
+// of its own for analysis (see `negamax.rs`), since the minimax crate only
+// tells us its best line.
+
+/// How good a move is for the player making it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Score {
+    /// Negamax evaluation in hundredths of a point. A finished game scores
+    /// more than any evaluation.
+    Points(i32),
+    /// Share of playouts won, from 0 to 1, with draws counting a half.
+    WinRate(f64),
+    /// The outcome proven by the solver.
+    Proven(Proof),
+}
+
+/// One of the best moves at the root.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    pub m: Move,
+    pub score: Score,
+    /// Playouts through the move, for the MCTS agents.
+    pub visits: Option<u32>,
+    /// The line expected to follow, starting with `m`.
+    pub pv: Vec<Move>,
+}
+
+/// What a search has found so far.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Analysis {
+    /// The best moves, best first.
+    pub lines: Vec<Line>,
+    /// The last depth completed by negamax, or the deepest node in an MCTS
+    /// tree.
+    pub depth: u32,
+    /// Positions searched: nodes for negamax, playouts for MCTS.
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
+
+impl Analysis {
+    pub fn best(&self) -> Option<Move> {
+        self.lines.first().map(|line| line.m)
+    }
+
+    /// Nodes searched per second.
+    pub fn nps(&self) -> f64 {
+        match self.elapsed.is_zero() {
+            true => 0.,
+            false => self.nodes as f64 / self.elapsed.as_secs_f64(),
+        }
+    }
+}
+
+/// How long to analyse for, and how much to report.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AnalysisOptions {
+    /// The number of moves to show.
+    pub lines: usize,
+    pub time: Duration,
+    /// How often to report while searching.
+    pub interval: Duration,
+}
+
+impl Default for AnalysisOptions {
+    fn default() -> Self {
+        AnalysisOptions {
+            lines: 3,
+            time: Duration::from_secs(5),
+            interval: Duration::from_millis(250),
+        }
+    }
+}
+
+/// A search which can show its best moves. Like a move search, it can be
+/// cancelled with `Player::set_stop`.
+pub trait Analyze: Player {
+    /// Analyse `state` for `options.time`, calling `report` with the analysis
+    /// so far every `options.interval`. Returns the final analysis, which has
+    /// no lines when there are no legal moves.
+    fn analyze(
+        &mut self,
+        state: &State,
+        options: &AnalysisOptions,
+        report: &mut dyn FnMut(&Analysis),
+    ) -> Analysis;
+}
+
+/// Analyse `state` on another thread. The reports arrive on the channel,
+/// followed by the final analysis, after which it is closed. To be able to
+/// cancel it, give the analyzer a `StopToken` first.
+pub fn spawn(
+    mut analyzer: Box<dyn Analyze>,
+    state: &State,
+    options: AnalysisOptions,
+) -> Receiver<Analysis> {
+    let (sender, receiver) = mpsc::channel();
+    let state = state.clone();
+    thread::spawn(move || {
+        let analysis = analyzer.analyze(&state, &options, &mut |analysis| {
+            _ = sender.send(analysis.clone());
+        });
+        _ = sender.send(analysis);
+    });
+    receiver
+}
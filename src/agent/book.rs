@@ -0,0 +1,374 @@
+use crate::{
+    agent::{stop::StopToken, time::Budget, Player},
+    core::{
+        error::Error,
+        game::{Color, State},
+        move_tab::LUTEntry,
+        pieces::PieceId,
+        r#move::Move,
+        rules::Rules,
+    },
+};
+
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Opening book
+//
+// Search has a hard time telling the first moves apart: there are 48 boss
+// placements, and then about 1700 replies for each. The book remembers moves
+// from self-play games along with how they turned out.
+//
+// Positions are keyed on `State::canonical_hash`, and moves are stored as
+// played in the canonical position, so that games which differ only by a
+// rotation of the board (e.g., starting in different corners) share their
+// statistics.
+//
+// On disk, a book is a header followed by the positions in hash order. All
+// numbers are little endian.
+//
+//     magic    b"NEGOBOOK"
+//     version  u8
+//     rules    u8 length, then the rules as written by `Rules::to_string`
+//     count    u32 positions
+//
+// and for each position:
+//
+//     hash     u64
+//     moves    u16
+//
+// followed by each move:
+//
+//     move     u16 raw value
+//     weight   u16
+//     games    u32
+//     wins     u32
+//     draws    u32
+
+const MAGIC: &[u8; 8] = b"NEGOBOOK";
+const VERSION: u8 = 2;
+
+/// Where the UI and tools look for a book by default.
+pub const DEFAULT_PATH: &str = "nego.book";
+
+/// A move in the book and the results of the games which played it, from the
+/// side of the player making it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BookMove {
+    pub m: Move,
+    /// Relative chance of playing the move. It is never played if zero.
+    pub weight: u16,
+    pub games: u32,
+    pub wins: u32,
+    pub draws: u32,
+}
+
+impl BookMove {
+    fn new(m: Move) -> BookMove {
+        BookMove {
+            m,
+            weight: 0,
+            games: 0,
+            wins: 0,
+            draws: 0,
+        }
+    }
+
+    /// The fraction of points scored, counting draws as half.
+    pub fn score(&self) -> f64 {
+        match self.games {
+            0 => 0.5,
+            games => (self.wins as f64 + self.draws as f64 / 2.) / games as f64,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Book {
+    pub rules: Rules,
+    positions: HashMap<u64, Vec<BookMove>>,
+}
+
+impl Default for Book {
+    fn default() -> Self {
+        Self::new(Rules::standard())
+    }
+}
+
+impl Book {
+    /// An empty book for games played with `rules`.
+    pub fn new(rules: Rules) -> Book {
+        Book {
+            rules,
+            positions: HashMap::new(),
+        }
+    }
+
+    /// The number of positions in the book.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// The book moves for `state`, or `None` if it isn't in the book.
+    pub fn lookup(&self, state: &State) -> Option<Vec<BookMove>> {
+        if state.rules != self.rules {
+            return None;
+        }
+        let canonical = state.canonical_hash();
+        let moves = self.positions.get(&canonical.hash)?;
+        let moves = moves
+            .iter()
+            .filter_map(|entry| {
+                let m = canonical.from_canonical(entry.m)?;
+                Some(BookMove { m, ..*entry })
+            })
+            .collect();
+        Some(moves)
+    }
+
+    /// Record the result of a game in which `m` was played from `state`.
+    pub fn add(&mut self, state: &State, m: Move, winner: Option<Color>) {
+        let canonical = state.canonical_hash();
+        let m = canonical.to_canonical(m);
+        let moves = self.positions.entry(canonical.hash).or_default();
+        let index = match moves.iter().position(|entry| entry.m == m) {
+            Some(index) => index,
+            None => {
+                moves.push(BookMove::new(m));
+                moves.len() - 1
+            }
+        };
+
+        let entry = &mut moves[index];
+        entry.games += 1;
+        match winner {
+            Some(color) if color == state.current => entry.wins += 1,
+            Some(_) => (),
+            None => entry.draws += 1,
+        }
+    }
+
+    /// Record every move of a game up to `plies` deep. The game is replayed
+    /// from `initial`.
+    pub fn add_game(
+        &mut self,
+        initial: &State,
+        moves: &[Move],
+        winner: Option<Color>,
+        plies: usize,
+    ) {
+        let mut state = initial.clone();
+        for m in moves.iter().take(plies) {
+            self.add(&state, *m, winner);
+            state.apply(*m);
+        }
+    }
+
+    /// Set the weight of every move from its results. Moves played fewer than
+    /// `min_games` times are left out of play, as are moves which score much
+    /// worse than the best move in the position.
+    pub fn reweight(&mut self, min_games: u32) {
+        for moves in self.positions.values_mut() {
+            let eligible = |entry: &BookMove| entry.games >= min_games.max(1);
+            let best = moves
+                .iter()
+                .filter(|entry| eligible(entry))
+                .map(|entry| entry.score())
+                .fold(0., f64::max);
+            for entry in moves.iter_mut() {
+                let score = entry.score();
+                entry.weight = match eligible(entry) && score >= best - 0.1 {
+                    true => (score * 1000.).round().max(1.) as u16,
+                    false => 0,
+                };
+            }
+            moves.sort_by(|a, b| b.weight.cmp(&a.weight).then(b.games.cmp(&a.games)));
+        }
+    }
+
+    /// Choose a book move for `state` in proportion to the weights, or `None`
+    /// if there isn't one.
+    pub fn choose<R: Rng>(&self, state: &State, rng: &mut R) -> Option<Move> {
+        let moves = self.lookup(state)?;
+        let total = moves.iter().map(|entry| entry.weight as u32).sum::<u32>();
+        if total == 0 {
+            return None;
+        }
+        let mut pick = rng.gen_range(0..total);
+        for entry in moves {
+            if pick < entry.weight as u32 {
+                return Some(entry.m);
+            }
+            pick -= entry.weight as u32;
+        }
+        unreachable!()
+    }
+
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let rules = self.rules.to_string();
+        w.write_all(MAGIC)?;
+        w.write_all(&[VERSION, rules.len() as u8])?;
+        w.write_all(rules.as_bytes())?;
+        w.write_all(&(self.positions.len() as u32).to_le_bytes())?;
+
+        let mut hashes = self.positions.keys().copied().collect::<Vec<_>>();
+        hashes.sort();
+        for hash in hashes {
+            let moves = &self.positions[&hash];
+            w.write_all(&hash.to_le_bytes())?;
+            w.write_all(&(moves.len() as u16).to_le_bytes())?;
+            for entry in moves {
+                w.write_all(&entry.m.get_raw_value().to_le_bytes())?;
+                w.write_all(&entry.weight.to_le_bytes())?;
+                w.write_all(&entry.games.to_le_bytes())?;
+                w.write_all(&entry.wins.to_le_bytes())?;
+                w.write_all(&entry.draws.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(r: &mut R) -> Result<Book, Error> {
+        let mut magic = [0; 8];
+        read_exact(r, &mut magic)?;
+        let [version, len] = read_bytes(r)?;
+        if &magic != MAGIC || version != VERSION {
+            return Err(Error::InvalidBook);
+        }
+        let mut rules = vec![0; len as usize];
+        read_exact(r, &mut rules)?;
+        let rules = String::from_utf8(rules)
+            .ok()
+            .and_then(|rules| rules.parse().ok())
+            .ok_or(Error::InvalidBook)?;
+
+        let mut book = Book::new(rules);
+        let count = u32::from_le_bytes(read_bytes(r)?);
+        for _ in 0..count {
+            let hash = u64::from_le_bytes(read_bytes(r)?);
+            let n = u16::from_le_bytes(read_bytes(r)?);
+            let moves = (0..n)
+                .map(|_| read_move(r))
+                .collect::<Result<Vec<_>, _>>()?;
+            book.positions.insert(hash, moves);
+        }
+        Ok(book)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Book, Error> {
+        let file = File::open(path).map_err(|e| Error::Io(e.kind()))?;
+        Book::read(&mut BufReader::new(file))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write(&mut w)?;
+        w.flush()
+    }
+}
+
+fn read_exact<R: Read>(r: &mut R, buf: &mut [u8]) -> Result<(), Error> {
+    r.read_exact(buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => Error::InvalidBook,
+        kind => Error::Io(kind),
+    })
+}
+
+fn read_bytes<R: Read, const N: usize>(r: &mut R) -> Result<[u8; N], Error> {
+    let mut buf = [0; N];
+    read_exact(r, &mut buf)?;
+    Ok(buf)
+}
+
+fn read_move<R: Read>(r: &mut R) -> Result<BookMove, Error> {
+    let raw = u16::from_le_bytes(read_bytes(r)?);
+    let m = match raw {
+        u16::MAX => Move::PASS,
+        // Check the piece and table entry, as `Move` assumes they're valid
+        raw if PieceId::from_index(raw & 0b1111).is_some()
+            && ((raw >> 4) as usize) <= LUTEntry::CENTER_BOSS.0 =>
+        {
+            Move::from_raw_value(raw)
+        }
+        _ => return Err(Error::InvalidBook),
+    };
+    Ok(BookMove {
+        m,
+        weight: u16::from_le_bytes(read_bytes(r)?),
+        games: u32::from_le_bytes(read_bytes(r)?),
+        wins: u32::from_le_bytes(read_bytes(r)?),
+        draws: u32::from_le_bytes(read_bytes(r)?),
+    })
+}
+
+/// Plays from the book while the position is in it, and otherwise leaves the
+/// move to another player.
+pub struct WithBook {
+    pub inner: Box<dyn Player>,
+    pub book: Arc<Book>,
+    rng: SmallRng,
+}
+
+impl WithBook {
+    pub fn new(inner: Box<dyn Player>, book: Arc<Book>) -> WithBook {
+        WithBook {
+            inner,
+            book,
+            rng: SmallRng::from_entropy(),
+        }
+    }
+}
+
+impl Player for WithBook {
+    fn name(&self) -> String {
+        format!("{} (book)", self.inner.name())
+    }
+
+    fn new_game(&mut self, state: &State) {
+        self.inner.new_game(state)
+    }
+
+    fn choose_move(&mut self, state: &State, budget: Duration) -> Option<Move> {
+        self.choose_move_within(state, Budget::fixed(budget))
+    }
+
+    fn choose_move_within(&mut self, state: &State, budget: Budget) -> Option<Move> {
+        if let Some(m) = self.book.choose(state, &mut self.rng) {
+            // The hash could collide with a position which isn't in the book
+            let mut moves = Vec::new();
+            state.get_moves(&mut moves);
+            if moves.contains(&m) {
+                log::info!("book: {}", m.notation());
+                return Some(m);
+            }
+        }
+        self.inner.choose_move_within(state, budget)
+    }
+
+    fn opponent_moved(&mut self, state: &State, m: Move) {
+        self.inner.opponent_moved(state, m)
+    }
+
+    fn stop(&mut self) {
+        self.inner.stop()
+    }
+
+    fn set_stop(&mut self, stop: StopToken) {
+        self.inner.set_stop(stop)
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = SmallRng::seed_from_u64(seed);
+        self.inner.set_seed(seed)
+    }
+}
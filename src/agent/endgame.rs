@@ -0,0 +1,219 @@
+use crate::{
+    agent::{
+        alphabeta::{AlphaBeta, Leaves, TO_THE_END},
+        stop::StopToken,
+        time::Budget,
+        Player,
+    },
+    core::{
+        game::{Color, State},
+        r#move::Move,
+    },
+};
+
+use std::time::{Duration, Instant};
+
+// Endgame solver
+//
+// Once only a few pieces are left in hand, the game tree is small enough to
+// search completely. This is alpha-beta (`agent/alphabeta.rs`) on the final
+// score margin, to the end of the game.
+
+/// Solve automatically when the players hold this many pieces between them.
+pub const DEFAULT_THRESHOLD: u32 = 10;
+
+/// The most nodes the solver searches when the budget is a fixed number of
+/// iterations rather than a time, so that the move still only depends on the
+/// position. A couple of seconds in release.
+pub const ITERATION_NODES: u64 = 1 << 20;
+
+// How often to check the clock
+const NODES_PER_CHECK: u64 = 1024;
+
+/// The exact result of a position with best play.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Solution {
+    /// The final margin for the player to move, in half points after komi.
+    pub margin: i32,
+    /// Best play for both sides until the end of the game.
+    pub line: Vec<Move>,
+    pub nodes: u64,
+}
+
+/// Pieces left in both hands.
+#[inline]
+pub fn pieces_in_hand(state: &State) -> u32 {
+    state.board.black.hand.bits().count_ones() + state.board.white.hand.bits().count_ones()
+}
+
+// The final margin for the player to move
+#[inline]
+fn terminal_value(state: &State) -> i32 {
+    let margin = state.rules.margin(&state.board);
+    match state.current {
+        Color::Black => margin,
+        Color::White => -margin,
+    }
+}
+
+// Gives up after `budget`, `max_nodes` or once stopped
+struct Limits {
+    start: Instant,
+    budget: Duration,
+    max_nodes: u64,
+    stop: StopToken,
+}
+
+impl Leaves for Limits {
+    fn terminal(&self, state: &State) -> i32 {
+        terminal_value(state)
+    }
+
+    // Only reached if a game were longer than `TO_THE_END`
+    fn evaluate(&self, state: &State) -> i32 {
+        terminal_value(state)
+    }
+
+    fn out_of_time(&mut self, nodes: u64) -> bool {
+        self.stop.is_stopped()
+            || nodes > self.max_nodes
+            || (nodes.is_multiple_of(NODES_PER_CHECK) && self.start.elapsed() >= self.budget)
+    }
+}
+
+// Follow moves which keep the value, checking each with a narrow window.
+// Returns `None` if the budget runs out, or no move keeps the value.
+fn line(search: &mut AlphaBeta<Limits>, state: &State, mut value: i32) -> Option<Vec<Move>> {
+    let mut state = state.clone();
+    let mut line = Vec::new();
+    loop {
+        let moves = search.ordered_moves(&state, search.best(&state));
+        if moves.is_empty() {
+            return Some(line);
+        }
+        let mut found = None;
+        for m in moves {
+            let undo = state.make(m);
+            let reply = search.search(&mut state, TO_THE_END, -value - 1, -value + 1);
+            state.undo(undo);
+            if reply? == -value {
+                found = Some(m);
+                break;
+            }
+        }
+        let m = found?;
+        state.apply(m);
+        line.push(m);
+        value = -value;
+    }
+}
+
+/// Search `state` to the end of the game, giving up after `budget`.
+pub fn solve(state: &State, budget: Duration) -> Option<Solution> {
+    solve_until(state, budget, &StopToken::new())
+}
+
+/// As `solve`, also giving up once `stop` is stopped.
+pub fn solve_until(state: &State, budget: Duration, stop: &StopToken) -> Option<Solution> {
+    solve_with(state, budget, u64::MAX, stop)
+}
+
+// Giving up after `budget` or `max_nodes`, whichever comes first
+fn solve_with(
+    state: &State,
+    budget: Duration,
+    max_nodes: u64,
+    stop: &StopToken,
+) -> Option<Solution> {
+    let mut search = AlphaBeta::new(Limits {
+        start: Instant::now(),
+        budget,
+        max_nodes,
+        stop: stop.clone(),
+    });
+    let margin = search.search(&mut state.clone(), TO_THE_END, i32::MIN + 1, i32::MAX)?;
+    let line = line(&mut search, state, margin)?;
+    Some(Solution {
+        margin,
+        line,
+        nodes: search.nodes,
+    })
+}
+
+/// Plays perfectly once few enough pieces are left in hand, and otherwise
+/// leaves the move to another player.
+pub struct WithEndgame {
+    pub inner: Box<dyn Player>,
+    pub threshold: u32,
+    stop: StopToken,
+}
+
+impl WithEndgame {
+    pub fn new(inner: Box<dyn Player>) -> WithEndgame {
+        WithEndgame {
+            inner,
+            threshold: DEFAULT_THRESHOLD,
+            stop: StopToken::new(),
+        }
+    }
+}
+
+impl Player for WithEndgame {
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    fn new_game(&mut self, state: &State) {
+        self.inner.new_game(state)
+    }
+
+    fn choose_move(&mut self, state: &State, budget: Duration) -> Option<Move> {
+        self.choose_move_within(state, Budget::fixed(budget))
+    }
+
+    // Half of the target goes to the solver, or `ITERATION_NODES` for a fixed
+    // number of iterations. If it doesn't finish, the rest goes to the other
+    // player.
+    fn choose_move_within(&mut self, state: &State, budget: Budget) -> Option<Move> {
+        if pieces_in_hand(state) <= self.threshold {
+            let start = Instant::now();
+            let solution = match budget.iterations {
+                Some(_) => solve_with(state, Duration::MAX, ITERATION_NODES, &self.stop),
+                None => solve_until(state, budget.target / 2, &self.stop),
+            };
+            if let Some(solution) = solution {
+                log::info!(
+                    "endgame: solved in {:?}, margin {}",
+                    start.elapsed(),
+                    solution.margin
+                );
+                return solution.line.first().copied();
+            }
+            let elapsed = start.elapsed();
+            let rest = Budget {
+                target: budget.target.saturating_sub(elapsed),
+                max: budget.max.saturating_sub(elapsed),
+                ..budget
+            };
+            return self.inner.choose_move_within(state, rest);
+        }
+        self.inner.choose_move_within(state, budget)
+    }
+
+    fn opponent_moved(&mut self, state: &State, m: Move) {
+        self.inner.opponent_moved(state, m)
+    }
+
+    fn stop(&mut self) {
+        self.inner.stop()
+    }
+
+    fn set_stop(&mut self, stop: StopToken) {
+        self.inner.set_stop(stop.clone());
+        self.stop = stop;
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.inner.set_seed(seed)
+    }
+}
@@ -0,0 +1,181 @@
+use crate::core::{
+    bitboard::{BitBoard, EMPTY},
+    game::{draw_boss_rays, Color, PlayerState, State},
+    orientation::ALL_ORIENTATIONS,
+    pieces::PieceId,
+    r#move::MoveCounter,
+    ray::Rays,
+};
+
+// Static evaluation
+//
+// The evaluation is a weighted sum of features counted for each side, from the
+// point of view of the player to move. Units are hundredths of a point, so the
+// default weight for the score itself is 100.
+
+/// What the evaluator counts for one side.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Features {
+    /// The player's score under the rules, not including komi.
+    pub score: u32,
+    /// Empty squares in the player's territory.
+    pub territory: u32,
+    /// Squares covered by pieces which can't be captured: the boss, and
+    /// pieces inside the player's territory.
+    pub secure: u32,
+    /// Squares covered by other pieces touching the opponent.
+    pub at_risk: u32,
+    /// Squares the pieces in hand would cover.
+    pub hand: u32,
+    /// Legal placements, whether or not it is the player's turn.
+    pub mobility: u32,
+    /// Empty squares in the boss's line of sight, where opponent pieces
+    /// can't face it.
+    pub boss_rays: u32,
+}
+
+/// How much each feature is worth, in hundredths of a point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Weights {
+    pub score: i32,
+    pub territory: i32,
+    pub secure: i32,
+    pub at_risk: i32,
+    pub hand: i32,
+    /// Counting moves is by far the slowest feature. It is skipped when zero.
+    pub mobility: i32,
+    pub boss_rays: i32,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            score: 100,
+            territory: 40,
+            secure: 20,
+            at_risk: -50,
+            hand: 10,
+            mobility: 2,
+            boss_rays: 5,
+        }
+    }
+}
+
+impl Weights {
+    /// Only the score, as the evaluation did originally.
+    pub fn score_only() -> Weights {
+        Weights {
+            score: 100,
+            territory: 0,
+            secure: 0,
+            at_risk: 0,
+            hand: 0,
+            mobility: 0,
+            boss_rays: 0,
+        }
+    }
+
+    #[inline]
+    pub fn apply(&self, f: &Features) -> i32 {
+        self.score * f.score as i32
+            + self.territory * f.territory as i32
+            + self.secure * f.secure as i32
+            + self.at_risk * f.at_risk as i32
+            + self.hand * f.hand as i32
+            + self.mobility * f.mobility as i32
+            + self.boss_rays * f.boss_rays as i32
+    }
+}
+
+#[inline]
+fn players(state: &State, color: Color) -> (&PlayerState, &PlayerState) {
+    match color {
+        Color::Black => (&state.board.black, &state.board.white),
+        Color::White => (&state.board.white, &state.board.black),
+    }
+}
+
+fn boss_rays(player: &PlayerState, empty: BitBoard) -> u32 {
+    let Some(boss) = player
+        .move_list
+        .iter()
+        .find(|m| m.get_piece() == PieceId::Boss)
+    else {
+        return 0;
+    };
+    let mut rays = Rays::new();
+    draw_boss_rays(&mut rays, *boss);
+    let sight = ALL_ORIENTATIONS
+        .iter()
+        .fold(EMPTY, |sight, o| sight | rays.get(*o));
+    (sight & empty).popcnt()
+}
+
+impl Features {
+    /// Every feature except mobility, which is left at zero.
+    pub fn without_mobility(state: &State, color: Color) -> Features {
+        let (player, other) = players(state, color);
+        let empty = !state.board.occupied();
+
+        let secure = player.move_list.iter().fold(EMPTY, |secure, m| {
+            match m.get_piece() == PieceId::Boss || m.mask().is_subset(player.owned) {
+                true => secure | m.mask(),
+                false => secure,
+            }
+        });
+        let exposed = other.occupied | other.owned;
+        let at_risk = player.move_list.iter().fold(EMPTY, |at_risk, m| {
+            match !m.mask().intersects(secure) && m.mask().get_adjacent_mask().intersects(exposed) {
+                true => at_risk | m.mask(),
+                false => at_risk,
+            }
+        });
+        let hand = player
+            .hand
+            .map(|piece| piece.piece_type_id().def().mask[0].count_ones())
+            .sum();
+
+        Features {
+            score: state.rules.score(&state.board, color),
+            territory: (player.owned & empty).popcnt(),
+            secure: secure.popcnt(),
+            at_risk: at_risk.popcnt(),
+            hand,
+            mobility: 0,
+            boss_rays: boss_rays(player, empty),
+        }
+    }
+
+    pub fn new(state: &State, color: Color) -> Features {
+        Features {
+            mobility: mobility(state, color),
+            ..Features::without_mobility(state, color)
+        }
+    }
+}
+
+/// Legal placements for `color`, as if it were their turn.
+pub fn mobility(state: &State, color: Color) -> u32 {
+    let mut counter = MoveCounter(0);
+    state
+        .board
+        .generate_moves(color, &state.rules, &mut counter);
+    counter.0 as u32
+}
+
+/// The evaluation for the player to move, in hundredths of a point.
+pub fn evaluate(state: &State, weights: &Weights) -> i32 {
+    let features = |color| match weights.mobility {
+        0 => Features::without_mobility(state, color),
+        _ => Features::new(state, color),
+    };
+    let black = weights.apply(&features(Color::Black));
+    let white = weights.apply(&features(Color::White));
+
+    // Komi is in half points
+    let score = black - white - state.rules.komi.half_points() * weights.score / 2;
+    match state.current {
+        Color::Black => score,
+        Color::White => -score,
+    }
+}
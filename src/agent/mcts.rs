@@ -1,43 +1,210 @@
-use crate::agent::Nego;
+use crate::agent::{
+    playout::Playout,
+    stop::StopToken,
+    time::Budget,
+    tree::{DEFAULT_MEMORY, MOST_MOVES},
+    Cut, Cutoff, Player, Position,
+};
 use crate::core::{game::State, r#move::Move};
 
-use minimax::{Game, MCTSOptions, MonteCarloTreeSearch, RolloutPolicy, Strategy};
+use minimax::{MCTSOptions, MonteCarloTreeSearch, RolloutPolicy, Strategy};
+use rand::rngs::ThreadRng;
 
-use rand::seq::SliceRandom;
+use std::time::{Duration, Instant};
 
-struct Policy;
+// Bounding the tree
+//
+// minimax-rs builds a new tree for every search, and expands a leaf with all
+// its moves once it has had `ROLLOUTS_BEFORE_EXPANDING` rollouts. A tree of
+// `n` rollouts therefore holds at most `n / (ROLLOUTS_BEFORE_EXPANDING + 1)`
+// expansions besides the root's. A search can be bounded by time or by
+// rollouts, but not both, so a move is searched as a series of trees of at
+// most `tree_rollouts(memory)` rollouts. A timed search gives each tree the
+// rollouts which fit in most of the time left, at the rate of the trees before
+// it, and cuts it short at the limit. Each tree votes for its move with its rollouts,
+// apart from one which was cut short. With the default budget a tree takes
+// nearly 100,000 rollouts, so most moves are a single tree.
+
+const ROLLOUTS_BEFORE_EXPANDING: u32 = 5;
+
+// More than a node of a minimax-rs tree takes: its move, three counters and
+// its children
+const NODE_BYTES: usize = 32;
+
+// A timed search starts with a tree of a single rollout, which measures the
+// rate. Later trees smaller than this aren't worth starting.
+const FEWEST_ROLLOUTS: u32 = 8;
+
+// How much of the time left a tree of a timed search is given, so that one
+// which runs slower than the trees before it isn't cut short
+const TREE_SHARE: f64 = 0.9;
+
+/// The most rollouts a tree is given within `memory` bytes, at least one.
+pub fn tree_rollouts(memory: usize) -> u32 {
+    let expansions = (memory / (MOST_MOVES * NODE_BYTES)).saturating_sub(1);
+    let rollouts = expansions.saturating_mul(ROLLOUTS_BEFORE_EXPANDING as usize + 1);
+    rollouts.clamp(1, u32::MAX as usize) as u32
+}
+
+/// The rollouts of `Mcts`, which play by `playout`.
+pub struct Policy {
+    playout: Playout,
+}
+
+impl Policy {
+    pub fn new(playout: Playout) -> Policy {
+        Policy { playout }
+    }
+}
 
 impl RolloutPolicy for Policy {
-    type G = Nego;
+    type G = Cut;
     fn random_move(
         &self,
-        state: &mut <Nego as Game>::S,
-        moves: &mut Vec<<Nego as Game>::M>,
-        rng: &mut rand::rngs::ThreadRng,
-    ) -> <Nego as Game>::M {
+        position: &mut Position,
+        moves: &mut Vec<Move>,
+        rng: &mut ThreadRng,
+    ) -> Move {
         assert!(moves.is_empty());
-        state.get_moves(moves);
-        // TODO:
-        // - wieghted random choice
-        // - increased weight for advantageous move
-        // - prefer moves that block potential connections
-        // - prefer moves that connect territory
-        // - prefer moves that create territory
-        // - add randomness for exploration?
-        *moves.choose(rng).unwrap()
-    }
-}
-
-pub fn step(state: &State, timeout: std::time::Duration) -> Option<Move> {
-    let opts = MCTSOptions::default()
-        .verbose()
-        .with_max_rollout_depth(1000)
-        .with_rollouts_before_expanding(5);
-
-    let mut strategy: MonteCarloTreeSearch<Nego> =
-        MonteCarloTreeSearch::new_with_policy(opts, Box::new(Policy));
-
-    // strategy.set_max_rollouts(40000);
-    strategy.set_timeout(timeout);
-    strategy.choose_move(state)
+        position.state.get_moves(moves);
+        self.playout.choose(&position.state, moves, rng).unwrap()
+    }
+}
+
+/// Statistics from the last call to `choose_move`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    pub rollouts: u32,
+    /// The trees the rollouts were split between.
+    pub trees: u32,
+    /// The most rollouts in one tree.
+    pub tree_rollouts: u32,
+}
+
+/// Monte Carlo tree search from minimax-rs. The trees are built from scratch
+/// for each move. It can't be seeded: minimax-rs picks between children which
+/// score the same with random numbers of its own.
+pub struct Mcts {
+    pub playout: Playout,
+    /// The most memory a tree may use, in bytes.
+    pub memory: usize,
+    stop: StopToken,
+    stats: SearchStats,
+}
+
+impl Default for Mcts {
+    fn default() -> Self {
+        Mcts {
+            playout: Playout::default(),
+            memory: DEFAULT_MEMORY,
+            stop: StopToken::new(),
+            stats: SearchStats::default(),
+        }
+    }
+}
+
+impl Mcts {
+    pub fn stats(&self) -> SearchStats {
+        self.stats
+    }
+
+    // The rollouts for the next tree, if there is to be one
+    fn next_tree(&self, budget: &Budget, start: Instant) -> Option<u32> {
+        let done = self.stats.rollouts;
+        let rollouts = match budget.iterations {
+            Some(rollouts) => rollouts.saturating_sub(done),
+            None if done == 0 => 1,
+            None => {
+                let elapsed = start.elapsed().as_secs_f64();
+                let left = budget.target.as_secs_f64() - elapsed;
+                (done as f64 * left * TREE_SHARE / elapsed) as u32
+            }
+        };
+        match rollouts {
+            _ if done == 0 => Some(rollouts.clamp(1, tree_rollouts(self.memory))),
+            _ if self.stop.is_stopped() => None,
+            _ if budget.iterations.is_none() && rollouts < FEWEST_ROLLOUTS => None,
+            0 => None,
+            _ => Some(rollouts.min(tree_rollouts(self.memory))),
+        }
+    }
+
+    // A tree of `rollouts` rollouts, unless it is cut short
+    fn search(&mut self, position: &Position, rollouts: u32) -> Option<Move> {
+        let opts = MCTSOptions::default()
+            .with_max_rollout_depth(1000)
+            .with_rollouts_before_expanding(ROLLOUTS_BEFORE_EXPANDING);
+        let policy = Policy::new(self.playout);
+        let mut strategy: MonteCarloTreeSearch<Cut> =
+            MonteCarloTreeSearch::new_with_policy(opts, Box::new(policy));
+        strategy.set_max_rollouts(rollouts);
+        strategy.choose_move(position)
+    }
+}
+
+impl Player for Mcts {
+    fn name(&self) -> String {
+        "Mcts".into()
+    }
+
+    fn choose_move(&mut self, state: &State, budget: Duration) -> Option<Move> {
+        self.choose_move_within(state, Budget::fixed(budget))
+    }
+
+    // A stopped search only makes a single rollout. A tree which is cut short
+    // only counts if it is the first.
+    fn choose_move_within(&mut self, state: &State, budget: Budget) -> Option<Move> {
+        let start = Instant::now();
+        let (budget, cutoff) = match self.stop.is_stopped() {
+            true => (Budget::iterations(1), Cutoff::default()),
+            false => (
+                budget,
+                Cutoff {
+                    stop: self.stop.clone(),
+                    deadline: start.checked_add(budget.max),
+                },
+            ),
+        };
+        let position = Position {
+            state: state.clone(),
+            cutoff,
+        };
+        self.stats = SearchStats::default();
+
+        // Moves with their votes, in the order they were first chosen
+        let mut votes: Vec<(Move, u32)> = Vec::new();
+        while let Some(rollouts) = self.next_tree(&budget, start) {
+            let Some(m) = self.search(&position, rollouts) else {
+                break;
+            };
+            if position.cutoff.passed() {
+                if votes.is_empty() {
+                    votes.push((m, rollouts));
+                }
+                break;
+            }
+            match votes.iter_mut().find(|(voted, _)| *voted == m) {
+                Some((_, weight)) => *weight = weight.saturating_add(rollouts),
+                None => votes.push((m, rollouts)),
+            }
+            self.stats.rollouts = self.stats.rollouts.saturating_add(rollouts);
+            self.stats.trees += 1;
+            self.stats.tree_rollouts = self.stats.tree_rollouts.max(rollouts);
+        }
+        log::info!(
+            "mcts: {} rollouts in {} trees",
+            self.stats.rollouts,
+            self.stats.trees
+        );
+
+        // The first of the moves with the most votes
+        votes
+            .into_iter()
+            .reduce(|best, vote| if vote.1 > best.1 { vote } else { best })
+            .map(|(m, _)| m)
+    }
+
+    fn set_stop(&mut self, stop: StopToken) {
+        self.stop = stop;
+    }
 }
@@ -1,128 +1,565 @@
-use std::cmp::Ordering;
-use std::sync::{Mutex, MutexGuard, OnceLock};
-
-use crate::core::{game::Color, game::State, r#move::Move};
-
-use mcts::game::{Game, PlayerIndex};
-use mcts::strategies::mcts::{backprop, select, simulate, strategy, Strategy};
-use mcts::strategies::mcts::{SearchConfig, TreeSearch};
-use mcts::strategies::Search;
-
-// #[derive(Clone, Copy, Default)]
-// struct NegoStrategy;
-
-// impl Strategy<Nego> for NegoStrategy {
-//     type Select = select::Rave;
-//     type Simulate = simulate::DecisiveMove<Nego, simulate::EpsilonGreedy<Nego, simulate::Mast>>;
-//     type Backprop = backprop::Classic;
-//     type FinalAction = select::MaxAvgScore;
-
-//     fn config() -> SearchConfig<Nego, Self> {
-//         SearchConfig::new()
-//             .expand_threshold(1)
-//             .use_transpositions(true)
-//             .select(
-//                 select::Rave::default()
-//                     .threshold(700)
-//                     .ucb(select::RaveUcb::Ucb1Tuned {
-//                         exploration_constant: 1.625,
-//                     })
-//                     .schedule(select::RaveSchedule::MinMSE { bias: 10e-3 }),
-//             )
-//             .verbose(true)
-//     }
-
-//     fn friendly_name() -> String {
-//         "mcts: amaf/dm+mast/maxavg".into()
-//     }
-// }
-
-type NegoStrategy = strategy::Ucb1;
-
-type NegoTS = TreeSearch<Nego, NegoStrategy>;
-
-static MCTS_CELL: OnceLock<Mutex<NegoTS>> = OnceLock::new();
-
-fn get_agent() -> MutexGuard<'static, NegoTS> {
-    MCTS_CELL
-        .get_or_init(|| Mutex::new(NegoTS::new().config(SearchConfig::new().verbose(true))))
-        .lock()
-        .unwrap()
+use crate::{
+    agent::{
+        analysis::{Analysis, AnalysisOptions, Analyze, Line, Score},
+        playout::Playout,
+        stop::StopToken,
+        time::Budget,
+        tree::{self, Memory, Widening, DEFAULT_MEMORY},
+        Player,
+    },
+    core::{
+        game::{Color, State},
+        r#move::Move,
+    },
+};
+
+use rand::{rngs::SmallRng, SeedableRng};
+
+use std::cmp::Reverse;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// UCT which keeps its tree from one move to the next
+//
+// After choosing a move, the tree is kept, and at the next move the subtree
+// for the position actually reached becomes the new tree. The visits spent on
+// the moves that were played aren't thrown away.
+//
+// With pondering on, the search carries on in a background thread from the
+// position the opponent has to answer. When their move arrives the thread is
+// stopped, and the subtree below their move is kept as usual. The tree stays
+// within its memory budget however long the opponent takes (see `tree.rs`).
+
+const ROOT: usize = 0;
+const NO_PARENT: usize = usize::MAX;
+
+struct Node {
+    m: Move,
+    parent: usize,
+    // The player who made `m`. Rewards are from their side.
+    mover: Color,
+    children: Vec<usize>,
+    // Generated when the node is first expanded, best last
+    untried: Vec<Move>,
+    expanded: bool,
+    visits: u32,
+    reward: f64,
+}
+
+impl Node {
+    fn new(m: Move, parent: usize, state: &State) -> Node {
+        Node {
+            m,
+            parent,
+            mover: state.current.next(),
+            children: Vec::new(),
+            untried: Vec::new(),
+            expanded: false,
+            visits: 0,
+            reward: 0.,
+        }
+    }
+}
+
+struct Tree {
+    // The position at the root
+    state: State,
+    nodes: Vec<Node>,
+    memory: Memory,
+}
+
+impl Tree {
+    fn new(state: &State, memory: usize) -> Tree {
+        Tree {
+            state: state.clone(),
+            nodes: vec![Node::new(Move::PASS, NO_PARENT, state)],
+            memory: Memory::new(memory),
+        }
+    }
+
+    fn select(&self, parent: usize, exploration: f64) -> usize {
+        let node = &self.nodes[parent];
+        let log_visits = (node.visits as f64).ln();
+        let ucb = |child: &Node| {
+            let visits = child.visits as f64;
+            child.reward / visits + exploration * (log_visits / visits).sqrt()
+        };
+        *node
+            .children
+            .iter()
+            .max_by(|a, b| ucb(&self.nodes[**a]).total_cmp(&ucb(&self.nodes[**b])))
+            .unwrap()
+    }
+
+    fn backpropagate(&mut self, mut node: usize, winner: Option<Color>) {
+        while node != NO_PARENT {
+            let n = &mut self.nodes[node];
+            n.visits += 1;
+            n.reward += match winner {
+                Some(color) if color == n.mover => 1.,
+                Some(_) => 0.,
+                None => 0.5,
+            };
+            node = n.parent;
+        }
+    }
+
+    fn is_settled(&self) -> bool {
+        let children = self.nodes[ROOT].children.iter();
+        tree::is_settled(
+            children.map(|child| (self.nodes[*child].visits, self.nodes[*child].reward)),
+        )
+    }
+
+    // The most visited move at the root
+    fn best(&self) -> Option<usize> {
+        self.most_visited(ROOT)
+    }
+
+    fn most_visited(&self, node: usize) -> Option<usize> {
+        self.nodes[node]
+            .children
+            .iter()
+            .copied()
+            .max_by_key(|child| self.nodes[*child].visits)
+    }
+
+    // The deepest node below the root. Parents come before their children.
+    fn depth(&self) -> u32 {
+        let mut depths = vec![0; self.nodes.len()];
+        for node in 1..self.nodes.len() {
+            depths[node] = depths[self.nodes[node].parent] + 1;
+        }
+        depths.into_iter().max().unwrap_or(0)
+    }
+
+    // The `lines` most visited moves at the root, each followed by the most
+    // visited replies
+    fn analysis(&self, lines: usize) -> Analysis {
+        let mut children = self.nodes[ROOT].children.clone();
+        children.sort_by_key(|child| Reverse(self.nodes[*child].visits));
+        let lines = children
+            .into_iter()
+            .take(lines)
+            .map(|child| {
+                let node = &self.nodes[child];
+                let mut pv = vec![node.m];
+                let mut next = child;
+                while let Some(reply) = self.most_visited(next) {
+                    pv.push(self.nodes[reply].m);
+                    next = reply;
+                }
+                Line {
+                    m: node.m,
+                    score: Score::WinRate(node.reward / node.visits.max(1) as f64),
+                    visits: Some(node.visits),
+                    pv,
+                }
+            })
+            .collect();
+        Analysis {
+            lines,
+            depth: self.depth(),
+            ..Analysis::default()
+        }
+    }
+
+    // Grow the tree while `more` says so, given the iterations so far, until
+    // `stop` is stopped. Returns the number of iterations.
+    fn search(
+        &mut self,
+        playout: &Playout,
+        exploration: f64,
+        widening: Option<Widening>,
+        mut more: impl FnMut(u32) -> bool,
+        stop: &StopToken,
+        rng: &mut SmallRng,
+    ) -> u32 {
+        let mut iterations = 0;
+        while more(iterations) && !stop.is_stopped() {
+            iterations += 1;
+
+            let mut state = self.state.clone();
+            let mut node = ROOT;
+            loop {
+                if !self.nodes[node].expanded {
+                    if !self.memory.has_room(&self.nodes) {
+                        break;
+                    }
+                    let untried = tree::untried(&state, playout);
+                    self.memory.add(&untried);
+                    self.nodes[node].untried = untried;
+                    self.nodes[node].expanded = true;
+                }
+                let n = &self.nodes[node];
+                let limit = widening.map_or(usize::MAX, |widening| widening.children(n.visits));
+                if n.children.len() < limit
+                    && !n.untried.is_empty()
+                    && self.memory.has_room(&self.nodes)
+                {
+                    let m = self.nodes[node].untried.pop().unwrap();
+                    state.apply(m);
+                    let child = self.nodes.len();
+                    self.nodes.push(Node::new(m, node, &state));
+                    self.memory.push(&mut self.nodes[node].children, child);
+                    node = child;
+                    break;
+                }
+                if self.nodes[node].children.is_empty() {
+                    break;
+                }
+                node = self.select(node, exploration);
+                state.apply(self.nodes[node].m);
+            }
+
+            let end = playout.playout(state, rng);
+            self.backpropagate(node, end.result().and_then(|result| result.winner));
+        }
+        iterations
+    }
+
+    // The node for `target` if it is the root or one ply below it. The tree
+    // is kept from after the agent's own move, so this is the opponent's reply.
+    fn find(&self, target: &State) -> Option<(usize, State)> {
+        if self.state.hash == target.hash && self.state.current == target.current {
+            return Some((ROOT, self.state.clone()));
+        }
+        // A move can't capture the piece it places
+        let played = |m: Move, mover: Color| {
+            let player = match mover {
+                Color::Black => &target.board.black,
+                Color::White => &target.board.white,
+            };
+            m.is_pass() || player.move_list.contains(&m)
+        };
+        self.nodes[ROOT]
+            .children
+            .iter()
+            .filter(|child| played(self.nodes[**child].m, self.nodes[**child].mover))
+            .map(|child| {
+                let mut state = self.state.clone();
+                state.apply(self.nodes[*child].m);
+                (*child, state)
+            })
+            .find(|(_, state)| state.hash == target.hash && state.current == target.current)
+    }
+
+    // The subtree below `node`, which is the position `state`
+    fn subtree(mut self, node: usize, state: State) -> Tree {
+        let mut memory = Memory::new(self.memory.budget);
+        let mut nodes: Vec<Node> = Vec::new();
+        let mut stack = vec![(node, NO_PARENT)];
+        while let Some((old, parent)) = stack.pop() {
+            let index = nodes.len();
+            let n = &mut self.nodes[old];
+            let children = std::mem::take(&mut n.children);
+            nodes.push(Node {
+                m: n.m,
+                parent,
+                mover: n.mover,
+                children: Vec::with_capacity(children.len()),
+                untried: std::mem::take(&mut n.untried),
+                expanded: n.expanded,
+                visits: n.visits,
+                reward: n.reward,
+            });
+            memory.add(&nodes[index].children);
+            memory.add(&nodes[index].untried);
+            if parent != NO_PARENT {
+                nodes[parent].children.push(index);
+            }
+            stack.extend(children.into_iter().rev().map(|child| (child, index)));
+        }
+        Tree {
+            state,
+            nodes,
+            memory,
+        }
+    }
+
+    // This tree re-rooted at `state`, if it is in the tree
+    fn reroot(self, state: &State) -> Option<Tree> {
+        match self.find(state)? {
+            (ROOT, _) => Some(self),
+            (node, state) => Some(self.subtree(node, state)),
+        }
+    }
+}
+
+/// Statistics from the last call to `choose_move` or `analyze`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    pub iterations: u32,
+    /// The size of the tree after the search.
+    pub nodes: usize,
+    pub bytes: usize,
+    /// Whether the tree reached its memory budget.
+    pub full: bool,
+    /// Nodes kept from earlier searches, including pondering.
+    pub reused: usize,
+    /// Iterations spent pondering since the move before.
+    pub pondered: u32,
+}
+
+struct Ponder {
+    stop: StopToken,
+    handle: JoinHandle<(Tree, u32)>,
 }
 
-pub fn step(state: &State, timeout: std::time::Duration) -> Option<Move> {
-    let mut mcts = get_agent();
-    mcts.config.max_time = timeout;
-    Some(mcts.choose_action(state))
+/// UCT with the shared playout policy, keeping its tree between moves.
+pub struct Mcts2 {
+    pub playout: Playout,
+    pub exploration: f64,
+    /// `None` lets every node have all its moves as children.
+    pub widening: Option<Widening>,
+    /// The most memory the tree may use, in bytes.
+    pub memory: usize,
+    /// Keep searching in the background during the opponent's turn.
+    pub ponder: bool,
+    tree: Option<Tree>,
+    pondering: Option<Ponder>,
+    pondered: u32,
+    stats: SearchStats,
+    rng: SmallRng,
+    stop: StopToken,
 }
 
-impl PlayerIndex for Color {
-    fn to_index(&self) -> usize {
-        *self as usize
+impl Mcts2 {
+    pub fn new() -> Self {
+        Self::with_playout(Playout::default())
+    }
+
+    pub fn with_playout(playout: Playout) -> Self {
+        Self {
+            playout,
+            exploration: std::f64::consts::SQRT_2,
+            widening: Some(Widening::default()),
+            memory: DEFAULT_MEMORY,
+            ponder: false,
+            tree: None,
+            pondering: None,
+            pondered: 0,
+            stats: SearchStats::default(),
+            rng: SmallRng::from_entropy(),
+            stop: StopToken::new(),
+        }
+    }
+
+    /// As `new`, but pondering during the opponent's turn.
+    pub fn pondering() -> Self {
+        let mut player = Self::new();
+        player.ponder = true;
+        player
+    }
+
+    pub fn stats(&self) -> SearchStats {
+        self.stats
+    }
+
+    /// Whether a background search is running.
+    pub fn is_pondering(&self) -> bool {
+        self.pondering
+            .as_ref()
+            .is_some_and(|ponder| !ponder.handle.is_finished())
+    }
+
+    // Stop the background search and take back its tree
+    fn stop_pondering(&mut self) {
+        if let Some(ponder) = self.pondering.take() {
+            ponder.stop.stop();
+            let (tree, iterations) = ponder.handle.join().expect("pondering panicked");
+            self.tree = Some(tree);
+            self.pondered += iterations;
+        }
+    }
+
+    // The kept tree re-rooted at `state`, or a new one, and the number of
+    // nodes kept
+    fn take_tree(&mut self, state: &State) -> (Tree, usize) {
+        let (mut tree, reused) = match self.tree.take().and_then(|tree| tree.reroot(state)) {
+            Some(tree) => {
+                let reused = tree.nodes.len();
+                (tree, reused)
+            }
+            None => (Tree::new(state, self.memory), 0),
+        };
+        tree.memory.budget = self.memory;
+        (tree, reused)
+    }
+
+    // Keep and log the statistics of a search
+    fn record(&mut self, tree: &Tree, iterations: u32, reused: usize) {
+        let stats = tree.memory.stats(&tree.nodes);
+        self.stats = SearchStats {
+            iterations,
+            nodes: stats.nodes,
+            bytes: stats.bytes,
+            full: stats.full,
+            reused,
+            pondered: std::mem::take(&mut self.pondered),
+        };
+        log::info!(
+            "mcts2: {} iterations, {} nodes in {} MB{} ({} reused, {} pondered iterations)",
+            self.stats.iterations,
+            self.stats.nodes,
+            self.stats.bytes >> 20,
+            if self.stats.full { " (full)" } else { "" },
+            self.stats.reused,
+            self.stats.pondered
+        );
+    }
+
+    fn start_pondering(&mut self, tree: Tree) {
+        let stop = StopToken::new();
+        let (playout, exploration, widening) = (self.playout, self.exploration, self.widening);
+        let handle = thread::spawn({
+            let stop = stop.clone();
+            move || {
+                let mut tree = tree;
+                let mut rng = SmallRng::from_entropy();
+                let iterations =
+                    tree.search(&playout, exploration, widening, |_| true, &stop, &mut rng);
+                (tree, iterations)
+            }
+        });
+        self.pondering = Some(Ponder { stop, handle });
     }
 }
 
-impl std::fmt::Display for State {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        std::fmt::Debug::fmt(&self, f)
+impl Default for Mcts2 {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-#[derive(Clone)]
-struct Nego;
+impl Drop for Mcts2 {
+    fn drop(&mut self) {
+        self.stop_pondering();
+    }
+}
 
-impl Game for Nego {
-    type S = State;
-    type A = Move;
-    type P = Color;
+impl Analyze for Mcts2 {
+    // The tree is kept at `state`, so choosing a move from it afterwards
+    // carries on from the analysis
+    fn analyze(
+        &mut self,
+        state: &State,
+        options: &AnalysisOptions,
+        report: &mut dyn FnMut(&Analysis),
+    ) -> Analysis {
+        let start = Instant::now();
+        self.stop_pondering();
+        let (mut tree, reused) = self.take_tree(state);
+        let mut iterations = 0;
+        let mut analysis = tree.analysis(options.lines);
+        let interval = options.interval.max(Duration::from_millis(1));
+        while state.has_moves() && start.elapsed() < options.time && !self.stop.is_stopped() {
+            let deadline = (Instant::now() + interval).min(start + options.time);
+            iterations += tree.search(
+                &self.playout,
+                self.exploration,
+                self.widening,
+                |_| Instant::now() < deadline,
+                &self.stop,
+                &mut self.rng,
+            );
+            analysis = Analysis {
+                nodes: iterations as u64,
+                elapsed: start.elapsed(),
+                ..tree.analysis(options.lines)
+            };
+            if start.elapsed() < options.time && !self.stop.is_stopped() {
+                report(&analysis);
+            }
+        }
 
-    fn apply(mut state: Self::S, action: &Self::A) -> Self::S {
-        state.apply(*action);
-        state
+        self.record(&tree, iterations, reused);
+        self.tree = Some(tree);
+        analysis
     }
+}
 
-    fn generate_actions(state: &Self::S, actions: &mut Vec<Self::A>) {
-        state.get_moves(actions);
+impl Player for Mcts2 {
+    fn name(&self) -> String {
+        "Mcts2".into()
     }
 
-    fn is_terminal(state: &Self::S) -> bool {
-        !state.has_moves()
+    fn new_game(&mut self, _state: &State) {
+        self.stop_pondering();
+        self.tree = None;
     }
 
-    fn notation(_: &Self::S, m: &Self::A) -> String {
-        m.notation()
+    fn choose_move(&mut self, state: &State, budget: Duration) -> Option<Move> {
+        self.choose_move_within(state, Budget::fixed(budget))
     }
 
-    fn winner(state: &Self::S) -> Option<Self::P> {
-        if state.has_moves() {
+    fn choose_move_within(&mut self, state: &State, budget: Budget) -> Option<Move> {
+        let start = Instant::now();
+        self.stop_pondering();
+        if !state.has_moves() {
             return None;
         }
 
-        // We use 0.5 komi to prevent draws
-        let komi = 1;
-        let b = state.board.black.occupied.popcnt() * 2;
-        let w = state.board.white.occupied.popcnt() * 2 + komi;
+        let (mut tree, reused) = self.take_tree(state);
+        let mut search = |tree: &mut Tree, more: &mut dyn FnMut(u32) -> bool| {
+            tree.search(
+                &self.playout,
+                self.exploration,
+                self.widening,
+                more,
+                &self.stop,
+                &mut self.rng,
+            )
+        };
+        let deadline = |deadline: Instant| move |_| Instant::now() < deadline;
+        let iterations = match budget.iterations {
+            Some(n) => search(&mut tree, &mut |iterations| iterations < n),
+            None => {
+                let mut iterations = search(&mut tree, &mut deadline(start + budget.target));
+
+                // Carry on while the search hasn't settled, checking now and then
+                let interval = (budget.target / 10).max(Duration::from_millis(1));
+                while start.elapsed() < budget.max && !tree.is_settled() && !self.stop.is_stopped()
+                {
+                    let until = (Instant::now() + interval).min(start + budget.max);
+                    iterations += search(&mut tree, &mut deadline(until));
+                }
+                iterations
+            }
+        };
+
+        self.record(&tree, iterations, reused);
 
-        match b.cmp(&w) {
-            Ordering::Greater => Some(Color::Black),
-            Ordering::Less => Some(Color::White),
-            Ordering::Equal => None,
+        let Some(best) = tree.best() else {
+            return tree::untried(state, &self.playout).last().copied();
+        };
+        let m = tree.nodes[best].m;
+        let mut after = state.clone();
+        after.apply(m);
+        let tree = tree.subtree(best, after);
+        match self.ponder && tree.state.has_moves() && !self.stop.is_stopped() {
+            true => self.start_pondering(tree),
+            false => self.tree = Some(tree),
         }
+        Some(m)
     }
 
-    fn player_to_move(state: &Self::S) -> Self::P {
-        state.current
+    fn opponent_moved(&mut self, _state: &State, _m: Move) {
+        self.stop_pondering();
     }
 
-    fn zobrist_hash(state: &Self::S) -> u64 {
-        state.hash
+    fn stop(&mut self) {
+        self.stop_pondering();
+        self.tree = None;
+    }
+
+    fn set_stop(&mut self, stop: StopToken) {
+        self.stop = stop;
     }
-}
 
-// TODO for rollouts:
-// - wieghted random choice
-// - increased weight for advantageous move
-// - prefer moves that block potential connections
-// - prefer moves that connect territory
-// - prefer moves that create territory
-// - add randomness for exploration?
+    // Pondering uses random numbers of its own, so only a player which
+    // doesn't ponder is reproducible
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = SmallRng::seed_from_u64(seed);
+    }
+}
@@ -1,20 +1,81 @@
+pub mod alphabeta;
+pub mod analysis;
+pub mod book;
+pub mod endgame;
+pub mod eval;
 pub mod mcts;
 pub mod mcts2;
 pub mod negamax;
+pub mod playout;
+pub mod solver;
+pub mod spec;
+pub mod stop;
+pub mod time;
+pub mod tournament;
+pub mod tree;
 
-use crate::core::{
-    game::{Color, State},
-    r#move::Move,
-};
+use crate::core::{game::State, r#move::Move};
+
+use stop::StopToken;
+use time::{Budget, TimeControl};
 
 use minimax::Game;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use std::time::{Duration, Instant};
+
+/// A computer player.
+///
+/// Each instance owns its own search state, so any number of players (including
+/// several configured differently from the same search) can take part in
+/// different games at the same time.
+pub trait Player: Send {
+    /// Describes the player in game records and logs.
+    fn name(&self) -> String;
+
+    /// Called before the first move of a game, which may not start from the
+    /// initial position.
+    fn new_game(&mut self, _state: &State) {}
+
+    /// Choose a move for the player to move in `state`, taking about `budget`
+    /// to do so. Returns `None` when there are no legal moves.
+    fn choose_move(&mut self, state: &State, budget: Duration) -> Option<Move>;
+
+    /// As `choose_move`, with a target time and a limit. Players which can
+    /// tell when their search has settled may go past the target, up to the
+    /// limit; the others stop at the target.
+    fn choose_move_within(&mut self, state: &State, budget: Budget) -> Option<Move> {
+        self.choose_move(state, budget.target)
+    }
+
+    /// Called with the opponent's move and the position after it.
+    fn opponent_moved(&mut self, _state: &State, _m: Move) {}
+
+    /// Called when the game is over or abandoned. Any work carried on between
+    /// moves should be stopped.
+    fn stop(&mut self) {}
+
+    /// Share `stop` with the player's searches. Once it is stopped, a search
+    /// returns the best move found so far, and later ones return at once until
+    /// another token is set. Players which don't search can ignore it.
+    fn set_stop(&mut self, _stop: StopToken) {}
+
+    /// Seed the player's random numbers. With a seed and a budget of
+    /// iterations (`Budget::iterations`), the player chooses the same moves on
+    /// every run. Players without randomness of their own ignore it, and so
+    /// does `mcts::Mcts`, which can't be seeded. Searching on several threads
+    /// or pondering isn't reproducible either way.
+    fn set_seed(&mut self, _seed: u64) {}
+}
 
-#[derive(Copy, Clone, Debug)]
+/// The built in players, with the time each one takes per move.
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Agent {
-    Parallel(std::time::Duration),
-    Iterative(std::time::Duration),
-    Mcts(std::time::Duration),
-    Mcts2(std::time::Duration),
+    Parallel(Duration),
+    Iterative(Duration),
+    Mcts(Duration),
+    Mcts2(Duration),
+    Solver(Duration),
     Random,
     Human,
 }
@@ -28,25 +89,62 @@ impl Agent {
         }
     }
 
-    pub fn step(&self, state: &mut State) -> Option<Move> {
-        if Nego::get_winner(state).is_some() {
-            return None;
+    /// The time given to each move.
+    pub fn budget(&self) -> Duration {
+        match self {
+            Agent::Parallel(timeout)
+            | Agent::Iterative(timeout)
+            | Agent::Mcts(timeout)
+            | Agent::Mcts2(timeout)
+            | Agent::Solver(timeout) => *timeout,
+            Agent::Random | Agent::Human => Duration::ZERO,
         }
+    }
 
-        let result = match self {
-            Agent::Parallel(timeout) => negamax::step_parallel(state, *timeout),
-            Agent::Iterative(timeout) => negamax::step_iterative(state, *timeout),
-            Agent::Mcts(timeout) => mcts::step(state, *timeout),
-            Agent::Mcts2(timeout) => mcts2::step(state, *timeout),
-            Agent::Random => step_random(state),
-            Agent::Human => None,
-        };
-
-        if let Some(m) = result {
-            state.apply(m);
+    /// The time control the agent was given: `budget` for every move. Random
+    /// doesn't search, so it has no limit.
+    pub fn time_control(&self) -> TimeControl {
+        match self {
+            Agent::Random => TimeControl::PerMove(Duration::MAX),
+            _ => TimeControl::PerMove(self.budget()),
         }
-        result
     }
+
+    /// A new instance of the player with the default settings, or `None` for a
+    /// human (see `AgentSpec::player`).
+    pub fn player(&self) -> Option<Box<dyn Player>> {
+        spec::AgentSpec::from(*self).player()
+    }
+
+    /// A new instance of the agent's search for analysis, or `None` for the
+    /// agents which can't show their lines.
+    pub fn analyzer(&self) -> Option<Box<dyn analysis::Analyze>> {
+        match self {
+            Agent::Parallel(_) => Some(Box::new(negamax::Parallel::new())),
+            Agent::Iterative(_) => Some(Box::new(negamax::Iterative::new())),
+            Agent::Mcts2(_) => Some(Box::new(mcts2::Mcts2::new())),
+            Agent::Solver(_) => Some(Box::new(solver::Solver::default())),
+            Agent::Mcts(_) | Agent::Random | Agent::Human => None,
+        }
+    }
+}
+
+/// Choose a move with `player` and apply it to `state`.
+pub fn step(player: &mut dyn Player, state: &mut State, budget: Duration) -> Option<Move> {
+    step_within(player, state, Budget::fixed(budget))
+}
+
+/// As `step`, with a target time and a limit.
+pub fn step_within(player: &mut dyn Player, state: &mut State, budget: Budget) -> Option<Move> {
+    if Nego::get_winner(state).is_some() {
+        return None;
+    }
+
+    let result = player.choose_move_within(state, budget);
+    if let Some(m) = result {
+        state.apply(m);
+    }
+    result
 }
 
 pub struct Nego;
@@ -62,22 +160,12 @@ impl minimax::Game for Nego {
 
     #[inline]
     fn get_winner(state: &State) -> Option<minimax::Winner> {
-        if state.has_moves() {
-            return None;
-        }
-
-        // We use 0.5 komi to prevent draws
-        let komi = 1;
-        let b = state.board.black.occupied.popcnt() * 2;
-        let w = state.board.white.occupied.popcnt() * 2 + komi;
-
-        if b == w {
-            Some(minimax::Winner::Draw)
-        } else if b > w && state.current == Color::White {
-            Some(minimax::Winner::PlayerJustMoved)
-        } else {
-            Some(minimax::Winner::PlayerToMove)
-        }
+        let result = state.result()?;
+        Some(match result.winner {
+            None => minimax::Winner::Draw,
+            Some(color) if color == state.current => minimax::Winner::PlayerToMove,
+            Some(_) => minimax::Winner::PlayerJustMoved,
+        })
     }
 
     #[inline]
@@ -92,6 +180,14 @@ impl minimax::Game for Nego {
         state.hash
     }
 
+    // minimax-rs fills its countermove table with the null move, so it is
+    // needed even when the rules don't allow passing. Null move pruning is
+    // left off since passing may not be legal.
+    #[inline]
+    fn null_move(_: &State) -> Option<Move> {
+        Some(Move::PASS)
+    }
+
     fn notation(_: &State, m: Move) -> Option<String> {
         Some(m.notation())
     }
@@ -107,10 +203,124 @@ impl minimax::Game for Nego {
     }
 }
 
-pub fn step_random(state: &State) -> Option<Move> {
-    use rand::Rng;
-    let mut rng = rand::thread_rng();
+// Cutting searches short
+//
+// minimax-rs can only be stopped by its own timeout, and a search has either a
+// timeout or a limit on its depth or rollouts, not both. So the positions the
+// minimax-rs searches see carry a `Cutoff` of our own. Once it is stopped or
+// past its deadline, every position counts as won by the player who just
+// moved: negamax unwinds at once, and `Mcts` soon proves a result for the root
+// of its tree, which ends every later rollout there. What was searched since
+// is worthless, and is thrown away.
+
+#[derive(Clone, Default)]
+pub(crate) struct Cutoff {
+    pub(crate) stop: StopToken,
+    pub(crate) deadline: Option<Instant>,
+}
+
+impl Cutoff {
+    #[inline]
+    pub(crate) fn passed(&self) -> bool {
+        self.stop.is_stopped()
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+/// A position in a minimax-rs search.
+#[derive(Clone)]
+pub struct Position {
+    pub state: State,
+    pub(crate) cutoff: Cutoff,
+}
+
+/// `Nego` for searches which can be cut short.
+pub struct Cut;
+
+impl minimax::Game for Cut {
+    type S = Position;
+    type M = Move;
+
+    #[inline]
+    fn generate_moves(position: &Position, moves: &mut Vec<Move>) {
+        Nego::generate_moves(&position.state, moves);
+    }
+
+    #[inline]
+    fn get_winner(position: &Position) -> Option<minimax::Winner> {
+        match position.cutoff.passed() {
+            true => Some(minimax::Winner::PlayerJustMoved),
+            false => Nego::get_winner(&position.state),
+        }
+    }
+
+    #[inline]
+    fn apply(position: &mut Position, m: Move) -> Option<Position> {
+        let mut position = position.clone();
+        position.state.apply(m);
+        Some(position)
+    }
+
+    #[inline]
+    fn zobrist_hash(position: &Position) -> u64 {
+        position.state.hash
+    }
+
+    #[inline]
+    fn null_move(position: &Position) -> Option<Move> {
+        Nego::null_move(&position.state)
+    }
+
+    fn notation(position: &Position, m: Move) -> Option<String> {
+        Nego::notation(&position.state, m)
+    }
+
+    fn table_index(m: Move) -> u16 {
+        Nego::table_index(m)
+    }
+
+    fn max_table_index() -> u16 {
+        Nego::max_table_index()
+    }
+}
+
+/// Plays uniformly random legal moves.
+pub struct Random {
+    rng: SmallRng,
+}
+
+impl Random {
+    pub fn new() -> Self {
+        Random {
+            rng: SmallRng::from_entropy(),
+        }
+    }
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Player for Random {
+    fn name(&self) -> String {
+        "Random".into()
+    }
+
+    fn choose_move(&mut self, state: &State, _budget: Duration) -> Option<Move> {
+        random_move(state, &mut self.rng)
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = SmallRng::seed_from_u64(seed);
+    }
+}
 
+/// A uniformly random legal move.
+pub fn random_move<R: Rng + ?Sized>(state: &State, rng: &mut R) -> Option<Move> {
     let mut ms = Vec::new();
     state.get_moves(&mut ms);
     if ms.is_empty() {
@@ -1,5 +1,13 @@
 use crate::{
-    agent::Nego,
+    agent::{
+        alphabeta::{AlphaBeta, Leaves},
+        analysis::{Analysis, AnalysisOptions, Analyze, Line, Score},
+        eval::{self, Weights},
+        playout::Playout,
+        stop::StopToken,
+        time::Budget,
+        Cut, Cutoff, Player, Position,
+    },
     core::{
         game::{Color, State},
         r#move::Move,
@@ -8,31 +16,33 @@ use crate::{
 
 use minimax::{IterativeOptions, IterativeSearch, ParallelOptions, ParallelSearch, Strategy};
 
-use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::cmp::Reverse;
+use std::time::{Duration, Instant};
+
+// Keeps evaluations well clear of the values minimax uses for wins and losses
+const EVAL_LIMIT: i32 = 10_000;
 
 #[derive(Clone)]
-struct Eval;
+pub struct Eval {
+    pub weights: Weights,
+}
 
 impl minimax::Evaluator for Eval {
-    type G = Nego;
+    type G = Cut;
 
     #[inline]
-    fn evaluate(&self, state: &State) -> minimax::Evaluation {
-        let b = state.board.black.occupied.popcnt() as i16;
-        let w = state.board.black.occupied.popcnt() as i16;
-
-        const KOMI: i16 = 1;
-        let score = (b - w) * 2 - KOMI;
-        match state.current {
-            Color::Black => score,
-            Color::White => -score,
-        }
+    fn evaluate(&self, position: &Position) -> minimax::Evaluation {
+        eval::evaluate(&position.state, &self.weights).clamp(-EVAL_LIMIT, EVAL_LIMIT)
+            as minimax::Evaluation
     }
 }
 
-fn iterative_opts() -> IterativeOptions {
+/// The size of the transposition table unless given another, in bytes.
+pub const DEFAULT_TABLE_SIZE: usize = 64_000;
+
+fn iterative_opts(table_size: usize) -> IterativeOptions {
     IterativeOptions::new()
-        .with_table_byte_size(64_000)
+        .with_table_byte_size(table_size)
         .with_mtdf()
         .with_singular_extension()
         // TODO: adding countermoves triggers a panic.
@@ -45,43 +55,422 @@ fn iterative_opts() -> IterativeOptions {
         .verbose()
 }
 
+// Finished games score beyond any evaluation, by the final margin
+const WIN: i32 = 2 * EVAL_LIMIT;
+
+// The deepest analysis, which is only reached near the end of the game
+const MAX_DEPTH: u32 = 64;
+
+// How often analysis checks the clock
+const NODES_PER_CHECK: u64 = 1024;
+
 fn parallel_opts() -> ParallelOptions {
     ParallelOptions::new()
 }
 
-static PARALLEL_CELL: OnceLock<Mutex<ParallelSearch<Eval>>> = OnceLock::new();
-static ITERATIVE_CELL: OnceLock<Mutex<IterativeSearch<Eval>>> = OnceLock::new();
+/// Iterative deepening negamax on the calling thread.
+pub struct Iterative {
+    search: IterativeSearch<Eval>,
+    weights: Weights,
+    table_size: usize,
+    stop: StopToken,
+}
+
+impl Iterative {
+    pub fn new() -> Self {
+        Self::with_weights(Weights::default())
+    }
+
+    pub fn with_weights(weights: Weights) -> Self {
+        Self {
+            search: IterativeSearch::new(Eval { weights }, iterative_opts(DEFAULT_TABLE_SIZE)),
+            weights,
+            table_size: DEFAULT_TABLE_SIZE,
+            stop: StopToken::new(),
+        }
+    }
+
+    /// Use a transposition table of `bytes` from the next search on.
+    pub fn set_table_size(&mut self, bytes: usize) {
+        self.table_size = bytes;
+        self.reset();
+    }
+
+    // A new search, with an empty transposition table
+    fn reset(&mut self) {
+        self.search = IterativeSearch::new(
+            Eval {
+                weights: self.weights,
+            },
+            iterative_opts(self.table_size),
+        );
+    }
+}
+
+impl Default for Iterative {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Analyze for Iterative {
+    fn analyze(
+        &mut self,
+        state: &State,
+        options: &AnalysisOptions,
+        report: &mut dyn FnMut(&Analysis),
+    ) -> Analysis {
+        analyze(&self.weights, &self.stop, state, options, report)
+    }
+}
+
+impl Player for Iterative {
+    fn name(&self) -> String {
+        "Iterative".into()
+    }
+
+    fn new_game(&mut self, _state: &State) {
+        self.reset();
+    }
 
-fn get_parallel_agent() -> MutexGuard<'static, ParallelSearch<Eval>> {
-    PARALLEL_CELL
-        .get_or_init(|| Mutex::new(ParallelSearch::new(Eval, iterative_opts(), parallel_opts())))
-        .lock()
-        .unwrap()
+    fn choose_move(&mut self, state: &State, budget: Duration) -> Option<Move> {
+        self.choose_move_within(state, Budget::fixed(budget))
+    }
+
+    fn choose_move_within(&mut self, state: &State, budget: Budget) -> Option<Move> {
+        let (m, cut) = step(state, budget, &mut self.search, &self.stop);
+        if cut {
+            self.reset();
+        }
+        m
+    }
+
+    fn set_stop(&mut self, stop: StopToken) {
+        self.stop = stop;
+    }
 }
 
-fn get_iterative_agent() -> MutexGuard<'static, IterativeSearch<Eval>> {
-    ITERATIVE_CELL
-        .get_or_init(|| Mutex::new(IterativeSearch::new(Eval, iterative_opts())))
-        .lock()
-        .unwrap()
+/// Iterative deepening negamax searched on all cores.
+pub struct Parallel {
+    search: ParallelSearch<Eval>,
+    weights: Weights,
+    table_size: usize,
+    stop: StopToken,
 }
 
-pub fn step<S>(
+impl Parallel {
+    pub fn new() -> Self {
+        Self::with_weights(Weights::default())
+    }
+
+    pub fn with_weights(weights: Weights) -> Self {
+        Self {
+            search: ParallelSearch::new(
+                Eval { weights },
+                iterative_opts(DEFAULT_TABLE_SIZE),
+                parallel_opts(),
+            ),
+            weights,
+            table_size: DEFAULT_TABLE_SIZE,
+            stop: StopToken::new(),
+        }
+    }
+
+    /// Use a transposition table of `bytes` from the next search on.
+    pub fn set_table_size(&mut self, bytes: usize) {
+        self.table_size = bytes;
+        self.reset();
+    }
+
+    // A new search, with an empty transposition table
+    fn reset(&mut self) {
+        self.search = ParallelSearch::new(
+            Eval {
+                weights: self.weights,
+            },
+            iterative_opts(self.table_size),
+            parallel_opts(),
+        );
+    }
+}
+
+impl Default for Parallel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Analyze for Parallel {
+    fn analyze(
+        &mut self,
+        state: &State,
+        options: &AnalysisOptions,
+        report: &mut dyn FnMut(&Analysis),
+    ) -> Analysis {
+        analyze(&self.weights, &self.stop, state, options, report)
+    }
+}
+
+impl Player for Parallel {
+    fn name(&self) -> String {
+        "Parallel".into()
+    }
+
+    fn new_game(&mut self, _state: &State) {
+        self.reset();
+    }
+
+    fn choose_move(&mut self, state: &State, budget: Duration) -> Option<Move> {
+        self.choose_move_within(state, Budget::fixed(budget))
+    }
+
+    fn choose_move_within(&mut self, state: &State, budget: Budget) -> Option<Move> {
+        let (m, cut) = step(state, budget, &mut self.search, &self.stop);
+        if cut {
+            self.reset();
+        }
+        m
+    }
+
+    fn set_stop(&mut self, stop: StopToken) {
+        self.stop = stop;
+    }
+}
+
+// Deepens a ply at a time, cutting the depth in progress short if `stop` is
+// stopped or the budget's limit is reached, and plays the best move of the
+// last depth completed. No depth is started past the target. If not even the
+// first depth completes, or the search was stopped before it started, it plays
+// the move the playouts would. Returns whether a depth was cut.
+fn step<S>(
     state: &State,
-    timeout: std::time::Duration,
-    strategy: &mut MutexGuard<'static, S>,
-) -> Option<Move>
+    budget: Budget,
+    strategy: &mut S,
+    stop: &StopToken,
+) -> (Option<Move>, bool)
 where
-    S: Strategy<Nego>,
+    S: Strategy<Cut>,
 {
-    strategy.set_timeout(timeout);
-    strategy.choose_move(state)
+    if stop.is_stopped() {
+        return (heaviest(state), false);
+    }
+    let start = Instant::now();
+    let depth = match budget.iterations {
+        Some(depth) => depth.clamp(1, u8::MAX as u32) as u8,
+        None => u8::MAX,
+    };
+    let position = Position {
+        state: state.clone(),
+        cutoff: Cutoff {
+            stop: stop.clone(),
+            deadline: start.checked_add(budget.max),
+        },
+    };
+    let mut best = None;
+    for depth in 1..=depth {
+        if depth > 1 && (stop.is_stopped() || start.elapsed() >= budget.target) {
+            break;
+        }
+        strategy.set_max_depth(depth);
+        let m = strategy.choose_move(&position);
+        if position.cutoff.passed() {
+            return (best.or_else(|| heaviest(state)), true);
+        }
+        if m.is_none() {
+            break;
+        }
+        best = m;
+    }
+    (best, false)
+}
+
+// The move the playouts weigh highest, which is quick to find
+fn heaviest(state: &State) -> Option<Move> {
+    let playout = Playout::default();
+    let mut moves = Vec::new();
+    state.get_moves(&mut moves);
+    moves.into_iter().max_by_key(|&m| playout.weight(state, m))
+}
+
+// Analysis
+//
+// The minimax crate only tells us its best line, so analysis uses our own
+// alpha-beta (`agent/alphabeta.rs`) on the same evaluation, deepening a ply at
+// a time. At the root, the first `lines` moves are searched with a full window
+// so that their scores are exact. The rest are searched with a null window
+// on the worst of those, and searched again if they beat it. Lines are read
+// back from the transposition table.
+
+// The value of a finished game for the player to move
+fn terminal_value(state: &State) -> i32 {
+    let margin = match state.current {
+        Color::Black => state.rules.margin(&state.board),
+        Color::White => -state.rules.margin(&state.board),
+    };
+    // Margins are in half points
+    margin.signum() * WIN + margin * 50
 }
 
-pub fn step_iterative(state: &State, timeout: std::time::Duration) -> Option<Move> {
-    step(state, timeout, &mut get_iterative_agent())
+struct Progress<'a> {
+    weights: Weights,
+    start: Instant,
+    time: Duration,
+    // The first depth always completes, so there is something to show
+    timed: bool,
+    interval: Duration,
+    next_report: Duration,
+    // The last depth completed, reported while the next one runs
+    last: Analysis,
+    report: &'a mut dyn FnMut(&Analysis),
+    stop: &'a StopToken,
 }
 
-pub fn step_parallel(state: &State, timeout: std::time::Duration) -> Option<Move> {
-    step(state, timeout, &mut get_parallel_agent())
+impl Leaves for Progress<'_> {
+    fn terminal(&self, state: &State) -> i32 {
+        terminal_value(state)
+    }
+
+    fn evaluate(&self, state: &State) -> i32 {
+        eval::evaluate(state, &self.weights).clamp(-EVAL_LIMIT, EVAL_LIMIT)
+    }
+
+    // Report now and then, and whether the time is up or the search was
+    // cancelled
+    fn out_of_time(&mut self, nodes: u64) -> bool {
+        if self.stop.is_stopped() {
+            return true;
+        }
+        if !nodes.is_multiple_of(NODES_PER_CHECK) {
+            return false;
+        }
+        let elapsed = self.start.elapsed();
+        if self.last.depth > 0 && elapsed >= self.next_report && elapsed < self.time {
+            self.next_report = elapsed + self.interval;
+            self.last.nodes = nodes;
+            self.last.elapsed = elapsed;
+            (self.report)(&self.last);
+        }
+        self.timed && elapsed >= self.time
+    }
+}
+
+// `m` followed by the best moves in the table, up to `depth` moves
+fn line(search: &AlphaBeta<Progress>, state: &State, m: Move, depth: u32) -> Vec<Move> {
+    let mut state = state.clone();
+    let mut line = vec![m];
+    state.apply(m);
+    let mut moves = Vec::new();
+    while line.len() < depth as usize {
+        let Some(best) = search.best(&state) else {
+            break;
+        };
+        moves.clear();
+        state.get_moves(&mut moves);
+        if !moves.contains(&best) {
+            break;
+        }
+        state.apply(best);
+        line.push(best);
+    }
+    line
+}
+
+// Multi-PV iterative deepening on the evaluation with `weights`, until the
+// time is up or `stop` is stopped
+fn analyze(
+    weights: &Weights,
+    stop: &StopToken,
+    state: &State,
+    options: &AnalysisOptions,
+    report: &mut dyn FnMut(&Analysis),
+) -> Analysis {
+    let mut search = AlphaBeta::new(Progress {
+        weights: *weights,
+        start: Instant::now(),
+        time: options.time,
+        timed: false,
+        interval: options.interval,
+        next_report: options.interval,
+        last: Analysis::default(),
+        report,
+        stop,
+    });
+
+    // Root moves with their values, best first after each depth
+    let mut root = search
+        .ordered_moves(state, None)
+        .into_iter()
+        .map(|m| (m, i32::MIN))
+        .collect::<Vec<_>>();
+    let lines = options.lines.clamp(1, root.len().max(1));
+    let mut state = state.clone();
+
+    for depth in 1..=MAX_DEPTH {
+        if root.is_empty() || (depth > 1 && search.leaves.start.elapsed() >= search.leaves.time) {
+            break;
+        }
+        search.leaves.timed = depth > 1;
+
+        // The values of the best `lines` moves so far, which are exact
+        let mut exact: Vec<i32> = Vec::new();
+        let mut values = Vec::with_capacity(root.len());
+        for &(m, _) in &root {
+            let undo = state.make(m);
+            let value = match exact.len() < lines {
+                true => search.search(&mut state, depth - 1, i32::MIN + 1, i32::MAX),
+                false => {
+                    let worst = exact[lines - 1];
+                    match search.search(&mut state, depth - 1, -worst - 1, -worst) {
+                        Some(value) if -value > worst => {
+                            search.search(&mut state, depth - 1, i32::MIN + 1, -worst)
+                        }
+                        value => value,
+                    }
+                }
+            }
+            .map(|value| -value);
+            state.undo(undo);
+
+            let Some(value) = value else {
+                break;
+            };
+            values.push((m, value));
+            if exact.len() < lines || value > exact[lines - 1] {
+                let at = exact.partition_point(|v| *v >= value);
+                exact.insert(at, value);
+                exact.truncate(lines);
+            }
+        }
+        if values.len() < root.len() {
+            break;
+        }
+
+        values.sort_by_key(|(_, value)| Reverse(*value));
+        root = values;
+        let last = Analysis {
+            lines: root
+                .iter()
+                .take(options.lines)
+                .map(|&(m, value)| Line {
+                    m,
+                    score: Score::Points(value),
+                    visits: None,
+                    pv: line(&search, &state, m, depth),
+                })
+                .collect(),
+            depth,
+            nodes: search.nodes,
+            elapsed: search.leaves.start.elapsed(),
+        };
+        let progress = &mut search.leaves;
+        progress.last = last;
+        if progress.last.elapsed < progress.time {
+            (progress.report)(&progress.last);
+        }
+    }
+
+    let mut last = search.leaves.last;
+    last.nodes = search.nodes;
+    last.elapsed = search.leaves.start.elapsed();
+    last
 }
@@ -0,0 +1,238 @@
+use crate::core::{
+    bitboard::EMPTY,
+    game::{find_territory, Color, PlayerState, State},
+    pieces::PieceId,
+    r#move::Move,
+    rules::Capture,
+};
+
+use rand::Rng;
+
+// Playout policy
+//
+// Random playouts are cheap but know nothing about the game. Rather than
+// picking uniformly, each legal move gets a weight from a few bitboard
+// features, and the playout picks a move with probability proportional to its
+// weight. Every move keeps some weight, so playouts still explore.
+
+/// How much each feature adds to a move's weight.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlayoutWeights {
+    /// Every legal move starts with this weight.
+    pub base: u32,
+    /// Per empty square of new territory.
+    pub territory: u32,
+    /// Per square of opponent pieces captured.
+    pub capture: u32,
+    /// Touching the player's own secure pieces, which connects to them.
+    pub support: u32,
+    /// Touching opponent pieces, which blocks their connections.
+    pub contact: u32,
+    /// Per square covered by the piece.
+    pub size: u32,
+}
+
+impl Default for PlayoutWeights {
+    fn default() -> Self {
+        PlayoutWeights {
+            base: 1,
+            territory: 40,
+            capture: 20,
+            support: 2,
+            contact: 2,
+            size: 4,
+        }
+    }
+}
+
+/// A weighted random playout policy, shared by the MCTS agents.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Playout {
+    pub weights: PlayoutWeights,
+    /// Chance of ignoring the weights and picking uniformly.
+    pub epsilon: f64,
+}
+
+impl Default for Playout {
+    fn default() -> Self {
+        Playout {
+            weights: PlayoutWeights::default(),
+            epsilon: 0.1,
+        }
+    }
+}
+
+#[inline]
+fn players(state: &State, color: Color) -> (&PlayerState, &PlayerState) {
+    match color {
+        Color::Black => (&state.board.black, &state.board.white),
+        Color::White => (&state.board.white, &state.board.black),
+    }
+}
+
+impl Playout {
+    /// Uniformly random playouts.
+    pub fn uniform() -> Playout {
+        Playout {
+            epsilon: 1.,
+            ..Default::default()
+        }
+    }
+
+    /// The weight of `m` for the player to move, without making it.
+    pub fn weight(&self, state: &State, m: Move) -> u32 {
+        let w = &self.weights;
+        if m.is_pass() {
+            return w.base;
+        }
+        let (player, other) = players(state, state.current);
+        let rules = &state.rules;
+        let mask = m.mask();
+        let adjacent = mask.get_adjacent_mask();
+
+        // Territory the move would enclose, as in `PlayerState::place`
+        let occupied = player.occupied | mask;
+        let group = rules.connection.group(occupied, m.to_square());
+        let territory = match w.territory | w.capture {
+            0 => EMPTY,
+            _ => find_territory(occupied, group, rules.connection) & !(player.owned | other.owned),
+        };
+        let captured = match rules.capture {
+            Capture::Territory => territory & other.occupied & !state.board.boss,
+            // Counting liberties would cost more than the playout gains
+            Capture::Liberties => EMPTY,
+        };
+
+        let secure = player.occupied & (player.owned | state.board.boss);
+        let support = adjacent.intersects(secure) as u32;
+        let contact = adjacent.intersects(other.occupied) as u32;
+
+        w.base
+            + w.territory * (territory & !other.occupied).popcnt()
+            + w.capture * captured.popcnt()
+            + w.support * support
+            + w.contact * contact
+            + w.size * mask.popcnt()
+    }
+
+    /// Pick one of `moves` for the player to move in `state`.
+    #[inline]
+    pub fn choose<R>(&self, state: &State, moves: &[Move], rng: &mut R) -> Option<Move>
+    where
+        R: Rng + ?Sized,
+    {
+        self.choose_index(state, moves, rng).map(|i| moves[i])
+    }
+
+    /// The index of the move picked from `moves`.
+    pub fn choose_index<R>(&self, state: &State, moves: &[Move], rng: &mut R) -> Option<usize>
+    where
+        R: Rng + ?Sized,
+    {
+        if moves.is_empty() {
+            return None;
+        }
+
+        // Bosses can go anywhere, so there is nothing to weigh. Passing is
+        // only ever last, so it is first when it is the only move.
+        let first = moves[0];
+        if self.epsilon >= 1.
+            || rng.gen_bool(self.epsilon)
+            || (!first.is_pass() && first.get_piece() == PieceId::Boss)
+        {
+            return Some(rng.gen_range(0..moves.len()));
+        }
+
+        let weights = moves
+            .iter()
+            .map(|m| self.weight(state, *m))
+            .collect::<Vec<_>>();
+        let total: u32 = weights.iter().sum();
+        if total == 0 {
+            return Some(rng.gen_range(0..moves.len()));
+        }
+        let mut pick = rng.gen_range(0..total);
+        for (i, weight) in weights.into_iter().enumerate() {
+            if pick < weight {
+                return Some(i);
+            }
+            pick -= weight;
+        }
+        unreachable!()
+    }
+
+    /// Play the game out from `state`, returning the final position.
+    pub fn playout<R>(&self, mut state: State, rng: &mut R) -> State
+    where
+        R: Rng + ?Sized,
+    {
+        let mut moves = Vec::new();
+        loop {
+            moves.clear();
+            state.get_moves(&mut moves);
+            match self.choose(&state, &moves, rng) {
+                Some(m) => state.apply(m),
+                None => return state,
+            }
+        }
+    }
+}
+
+/// Results of games between two policies.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PlayoutMatch {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+impl PlayoutMatch {
+    /// Wins for the first policy, counting draws as half.
+    pub fn win_rate(&self) -> f64 {
+        let games = self.wins + self.losses + self.draws;
+        match games {
+            0 => 0.,
+            _ => (self.wins as f64 + self.draws as f64 / 2.) / games as f64,
+        }
+    }
+}
+
+/// Play `games` games between two policies from `state`, alternating colors.
+pub fn play_match<R>(
+    policy: &Playout,
+    opponent: &Playout,
+    state: &State,
+    games: u32,
+    rng: &mut R,
+) -> PlayoutMatch
+where
+    R: Rng + ?Sized,
+{
+    let mut result = PlayoutMatch::default();
+    let mut moves = Vec::new();
+    for game in 0..games {
+        let color = match game & 1 {
+            0 => Color::Black,
+            _ => Color::White,
+        };
+        let mut state = state.clone();
+        loop {
+            moves.clear();
+            state.get_moves(&mut moves);
+            let chooser = match state.current == color {
+                true => policy,
+                false => opponent,
+            };
+            match chooser.choose(&state, &moves, rng) {
+                Some(m) => state.apply(m),
+                None => break,
+            }
+        }
+        match state.result().and_then(|result| result.winner) {
+            Some(winner) if winner == color => result.wins += 1,
+            Some(_) => result.losses += 1,
+            None => result.draws += 1,
+        }
+    }
+    result
+}
@@ -0,0 +1,491 @@
+use crate::{
+    agent::{
+        analysis::{Analysis, AnalysisOptions, Analyze, Line, Score},
+        playout::Playout,
+        stop::StopToken,
+        time::Budget,
+        tree::{self, Memory, Widening, DEFAULT_MEMORY},
+        Player,
+    },
+    core::{
+        game::{Color, State},
+        r#move::Move,
+    },
+};
+
+use rand::{rngs::SmallRng, SeedableRng};
+
+use std::cmp::Reverse;
+use std::time::{Duration, Instant};
+
+// MCTS-Solver
+//
+// Monte Carlo tree search which also proves results (Winands et al., "Monte
+// Carlo Tree Search Solver"). Terminal positions are scored exactly, and
+// proofs are propagated up the tree: a position is lost if the opponent has
+// a winning reply, and won if every reply loses. Near the end of the game,
+// when only a few pieces remain in hand, the search proves the result
+// rather than sampling it.
+
+/// The proven outcome of a position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Proof {
+    Unknown,
+    Win,
+    Loss,
+    Draw,
+}
+
+impl Proof {
+    #[inline]
+    fn flip(self) -> Proof {
+        match self {
+            Proof::Win => Proof::Loss,
+            Proof::Loss => Proof::Win,
+            proof => proof,
+        }
+    }
+}
+
+const ROOT: usize = 0;
+const NO_PARENT: usize = usize::MAX;
+
+struct Node {
+    m: Move,
+    parent: usize,
+    // The player who made `m`. Rewards and proofs are from their side.
+    mover: Color,
+    children: Vec<usize>,
+    // Generated when the node is first expanded, best last
+    untried: Vec<Move>,
+    expanded: bool,
+    visits: u32,
+    reward: f64,
+    proof: Proof,
+}
+
+impl Node {
+    fn new(m: Move, parent: usize, state: &State) -> Node {
+        let mover = state.current.next();
+        let proof = match state.has_moves() {
+            false => outcome(state, mover),
+            true => Proof::Unknown,
+        };
+        Node {
+            m,
+            parent,
+            mover,
+            children: Vec::new(),
+            untried: Vec::new(),
+            expanded: false,
+            visits: 0,
+            reward: 0.,
+            proof,
+        }
+    }
+}
+
+// The result of a finished game for `color`
+fn outcome(state: &State, color: Color) -> Proof {
+    match state.result().map(|result| result.winner) {
+        None => Proof::Unknown,
+        Some(None) => Proof::Draw,
+        Some(Some(winner)) if winner == color => Proof::Win,
+        Some(Some(_)) => Proof::Loss,
+    }
+}
+
+/// What the search found.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SolverResult {
+    pub best: Option<Move>,
+    /// The outcome for the player to move, if it was proven.
+    pub proof: Proof,
+    pub iterations: u32,
+    pub nodes: usize,
+    pub bytes: usize,
+    /// Whether the tree reached its memory budget.
+    pub full: bool,
+}
+
+/// Stops the search once either limit is reached, or the root is proven.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    pub time: Duration,
+    /// Past `time`, the search carries on up to `max_time` until it settles
+    /// on a move.
+    pub max_time: Duration,
+    pub iterations: Option<u32>,
+}
+
+impl Limits {
+    pub fn time(time: Duration) -> Limits {
+        Limits::budget(Budget::fixed(time))
+    }
+
+    pub fn budget(budget: Budget) -> Limits {
+        Limits {
+            time: budget.target,
+            max_time: budget.max,
+            iterations: budget.iterations,
+        }
+    }
+
+    pub fn iterations(iterations: u32) -> Limits {
+        Limits {
+            time: Duration::MAX,
+            max_time: Duration::MAX,
+            iterations: Some(iterations),
+        }
+    }
+}
+
+struct Tree {
+    nodes: Vec<Node>,
+    memory: Memory,
+}
+
+impl Tree {
+    fn select(&self, parent: usize, exploration: f64) -> Option<usize> {
+        let node = &self.nodes[parent];
+        let log_visits = (node.visits as f64).ln();
+        let ucb = |child: &Node| {
+            let visits = child.visits as f64;
+            child.reward / visits + exploration * (log_visits / visits).sqrt()
+        };
+
+        // Losing replies are never worth exploring
+        node.children
+            .iter()
+            .copied()
+            .filter(|child| self.nodes[*child].proof != Proof::Loss)
+            .max_by(|a, b| ucb(&self.nodes[*a]).total_cmp(&ucb(&self.nodes[*b])))
+    }
+
+    // The proof for `parent` given what is known about its children. Children
+    // are proven from the side of the player to move at `parent`.
+    fn prove(&self, parent: usize) -> Proof {
+        let node = &self.nodes[parent];
+        let proofs = || node.children.iter().map(|child| self.nodes[*child].proof);
+        if proofs().any(|proof| proof == Proof::Win) {
+            return Proof::Loss;
+        }
+        if !node.untried.is_empty() || proofs().any(|proof| proof == Proof::Unknown) {
+            return Proof::Unknown;
+        }
+        match proofs().any(|proof| proof == Proof::Draw) {
+            true => Proof::Draw,
+            false => Proof::Win,
+        }
+    }
+
+    fn propagate(&mut self, mut node: usize) {
+        while self.nodes[node].proof != Proof::Unknown && self.nodes[node].parent != NO_PARENT {
+            node = self.nodes[node].parent;
+            let proof = self.prove(node);
+            if proof == Proof::Unknown {
+                break;
+            }
+            self.nodes[node].proof = proof;
+        }
+    }
+
+    fn backpropagate(&mut self, mut node: usize, winner: Option<Color>) {
+        while node != NO_PARENT {
+            let n = &mut self.nodes[node];
+            n.visits += 1;
+            n.reward += match winner {
+                Some(color) if color == n.mover => 1.,
+                Some(_) => 0.,
+                None => 0.5,
+            };
+            node = n.parent;
+        }
+    }
+
+    fn is_settled(&self) -> bool {
+        let root = &self.nodes[ROOT];
+        let children = root.children.iter().map(|child| &self.nodes[*child]);
+        children.clone().any(|child| child.proof == Proof::Win)
+            || tree::is_settled(
+                children
+                    .filter(|child| child.proof != Proof::Loss)
+                    .map(|child| (child.visits, child.reward)),
+            )
+    }
+
+    fn best(&self) -> Option<Move> {
+        self.best_child(ROOT).map(|child| self.nodes[child].m)
+    }
+
+    // A winning move, or else the most visited one which isn't lost
+    fn best_child(&self, node: usize) -> Option<usize> {
+        let children = || self.nodes[node].children.iter().copied();
+        children()
+            .find(|child| self.nodes[*child].proof == Proof::Win)
+            .or_else(|| {
+                children()
+                    .filter(|child| self.nodes[*child].proof != Proof::Loss)
+                    .max_by_key(|child| self.nodes[*child].visits)
+            })
+            .or_else(|| children().max_by_key(|child| self.nodes[*child].visits))
+    }
+
+    // The best `lines` moves at the root in the order `best_child` prefers
+    // them, each followed by the best replies
+    fn analysis(&self, lines: usize) -> Analysis {
+        let mut children = self.nodes[ROOT].children.clone();
+        children.sort_by_key(|child| {
+            let node = &self.nodes[*child];
+            let rank = match node.proof {
+                Proof::Win => 0,
+                Proof::Loss => 2,
+                _ => 1,
+            };
+            (rank, Reverse(node.visits))
+        });
+        let lines = children
+            .into_iter()
+            .take(lines)
+            .map(|child| {
+                let node = &self.nodes[child];
+                let mut pv = vec![node.m];
+                let mut next = child;
+                while let Some(reply) = self.best_child(next) {
+                    pv.push(self.nodes[reply].m);
+                    next = reply;
+                }
+                let score = match node.proof {
+                    Proof::Unknown => Score::WinRate(node.reward / node.visits.max(1) as f64),
+                    proof => Score::Proven(proof),
+                };
+                Line {
+                    m: node.m,
+                    score,
+                    visits: Some(node.visits),
+                    pv,
+                }
+            })
+            .collect();
+
+        // Parents come before their children
+        let mut depths = vec![0; self.nodes.len()];
+        for node in 1..self.nodes.len() {
+            depths[node] = depths[self.nodes[node].parent] + 1;
+        }
+        Analysis {
+            lines,
+            depth: depths.into_iter().max().unwrap_or(0),
+            ..Analysis::default()
+        }
+    }
+}
+
+/// MCTS-Solver, with the shared playout policy for unproven positions.
+#[derive(Clone, Debug)]
+pub struct Solver {
+    pub playout: Playout,
+    pub exploration: f64,
+    /// `None` lets every node have all its moves as children.
+    pub widening: Option<Widening>,
+    /// The most memory the tree may use, in bytes.
+    pub memory: usize,
+    /// Cancels the search (see `Player::set_stop`).
+    pub stop: StopToken,
+    /// Seeds the random numbers of every search, or `None` for different
+    /// ones each time.
+    pub seed: Option<u64>,
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Solver {
+            playout: Playout::default(),
+            exploration: std::f64::consts::SQRT_2,
+            widening: Some(Widening::default()),
+            memory: DEFAULT_MEMORY,
+            stop: StopToken::new(),
+            seed: None,
+        }
+    }
+}
+
+impl Solver {
+    pub fn search(&self, state: &State, limits: Limits) -> SolverResult {
+        let start = Instant::now();
+        let mut tree = self.tree(state);
+        let mut rng = self.rng();
+        let iterations = self.grow(&mut tree, state, &mut rng, |tree, iterations| {
+            !matches!(limits.iterations, Some(n) if iterations >= n)
+                && (start.elapsed() < limits.time
+                    || (start.elapsed() < limits.max_time && !tree.is_settled()))
+        });
+
+        let stats = tree.memory.stats(&tree.nodes);
+        SolverResult {
+            best: (tree.best()).or_else(|| tree::untried(state, &self.playout).last().copied()),
+            proof: tree.nodes[ROOT].proof.flip(),
+            iterations,
+            nodes: stats.nodes,
+            bytes: stats.bytes,
+            full: stats.full,
+        }
+    }
+
+    // Every search starts from the seed, if there is one
+    fn rng(&self) -> SmallRng {
+        match self.seed {
+            Some(seed) => SmallRng::seed_from_u64(seed),
+            None => SmallRng::from_entropy(),
+        }
+    }
+
+    fn tree(&self, state: &State) -> Tree {
+        Tree {
+            nodes: vec![Node::new(Move::PASS, NO_PARENT, state)],
+            memory: Memory::new(self.memory),
+        }
+    }
+
+    // Grow the tree for `state` while the root is unproven, the search hasn't
+    // been stopped and `more` says so, given the tree and the iterations so
+    // far. Returns the iterations.
+    fn grow(
+        &self,
+        tree: &mut Tree,
+        state: &State,
+        rng: &mut SmallRng,
+        mut more: impl FnMut(&Tree, u32) -> bool,
+    ) -> u32 {
+        let mut iterations = 0;
+        while tree.nodes[ROOT].proof == Proof::Unknown
+            && !self.stop.is_stopped()
+            && more(tree, iterations)
+        {
+            iterations += 1;
+
+            // Select down to a proven node, or expand a new one. Without
+            // room to grow, the playout starts where selection stops.
+            let mut state = state.clone();
+            let mut node = ROOT;
+            while tree.nodes[node].proof == Proof::Unknown {
+                if !tree.nodes[node].expanded {
+                    if !tree.memory.has_room(&tree.nodes) {
+                        break;
+                    }
+                    let untried = tree::untried(&state, &self.playout);
+                    tree.memory.add(&untried);
+                    tree.nodes[node].untried = untried;
+                    tree.nodes[node].expanded = true;
+                }
+
+                // Widen anyway once every reply tried so far loses
+                let selected = tree.select(node, self.exploration);
+                let n = &tree.nodes[node];
+                let limit = self
+                    .widening
+                    .map_or(usize::MAX, |widening| widening.children(n.visits));
+                if (n.children.len() < limit || selected.is_none())
+                    && !n.untried.is_empty()
+                    && tree.memory.has_room(&tree.nodes)
+                {
+                    let m = tree.nodes[node].untried.pop().unwrap();
+                    state.apply(m);
+                    let child = tree.nodes.len();
+                    tree.nodes.push(Node::new(m, node, &state));
+                    tree.memory.push(&mut tree.nodes[node].children, child);
+                    node = child;
+                    break;
+                }
+                let Some(child) = selected else {
+                    break;
+                };
+                node = child;
+                state.apply(tree.nodes[node].m);
+            }
+
+            // Proven nodes don't need a playout
+            let mover = tree.nodes[node].mover;
+            let winner = match tree.nodes[node].proof {
+                Proof::Win => Some(mover),
+                Proof::Loss => Some(mover.next()),
+                Proof::Draw => None,
+                Proof::Unknown => {
+                    let end = self.playout.playout(state, rng);
+                    end.result().and_then(|result| result.winner)
+                }
+            };
+
+            tree.propagate(node);
+            tree.backpropagate(node, winner);
+        }
+        iterations
+    }
+}
+
+impl Analyze for Solver {
+    // Stops early once the root is proven
+    fn analyze(
+        &mut self,
+        state: &State,
+        options: &AnalysisOptions,
+        report: &mut dyn FnMut(&Analysis),
+    ) -> Analysis {
+        let start = Instant::now();
+        let mut tree = self.tree(state);
+        let mut rng = self.rng();
+        let mut iterations = 0;
+        let mut analysis = tree.analysis(options.lines);
+        let interval = options.interval.max(Duration::from_millis(1));
+        while tree.nodes[ROOT].proof == Proof::Unknown
+            && start.elapsed() < options.time
+            && !self.stop.is_stopped()
+        {
+            let deadline = (Instant::now() + interval).min(start + options.time);
+            iterations += self.grow(&mut tree, state, &mut rng, |_, _| Instant::now() < deadline);
+            analysis = Analysis {
+                nodes: iterations as u64,
+                elapsed: start.elapsed(),
+                ..tree.analysis(options.lines)
+            };
+            if tree.nodes[ROOT].proof == Proof::Unknown
+                && start.elapsed() < options.time
+                && !self.stop.is_stopped()
+            {
+                report(&analysis);
+            }
+        }
+        analysis
+    }
+}
+
+impl Player for Solver {
+    fn name(&self) -> String {
+        "Solver".into()
+    }
+
+    fn choose_move(&mut self, state: &State, budget: Duration) -> Option<Move> {
+        self.choose_move_within(state, Budget::fixed(budget))
+    }
+
+    fn choose_move_within(&mut self, state: &State, budget: Budget) -> Option<Move> {
+        let result = self.search(state, Limits::budget(budget));
+        log::info!(
+            "solver: {} iterations, {} nodes in {} MB{}, {:?}",
+            result.iterations,
+            result.nodes,
+            result.bytes >> 20,
+            if result.full { " (full)" } else { "" },
+            result.proof
+        );
+        result.best
+    }
+
+    fn set_stop(&mut self, stop: StopToken) {
+        self.stop = stop;
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+    }
+}
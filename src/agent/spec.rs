@@ -0,0 +1,515 @@
+use crate::{
+    agent::{
+        book::{self, Book},
+        endgame::WithEndgame,
+        mcts, mcts2, negamax, solver,
+        stop::StopToken,
+        time::Budget,
+        Agent, Player, Random,
+    },
+    core::{error::Error, game::State, r#move::Move},
+};
+
+use serde::{de, Deserialize, Deserializer};
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Agent specifications
+//
+// An agent is written as its name, optionally followed by settings, e.g.
+// `mcts2:time=5s,c=1.4,seed=7` or `negamax:depth=4,tt=64MB`. The names are
+// `negamax` (or `iterative`), `parallel`, `mcts`, `mcts2`, `solver`, `random`
+// and `human`. The settings are:
+//
+// * `time`: per move, e.g. `500ms`, `5s` or `2m`; plain numbers are seconds.
+// * `depth` (negamax) or `playouts` (MCTS): a fixed amount of search per
+//   move instead of the time.
+// * `tt`: the negamax transposition table size, e.g. `64MB`.
+// * `c`: the exploration constant of `mcts2` and `solver`.
+// * `memory`: the memory budget of an MCTS tree.
+// * `ponder`: `yes` to search during the opponent's turn (`mcts2`).
+// * `seed`: seeds the player's random numbers (not `mcts`).
+// * `endgame`: the pieces in hand at which the endgame solver takes over.
+// * `book`: `no` to play without the opening book.
+//
+// A config file names agents, in TOML or JSON. Each one is either a spec or
+// a table of settings with the name under `agent`:
+//
+//     black = "mcts2:time=5s,c=1.4"
+//
+//     [white]
+//     agent = "negamax"
+//     depth = 4
+//     tt = "64MB"
+//
+// The UI and the demo play `black` against `white`.
+
+/// Where the UI and tools look for a config by default.
+pub const DEFAULT_PATH: &str = "nego.toml";
+
+/// The time per move unless given another.
+pub const DEFAULT_TIME: Duration = Duration::from_secs(5);
+
+/// An agent and its settings. Settings which are `None` are left at the
+/// player's defaults.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AgentSpec {
+    pub agent: Agent,
+    /// Search this many plies (negamax) or playouts (MCTS) for every move,
+    /// however long it takes.
+    pub iterations: Option<u32>,
+    /// The negamax transposition table size, in bytes.
+    pub table_size: Option<usize>,
+    pub exploration: Option<f64>,
+    /// The memory budget of an MCTS tree, in bytes.
+    pub memory: Option<usize>,
+    pub ponder: bool,
+    pub seed: Option<u64>,
+    /// Pieces in hand at which the endgame solver takes over.
+    pub endgame: Option<u32>,
+    /// Whether to open with the book, when there is one.
+    pub book: bool,
+}
+
+impl From<Agent> for AgentSpec {
+    fn from(agent: Agent) -> Self {
+        AgentSpec {
+            agent,
+            iterations: None,
+            table_size: None,
+            exploration: None,
+            memory: None,
+            ponder: false,
+            seed: None,
+            endgame: None,
+            book: true,
+        }
+    }
+}
+
+// The agent with `time` per move
+fn with_time(agent: Agent, time: Duration) -> Agent {
+    match agent {
+        Agent::Parallel(_) => Agent::Parallel(time),
+        Agent::Iterative(_) => Agent::Iterative(time),
+        Agent::Mcts(_) => Agent::Mcts(time),
+        Agent::Mcts2(_) => Agent::Mcts2(time),
+        Agent::Solver(_) => Agent::Solver(time),
+        Agent::Random | Agent::Human => agent,
+    }
+}
+
+fn parse_name(name: &str) -> Result<Agent, Error> {
+    match name {
+        "negamax" | "iterative" => Ok(Agent::Iterative(DEFAULT_TIME)),
+        "parallel" => Ok(Agent::Parallel(DEFAULT_TIME)),
+        "mcts" => Ok(Agent::Mcts(DEFAULT_TIME)),
+        "mcts2" => Ok(Agent::Mcts2(DEFAULT_TIME)),
+        "solver" => Ok(Agent::Solver(DEFAULT_TIME)),
+        "random" => Ok(Agent::Random),
+        "human" => Ok(Agent::Human),
+        _ => Err(Error::InvalidAgent),
+    }
+}
+
+fn name(agent: Agent) -> &'static str {
+    match agent {
+        Agent::Iterative(_) => "negamax",
+        Agent::Parallel(_) => "parallel",
+        Agent::Mcts(_) => "mcts",
+        Agent::Mcts2(_) => "mcts2",
+        Agent::Solver(_) => "solver",
+        Agent::Random => "random",
+        Agent::Human => "human",
+    }
+}
+
+/// Parses a duration such as `500ms`, `5s`, `1.5m` or `5` (seconds).
+pub fn parse_duration(s: &str) -> Result<Duration, Error> {
+    let (number, unit) = match s {
+        _ if s.ends_with("ms") => (&s[..s.len() - 2], 0.001),
+        _ if s.ends_with('s') => (&s[..s.len() - 1], 1.),
+        _ if s.ends_with('m') => (&s[..s.len() - 1], 60.),
+        _ => (s, 1.),
+    };
+    let number = number.parse::<f64>().map_err(|_| Error::InvalidAgent)?;
+    Duration::try_from_secs_f64(number * unit).map_err(|_| Error::InvalidAgent)
+}
+
+const UNITS: [(&str, usize); 3] = [("GB", 1 << 30), ("MB", 1 << 20), ("KB", 1 << 10)];
+
+/// Parses a size in bytes such as `64MB`, `512KB`, `1GB` or `1000`.
+pub fn parse_size(s: &str) -> Result<usize, Error> {
+    let upper = s.to_ascii_uppercase();
+    let (number, unit) = UNITS
+        .iter()
+        .find_map(|(suffix, unit)| Some((upper.strip_suffix(suffix)?, *unit)))
+        .unwrap_or((upper.strip_suffix('B').unwrap_or(&upper), 1));
+    let number = number.parse::<usize>().map_err(|_| Error::InvalidAgent)?;
+    number.checked_mul(unit).ok_or(Error::InvalidAgent)
+}
+
+fn size(bytes: usize) -> String {
+    UNITS
+        .iter()
+        .find(|(_, unit)| bytes != 0 && bytes.is_multiple_of(*unit))
+        .map(|(suffix, unit)| format!("{}{}", bytes / unit, suffix))
+        .unwrap_or_else(|| bytes.to_string())
+}
+
+fn yes_no(b: bool) -> &'static str {
+    if b {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+fn parse_yes_no(s: &str) -> Result<bool, Error> {
+    match s {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err(Error::InvalidAgent),
+    }
+}
+
+fn parse<T: FromStr>(s: &str) -> Result<T, Error> {
+    s.parse().map_err(|_| Error::InvalidAgent)
+}
+
+impl AgentSpec {
+    pub fn is_human(&self) -> bool {
+        self.agent.is_human()
+    }
+
+    fn is_negamax(&self) -> bool {
+        matches!(self.agent, Agent::Iterative(_) | Agent::Parallel(_))
+    }
+
+    fn is_mcts(&self) -> bool {
+        matches!(
+            self.agent,
+            Agent::Mcts(_) | Agent::Mcts2(_) | Agent::Solver(_)
+        )
+    }
+
+    fn has_tree(&self) -> bool {
+        matches!(self.agent, Agent::Mcts2(_) | Agent::Solver(_))
+    }
+
+    /// Change one setting, given as in a spec. Fails for settings the agent
+    /// doesn't have.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), Error> {
+        let searches = self.is_negamax() || self.is_mcts();
+        let value = value.trim();
+        match key.trim() {
+            "time" if searches => self.agent = with_time(self.agent, parse_duration(value)?),
+            "depth" if self.is_negamax() => self.iterations = Some(parse(value)?),
+            "playouts" if self.is_mcts() => self.iterations = Some(parse(value)?),
+            "tt" if self.is_negamax() => self.table_size = Some(parse_size(value)?),
+            "c" if self.has_tree() => self.exploration = Some(parse(value)?),
+            "memory" if self.is_mcts() => self.memory = Some(parse_size(value)?),
+            "ponder" if matches!(self.agent, Agent::Mcts2(_)) => self.ponder = parse_yes_no(value)?,
+            "seed" if !self.is_human() && !matches!(self.agent, Agent::Mcts(_)) => {
+                self.seed = Some(parse(value)?)
+            }
+            "endgame" if searches => self.endgame = Some(parse(value)?),
+            "book" if searches => self.book = parse_yes_no(value)?,
+            _ => return Err(Error::InvalidAgent),
+        }
+        Ok(())
+    }
+
+    /// A new instance of the player, or `None` for a human. Searching players
+    /// switch to the endgame solver when few pieces are left in hand.
+    pub fn player(&self) -> Option<Box<dyn Player>> {
+        let search: Box<dyn Player> = match self.agent {
+            Agent::Parallel(_) => {
+                let mut player = negamax::Parallel::new();
+                if let Some(bytes) = self.table_size {
+                    player.set_table_size(bytes);
+                }
+                Box::new(player)
+            }
+            Agent::Iterative(_) => {
+                let mut player = negamax::Iterative::new();
+                if let Some(bytes) = self.table_size {
+                    player.set_table_size(bytes);
+                }
+                Box::new(player)
+            }
+            Agent::Mcts(_) => {
+                let mut player = mcts::Mcts::default();
+                player.memory = self.memory.unwrap_or(player.memory);
+                Box::new(player)
+            }
+            Agent::Mcts2(_) => {
+                let mut player = mcts2::Mcts2::new();
+                player.exploration = self.exploration.unwrap_or(player.exploration);
+                player.memory = self.memory.unwrap_or(player.memory);
+                player.ponder = self.ponder;
+                Box::new(player)
+            }
+            Agent::Solver(_) => {
+                let mut player = solver::Solver::default();
+                player.exploration = self.exploration.unwrap_or(player.exploration);
+                player.memory = self.memory.unwrap_or(player.memory);
+                Box::new(player)
+            }
+            Agent::Random => {
+                let mut player = Random::new();
+                if let Some(seed) = self.seed {
+                    player.set_seed(seed);
+                }
+                return Some(Box::new(player));
+            }
+            Agent::Human => return None,
+        };
+
+        let mut endgame = WithEndgame::new(search);
+        endgame.threshold = self.endgame.unwrap_or(endgame.threshold);
+        let mut player: Box<dyn Player> = match self.iterations {
+            Some(iterations) => Box::new(WithIterations {
+                inner: Box::new(endgame),
+                iterations,
+            }),
+            None => Box::new(endgame),
+        };
+        if let Some(seed) = self.seed {
+            player.set_seed(seed);
+        }
+        Some(player)
+    }
+
+    /// As `player`, but opening with moves from `book` unless the spec says
+    /// not to.
+    pub fn player_with_book(&self, book: Option<&Arc<Book>>) -> Option<Box<dyn Player>> {
+        let player = self.player()?;
+        Some(
+            match (self.is_negamax() || self.is_mcts(), self.book, book) {
+                (true, true, Some(book)) => {
+                    let mut player = book::WithBook::new(player, book.clone());
+                    if let Some(seed) = self.seed {
+                        player.set_seed(seed);
+                    }
+                    Box::new(player)
+                }
+                _ => player,
+            },
+        )
+    }
+}
+
+// Searches a fixed amount for every move, whatever the budget's time
+struct WithIterations {
+    inner: Box<dyn Player>,
+    iterations: u32,
+}
+
+impl Player for WithIterations {
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    fn new_game(&mut self, state: &State) {
+        self.inner.new_game(state)
+    }
+
+    fn choose_move(&mut self, state: &State, budget: Duration) -> Option<Move> {
+        self.choose_move_within(state, Budget::fixed(budget))
+    }
+
+    fn choose_move_within(&mut self, state: &State, budget: Budget) -> Option<Move> {
+        let budget = Budget {
+            iterations: Some(self.iterations),
+            ..budget
+        };
+        self.inner.choose_move_within(state, budget)
+    }
+
+    fn opponent_moved(&mut self, state: &State, m: Move) {
+        self.inner.opponent_moved(state, m)
+    }
+
+    fn stop(&mut self) {
+        self.inner.stop()
+    }
+
+    fn set_stop(&mut self, stop: StopToken) {
+        self.inner.set_stop(stop)
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.inner.set_seed(seed)
+    }
+}
+
+// Only the settings which aren't the defaults are written
+impl fmt::Display for AgentSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut settings = Vec::new();
+        let time = self.agent.budget();
+        if (self.is_negamax() || self.is_mcts()) && time != DEFAULT_TIME {
+            settings.push(format!("time={}s", time.as_secs_f64()));
+        }
+        if let Some(iterations) = self.iterations {
+            let key = if self.is_negamax() {
+                "depth"
+            } else {
+                "playouts"
+            };
+            settings.push(format!("{}={}", key, iterations));
+        }
+        if let Some(bytes) = self.table_size {
+            settings.push(format!("tt={}", size(bytes)));
+        }
+        if let Some(c) = self.exploration {
+            settings.push(format!("c={}", c));
+        }
+        if let Some(bytes) = self.memory {
+            settings.push(format!("memory={}", size(bytes)));
+        }
+        if self.ponder {
+            settings.push(format!("ponder={}", yes_no(self.ponder)));
+        }
+        if let Some(seed) = self.seed {
+            settings.push(format!("seed={}", seed));
+        }
+        if let Some(pieces) = self.endgame {
+            settings.push(format!("endgame={}", pieces));
+        }
+        if !self.book {
+            settings.push(format!("book={}", yes_no(self.book)));
+        }
+
+        write!(f, "{}", name(self.agent))?;
+        if !settings.is_empty() {
+            write!(f, ":{}", settings.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for AgentSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, settings) = match s.split_once(':') {
+            Some((name, settings)) => (name, Some(settings)),
+            None => (s, None),
+        };
+        let mut spec = AgentSpec::from(parse_name(name.trim())?);
+        for setting in settings.into_iter().flat_map(|s| s.split(',')) {
+            let (key, value) = setting.split_once('=').ok_or(Error::InvalidAgent)?;
+            spec.set(key, value)?;
+        }
+        Ok(spec)
+    }
+}
+
+// A setting in a config file, written as in a spec
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Setting {
+    Bool(bool),
+    Integer(u64),
+    Float(f64),
+    Text(String),
+}
+
+impl fmt::Display for Setting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Setting::Bool(b) => write!(f, "{}", yes_no(*b)),
+            Setting::Integer(n) => write!(f, "{}", n),
+            Setting::Float(x) => write!(f, "{}", x),
+            Setting::Text(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SpecConfig {
+    Spec(String),
+    Table(BTreeMap<String, Setting>),
+}
+
+impl SpecConfig {
+    fn spec(self) -> Result<AgentSpec, Error> {
+        match self {
+            SpecConfig::Spec(spec) => spec.parse(),
+            SpecConfig::Table(mut settings) => {
+                let name = match settings.remove("agent") {
+                    Some(Setting::Text(name)) => name,
+                    _ => return Err(Error::InvalidAgent),
+                };
+                let mut spec = AgentSpec::from(parse_name(&name)?);
+                for (key, value) in settings {
+                    spec.set(&key, &value.to_string())?;
+                }
+                Ok(spec)
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for AgentSpec {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        SpecConfig::deserialize(deserializer)?
+            .spec()
+            .map_err(|e| de::Error::custom(format!("{:?}", e)))
+    }
+}
+
+/// Agents by name, in order of name.
+pub type Config = BTreeMap<String, AgentSpec>;
+
+pub fn from_toml(s: &str) -> Result<Config, Error> {
+    toml::from_str(s).map_err(|e| {
+        log::warn!("invalid config: {}", e);
+        Error::InvalidConfig
+    })
+}
+
+pub fn from_json(s: &str) -> Result<Config, Error> {
+    serde_json::from_str(s).map_err(|e| {
+        log::warn!("invalid config: {}", e);
+        Error::InvalidConfig
+    })
+}
+
+/// Load a config file, which is JSON if its name ends in `.json` and TOML
+/// otherwise.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, Error> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path).map_err(|e| Error::Io(e.kind()))?;
+    match path.extension().is_some_and(|ext| ext == "json") {
+        true => from_json(&text),
+        false => from_toml(&text),
+    }
+}
+
+/// The agents for `black` and `white` from the config file at `path`, or
+/// `defaults` for any not given there. The file is optional.
+pub fn load_players<P: AsRef<Path>>(path: P, defaults: [AgentSpec; 2]) -> [AgentSpec; 2] {
+    let config = match load(&path) {
+        Ok(config) => config,
+        Err(Error::Io(std::io::ErrorKind::NotFound)) => Config::new(),
+        Err(e) => {
+            log::warn!("ignoring {}: {:?}", path.as_ref().display(), e);
+            Config::new()
+        }
+    };
+    let [black, white] = defaults;
+    [
+        config.get("black").copied().unwrap_or(black),
+        config.get("white").copied().unwrap_or(white),
+    ]
+}
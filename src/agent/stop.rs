@@ -0,0 +1,34 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// Cancelling searches
+//
+// A `StopToken` is shared between a player and whoever runs it, such as the
+// UI's worker. Once it is stopped, the player's search returns the best move
+// it has found so far, and later searches return at once, until the player is
+// given a new token.
+//
+// The searches of our own (mcts2, the solver, the endgame solver and analysis)
+// check the token as they go, and return within a few milliseconds. The
+// minimax-rs searches can't be interrupted from outside, so they search
+// positions which count as finished games once the token is stopped, and soon
+// run out (see `Cutoff` in `agent/mod.rs`).
+
+/// A flag for cancelling searches, shared by all its clones.
+#[derive(Clone, Debug, Default)]
+pub struct StopToken(Arc<AtomicBool>);
+
+impl StopToken {
+    pub fn new() -> StopToken {
+        StopToken::default()
+    }
+
+    /// Cancel every search using this token.
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
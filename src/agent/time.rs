@@ -0,0 +1,230 @@
+use crate::{
+    agent::{step_within, Player},
+    core::{
+        game::{Color, State},
+        r#move::Move,
+    },
+};
+
+use std::time::{Duration, Instant};
+
+// Time management
+//
+// A game is played under a `TimeControl`, and each player has a `Clock`
+// which runs during their turns. Before each move, a `TimeManager` turns the
+// time left on the clock into a `Budget`: the time the search aims to use, and
+// the most it may use when it hasn't settled on a move. Searches which can
+// tell whether they have settled (the MCTS agents) use the extra time; the
+// others stop at the target.
+//
+// The share of the clock a move gets depends on how many moves the player has
+// left, which is the number of pieces in their hand, and on how close the
+// game is. The budget always leaves a reserve on the clock for the time spent
+// outside the search.
+
+/// How much time the players have.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeControl {
+    /// The same time for every move, which can't be saved for later.
+    PerMove(Duration),
+    /// All the time for the game, with nothing added.
+    SuddenDeath(Duration),
+    /// Main time, with `increment` added after every move.
+    Increment { main: Duration, increment: Duration },
+    /// Main time, then `periods` periods of `period`. A move which takes
+    /// longer than the current period uses it up (Japanese byo-yomi).
+    ByoYomi {
+        main: Duration,
+        periods: u32,
+        period: Duration,
+    },
+}
+
+/// The time a player has left.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Clock {
+    pub control: TimeControl,
+    /// Main time left. For `PerMove`, the time for each move.
+    pub main: Duration,
+    /// Byo-yomi periods left.
+    pub periods: u32,
+    flagged: bool,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Clock {
+        let (main, periods) = match control {
+            TimeControl::PerMove(time) | TimeControl::SuddenDeath(time) => (time, 0),
+            TimeControl::Increment { main, .. } => (main, 0),
+            TimeControl::ByoYomi { main, periods, .. } => (main, periods),
+        };
+        Clock {
+            control,
+            main,
+            periods,
+            flagged: false,
+        }
+    }
+
+    /// The longest the next move can take without losing on time.
+    pub fn available(&self) -> Duration {
+        match self.control {
+            _ if self.flagged => Duration::ZERO,
+            TimeControl::ByoYomi { period, .. } => self.main + period * self.periods,
+            _ => self.main,
+        }
+    }
+
+    /// Whether the player has run out of time.
+    pub fn is_flagged(&self) -> bool {
+        self.flagged
+    }
+
+    /// Charge the clock for a move which took `elapsed`. Returns `false` if
+    /// the player ran out of time.
+    pub fn spend(&mut self, elapsed: Duration) -> bool {
+        self.flagged |= elapsed > self.available();
+        if self.flagged {
+            self.main = Duration::ZERO;
+            self.periods = 0;
+            return false;
+        }
+        match self.control {
+            TimeControl::PerMove(_) => (),
+            TimeControl::SuddenDeath(_) => self.main -= elapsed,
+            TimeControl::Increment { increment, .. } => self.main = self.main - elapsed + increment,
+            TimeControl::ByoYomi { period, .. } => {
+                let mut over = elapsed.saturating_sub(self.main);
+                self.main = self.main.saturating_sub(elapsed);
+                // Only a period which is overrun is used up
+                while over > period {
+                    over -= period;
+                    self.periods -= 1;
+                }
+            }
+        }
+        true
+    }
+}
+
+/// The time for one move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Budget {
+    /// The time the search aims to use.
+    pub target: Duration,
+    /// The most it may use if it hasn't settled on a move.
+    pub max: Duration,
+    /// Search a fixed amount instead, whatever the time: this many playouts
+    /// for the MCTS agents, or plies for negamax. With a seed, the move then
+    /// only depends on the position (see `Player::set_seed`).
+    pub iterations: Option<u32>,
+}
+
+impl Budget {
+    /// The same target and limit.
+    pub fn fixed(time: Duration) -> Budget {
+        Budget {
+            target: time,
+            max: time,
+            iterations: None,
+        }
+    }
+
+    /// A fixed number of iterations, with no time limit.
+    pub fn iterations(iterations: u32) -> Budget {
+        Budget {
+            iterations: Some(iterations),
+            ..Budget::fixed(Duration::MAX)
+        }
+    }
+}
+
+/// Splits the time on a clock between the moves left in the game.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeManager {
+    /// Left on the clock for the time spent outside the search.
+    pub reserve: Duration,
+    /// How far an unsettled search may run past the target, as a multiple.
+    pub extension: f64,
+    /// The most of the remaining main time a single move may use.
+    pub max_share: f64,
+}
+
+impl Default for TimeManager {
+    fn default() -> Self {
+        TimeManager {
+            reserve: Duration::from_millis(50),
+            extension: 3.,
+            max_share: 0.25,
+        }
+    }
+}
+
+/// How much a position deserves extra time, from 1 for a one-sided game up to
+/// 1.5 for an even one.
+pub fn criticality(state: &State) -> f64 {
+    let margin = state.score().margin.unsigned_abs() as f64;
+    1. + 0.5 / (1. + margin / 8.)
+}
+
+/// The moves the player to move has left, one per piece in hand.
+pub fn moves_left(state: &State) -> u32 {
+    let player = match state.current {
+        Color::Black => &state.board.black,
+        Color::White => &state.board.white,
+    };
+    player.hand.bits().count_ones().max(1)
+}
+
+impl TimeManager {
+    /// The budget for the player to move in `state`.
+    pub fn budget(&self, clock: &Clock, state: &State) -> Budget {
+        let limit = clock.available().saturating_sub(self.reserve);
+        let (share, max) = match clock.control {
+            TimeControl::PerMove(_) => return Budget::fixed(limit),
+            TimeControl::SuddenDeath(_) => {
+                let share = clock.main / moves_left(state);
+                (share, clock.main.mul_f64(self.max_share))
+            }
+            TimeControl::Increment { increment, .. } => {
+                let share = clock.main / moves_left(state) + increment;
+                (share, clock.main.mul_f64(self.max_share) + increment)
+            }
+            // A move within the period costs nothing once the main time is
+            // used up, so each move can count on one period
+            TimeControl::ByoYomi { period, .. } => {
+                let period = match clock.periods {
+                    0 => Duration::ZERO,
+                    _ => period,
+                };
+                let share = clock.main / moves_left(state) + period;
+                (share, clock.main.mul_f64(self.max_share) + period)
+            }
+        };
+        let max = max.min(limit);
+        let target = share.mul_f64(criticality(state)).min(max);
+        Budget {
+            target,
+            max: target.mul_f64(self.extension).min(max),
+            iterations: None,
+        }
+    }
+}
+
+/// Choose a move with `player` on `clock`, apply it to `state` and charge the
+/// clock. Returns `None` at the end of the game; check the clock to see
+/// whether the player ran out of time.
+pub fn step_clocked(
+    player: &mut dyn Player,
+    state: &mut State,
+    clock: &mut Clock,
+    manager: &TimeManager,
+) -> Option<Move> {
+    let start = Instant::now();
+    let budget = manager.budget(clock, state);
+    let m = step_within(player, state, budget);
+    if m.is_some() {
+        clock.spend(start.elapsed());
+    }
+    m
+}
@@ -0,0 +1,456 @@
+use crate::{
+    agent::{
+        spec::AgentSpec,
+        step_within,
+        stop::StopToken,
+        time::{self, Budget, Clock, TimeControl, TimeManager},
+        Agent, Player,
+    },
+    core::{
+        game::{Color, State},
+        record::GameRecord,
+    },
+};
+
+use std::fmt;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// Tournaments
+//
+// A tournament plays games between agents: every pair of them in a round
+// robin, or the first against each of the others in a gauntlet. Every pairing
+// plays each opening twice per round, once with each color, so that neither
+// side gains from moving first or from a lopsided opening.
+//
+// Results are summarised as an Elo difference with a 95% confidence interval,
+// worked out from the mean and variance of the scores (1 for a win, 1/2 for a
+// draw). To tell whether a change made an agent stronger, a sequential
+// probability ratio test (SPRT) weighs two hypotheses about the difference,
+// `elo0` and `elo1`, after every game, and stops as soon as one of them is
+// likely enough. It usually needs far fewer games than a fixed length match
+// with the same error rates.
+
+/// Wins, losses and draws from one side's point of view.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+impl Tally {
+    pub fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    /// Count a game won by `winner` (`None` for a draw), played as `color`.
+    pub fn add(&mut self, winner: Option<Color>, color: Color) {
+        match winner {
+            Some(winner) if winner == color => self.wins += 1,
+            Some(_) => self.losses += 1,
+            None => self.draws += 1,
+        }
+    }
+
+    /// The same games from the opponent's point of view.
+    pub fn reversed(&self) -> Tally {
+        Tally {
+            wins: self.losses,
+            losses: self.wins,
+            draws: self.draws,
+        }
+    }
+
+    /// The mean score, counting draws as half.
+    pub fn score(&self) -> f64 {
+        match self.games() {
+            0 => 0.5,
+            games => (self.wins as f64 + self.draws as f64 / 2.) / games as f64,
+        }
+    }
+
+    /// The variance of the score of a single game.
+    pub fn variance(&self) -> f64 {
+        let games = self.games();
+        if games == 0 {
+            return 0.;
+        }
+        let score = self.score();
+        let deviations = self.wins as f64 * (1. - score).powi(2)
+            + self.losses as f64 * score.powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2);
+        deviations / games as f64
+    }
+
+    /// The Elo difference to the opponent, with its 95% confidence interval.
+    pub fn elo(&self) -> Elo {
+        let score = self.score();
+        let error = 1.96 * (self.variance() / self.games().max(1) as f64).sqrt();
+        let lower = elo_difference(score - error);
+        let upper = elo_difference(score + error);
+        let estimate = elo_difference(score);
+        Elo {
+            estimate,
+            margin: match estimate.is_finite() {
+                true => (upper - lower) / 2.,
+                false => f64::INFINITY,
+            },
+        }
+    }
+}
+
+/// The Elo difference which gives an expected `score`. It is infinite for a
+/// score of 0 or 1.
+pub fn elo_difference(score: f64) -> f64 {
+    let score = score.clamp(0., 1.);
+    400. * (score / (1. - score)).log10()
+}
+
+/// The score expected from an Elo difference.
+pub fn expected_score(elo: f64) -> f64 {
+    1. / (1. + 10f64.powf(-elo / 400.))
+}
+
+/// An Elo difference and the half width of its 95% confidence interval.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Elo {
+    pub estimate: f64,
+    pub margin: f64,
+}
+
+impl fmt::Display for Elo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.estimate.is_finite() {
+            true => write!(f, "{:+.1} ± {:.1}", self.estimate, self.margin),
+            false => write!(f, "{:+}", self.estimate),
+        }
+    }
+}
+
+/// A sequential probability ratio test of whether an agent is `elo1` stronger
+/// than its opponent (accepted) or only `elo0` (rejected), with a chance
+/// `alpha` of wrongly accepting and `beta` of wrongly rejecting.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Default for Sprt {
+    fn default() -> Self {
+        Sprt {
+            elo0: 0.,
+            elo1: 10.,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+}
+
+/// The state of an SPRT.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SprtResult {
+    /// The agent is at least `elo1` stronger.
+    Accept,
+    /// The agent is no more than `elo0` stronger.
+    Reject,
+    /// More games are needed.
+    Continue,
+}
+
+impl Sprt {
+    /// The log likelihood ratios at which the test rejects and accepts.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1. - self.alpha)).ln(),
+            ((1. - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// The log likelihood ratio of `elo1` to `elo0` after the games in
+    /// `tally`, from a normal approximation of the scores.
+    pub fn llr(&self, tally: &Tally) -> f64 {
+        // While every game has had the same result, the variance is zero, so
+        // count an extra draw to be able to tell how likely the results are
+        let variance = match tally.variance() {
+            0. if tally.games() > 0 => Tally {
+                draws: tally.draws + 1,
+                ..*tally
+            }
+            .variance(),
+            variance => variance,
+        };
+        if variance == 0. {
+            return 0.;
+        }
+        let score0 = expected_score(self.elo0);
+        let score1 = expected_score(self.elo1);
+        tally.games() as f64 * (score1 - score0) * (2. * tally.score() - score0 - score1)
+            / (2. * variance)
+    }
+
+    pub fn test(&self, tally: &Tally) -> SprtResult {
+        let (lower, upper) = self.bounds();
+        match self.llr(tally) {
+            llr if llr >= upper => SprtResult::Accept,
+            llr if llr <= lower => SprtResult::Reject,
+            _ => SprtResult::Continue,
+        }
+    }
+}
+
+/// Who plays whom.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Every agent against every other.
+    RoundRobin,
+    /// The first agent against each of the others.
+    Gauntlet,
+}
+
+/// One game of a tournament. The agents and openings are indices.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pairing {
+    /// The game's place in the schedule.
+    pub game: usize,
+    pub round: u32,
+    pub black: usize,
+    pub white: usize,
+    pub opening: usize,
+}
+
+/// The games of `rounds` rounds between `agents` agents from `openings`
+/// openings. Each opening is played by both colors in consecutive games.
+pub fn schedule(format: Format, agents: usize, openings: usize, rounds: u32) -> Vec<Pairing> {
+    let pairs = (0..agents)
+        .flat_map(|a| (a + 1..agents).map(move |b| (a, b)))
+        .filter(|(a, _)| format == Format::RoundRobin || *a == 0)
+        .collect::<Vec<_>>();
+    let mut games = Vec::new();
+    for round in 1..=rounds {
+        for &(a, b) in &pairs {
+            for opening in 0..openings {
+                for (black, white) in [(a, b), (b, a)] {
+                    games.push(Pairing {
+                        game: games.len(),
+                        round,
+                        black,
+                        white,
+                        opening,
+                    });
+                }
+            }
+        }
+    }
+    games
+}
+
+/// How a player is limited in a game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    /// Playing on the clock, losing on time.
+    Clock(TimeControl),
+    /// A fixed number of iterations for every move, however long they take.
+    Iterations(u32),
+}
+
+/// A finished game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameOutcome {
+    /// `None` for a draw.
+    pub winner: Option<Color>,
+    /// Whether the loser ran out of time.
+    pub on_time: bool,
+    pub record: GameRecord,
+}
+
+/// Play a game from `initial` between `players`, black first. The record
+/// names them by `Player::name`.
+pub fn play_game(
+    mut players: [&mut dyn Player; 2],
+    limits: [Limit; 2],
+    initial: &State,
+) -> GameOutcome {
+    let mut clocks = limits.map(|limit| match limit {
+        Limit::Clock(control) => Some(Clock::new(control)),
+        Limit::Iterations(_) => None,
+    });
+    let manager = TimeManager::default();
+
+    let mut record = GameRecord::new();
+    record.black = players[0].name();
+    record.white = players[1].name();
+    record.set_rules(&initial.rules);
+    let position = initial.to_notation();
+    if position != State::new().to_notation() {
+        record.position = Some(position);
+    }
+
+    players
+        .iter_mut()
+        .for_each(|player| player.new_game(initial));
+    let mut state = initial.clone();
+    let mut flagged = None;
+    loop {
+        let color = state.current;
+        let s = match color {
+            Color::Black => 0,
+            Color::White => 1,
+        };
+        let m = match (&mut clocks[s], limits[s]) {
+            (Some(clock), _) => time::step_clocked(&mut *players[s], &mut state, clock, &manager),
+            (None, Limit::Iterations(n)) => {
+                step_within(&mut *players[s], &mut state, Budget::iterations(n))
+            }
+            (None, Limit::Clock(_)) => unreachable!(),
+        };
+        if clocks[s].is_some_and(|clock| clock.is_flagged()) {
+            flagged = Some(color);
+            break;
+        }
+        match m {
+            Some(m) => {
+                players[1 - s].opponent_moved(&state, m);
+                record.push(m);
+            }
+            None => break,
+        }
+    }
+    players.iter_mut().for_each(|player| player.stop());
+
+    let (winner, on_time) = match flagged {
+        Some(loser) => {
+            let winner = match loser {
+                Color::Black => Color::White,
+                Color::White => Color::Black,
+            };
+            let letter = match winner {
+                Color::Black => "B",
+                Color::White => "W",
+            };
+            record.result = format!("{}+Time", letter);
+            (Some(winner), true)
+        }
+        None => {
+            let result = state.result().expect("the game is over");
+            record.result = result.to_string();
+            (result.winner, false)
+        }
+    };
+    GameOutcome {
+        winner,
+        on_time,
+        record,
+    }
+}
+
+/// How the games of a tournament are played.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TournamentOptions {
+    /// Games played at the same time.
+    pub threads: usize,
+    /// Play a fixed number of iterations per move instead of on the clock,
+    /// for agents which don't have their own.
+    pub iterations: Option<u32>,
+    /// Seed the players, each game differently, so that with `iterations`
+    /// the tournament can be repeated.
+    pub seed: Option<u64>,
+}
+
+impl Default for TournamentOptions {
+    fn default() -> Self {
+        TournamentOptions {
+            threads: 1,
+            iterations: None,
+            seed: None,
+        }
+    }
+}
+
+/// Play `games` between `agents` from `openings`, on `options.threads`
+/// threads. Each agent plays on the clock with its own time control, unless
+/// it searches a fixed number of iterations. `result` is called on this
+/// thread as each game finishes, in no particular order; once it returns
+/// `false`, no more games are started, and those still being played are cut
+/// short and not reported.
+pub fn run(
+    agents: &[AgentSpec],
+    games: &[Pairing],
+    openings: &[State],
+    options: &TournamentOptions,
+    mut result: impl FnMut(&Pairing, &GameOutcome) -> bool,
+) {
+    let stop = StopToken::new();
+    let next = Arc::new(Mutex::new(games.iter()));
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..options.threads.max(1) {
+            let (stop, next, sender) = (stop.clone(), next.clone(), sender.clone());
+            scope.spawn(move || loop {
+                let Some(pairing) = next.lock().unwrap().next() else {
+                    break;
+                };
+                if stop.is_stopped() {
+                    break;
+                }
+                let outcome = play_pairing(agents, pairing, openings, options, &stop);
+                if stop.is_stopped() || sender.send((*pairing, outcome)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        for (pairing, outcome) in &receiver {
+            if !result(&pairing, &outcome) {
+                stop.stop();
+                break;
+            }
+        }
+    });
+}
+
+fn play_pairing(
+    agents: &[AgentSpec],
+    pairing: &Pairing,
+    openings: &[State],
+    options: &TournamentOptions,
+    stop: &StopToken,
+) -> GameOutcome {
+    let sides = [pairing.black, pairing.white];
+    let mut players = sides.map(|i| agents[i].player().expect("agents aren't human"));
+    for (color, player) in players.iter_mut().enumerate() {
+        player.set_stop(stop.clone());
+        if let Some(seed) = options.seed {
+            player.set_seed(seed.wrapping_add(2 * pairing.game as u64 + color as u64));
+        }
+    }
+    // Random doesn't search, so it has no time control worth keeping to
+    let limits = sides.map(|i| {
+        let spec = &agents[i];
+        match (spec.agent, spec.iterations.or(options.iterations)) {
+            (Agent::Random, _) => Limit::Iterations(1),
+            (_, Some(n)) => Limit::Iterations(n),
+            (agent, None) => Limit::Clock(agent.time_control()),
+        }
+    });
+    let [black, white] = &mut players;
+    let mut outcome = play_game(
+        [black.as_mut(), white.as_mut()],
+        limits,
+        &openings[pairing.opening],
+    );
+    // The players' names don't tell configurations of the same agent apart
+    let [black, white] = sides.map(|i| agents[i].to_string());
+    outcome.record.black = black;
+    outcome.record.white = white;
+    let tags = &mut outcome.record.tags;
+    tags.push(("Round".to_string(), pairing.round.to_string()));
+    tags.push(("Game".to_string(), (pairing.game + 1).to_string()));
+    tags.push(("Opening".to_string(), (pairing.opening + 1).to_string()));
+    outcome
+}
@@ -0,0 +1,154 @@
+use crate::{
+    agent::playout::Playout,
+    core::{game::State, r#move::Move},
+};
+
+use std::cmp::Reverse;
+use std::mem::size_of;
+
+// Keeping search trees in memory
+//
+// An expanded node keeps the moves it hasn't tried yet, around 1700 of them
+// early in the game, so a tree which grows without limit fills memory within
+// minutes. The trees of the MCTS agents are kept in check in three ways:
+//
+// - Moves are only generated when a node is first expanded, not when it is
+//   added, so leaves are small.
+// - With progressive widening, a node only gets more children as it is
+//   visited more, and they are tried in order of their playout weight, so
+//   that rarely visited nodes have few children.
+// - Once the tree reaches its memory budget it stops growing. The search
+//   carries on from the nodes it has, so more time still helps.
+//
+// The minimax-rs `Mcts` expands a node with all its moves at once, and its
+// tree can't be looked into, so it is bounded by rollouts instead (see
+// `mcts.rs`).
+
+/// The memory budget of a search tree unless given another.
+pub const DEFAULT_MEMORY: usize = 1 << 30;
+
+// More than the legal moves in any position
+pub(crate) const MOST_MOVES: usize = 2048;
+
+// Room kept for the moves of a newly expanded node
+const EXPANSION: usize = MOST_MOVES * size_of::<Move>();
+
+/// Progressive widening: a node visited `n` times may have up to
+/// `ceil(base * n^exponent)` children.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Widening {
+    pub base: f64,
+    pub exponent: f64,
+}
+
+impl Default for Widening {
+    fn default() -> Self {
+        Widening {
+            base: 2.,
+            exponent: 0.5,
+        }
+    }
+}
+
+impl Widening {
+    /// The number of children allowed after `visits` visits, never less than
+    /// one.
+    pub fn children(&self, visits: u32) -> usize {
+        (self.base * (visits as f64).powf(self.exponent))
+            .ceil()
+            .max(1.) as usize
+    }
+}
+
+/// The size of a search tree.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TreeStats {
+    pub nodes: usize,
+    /// Memory held by the nodes and their move lists.
+    pub bytes: usize,
+    /// Whether the tree stopped growing to stay within its budget.
+    pub full: bool,
+}
+
+/// The legal moves in `state` in increasing order of playout weight, so that
+/// the best is popped first.
+pub(crate) fn untried(state: &State, playout: &Playout) -> Vec<Move> {
+    let mut moves = Vec::new();
+    state.get_moves(&mut moves);
+    let mut weighted = moves
+        .into_iter()
+        .map(|m| (playout.weight(state, m), m))
+        .collect::<Vec<_>>();
+    weighted.sort_by_key(|(weight, _)| *weight);
+    weighted.into_iter().map(|(_, m)| m).collect()
+}
+
+/// Memory accounting for a tree stored as a vector of nodes, each of which
+/// owns its children and untried moves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Memory {
+    pub budget: usize,
+    // Bytes held by the nodes' own vectors
+    pub heap: usize,
+    pub full: bool,
+}
+
+impl Memory {
+    pub fn new(budget: usize) -> Memory {
+        Memory {
+            budget,
+            heap: 0,
+            full: false,
+        }
+    }
+
+    pub fn bytes<N>(&self, nodes: &Vec<N>) -> usize {
+        nodes.capacity() * size_of::<N>() + self.heap
+    }
+
+    /// Whether another node can be added and expanded within the budget,
+    /// allowing for the vector of nodes to grow. Once there isn't room, the
+    /// tree stays full.
+    pub fn has_room<N>(&mut self, nodes: &Vec<N>) -> bool {
+        let growth = match nodes.len() < nodes.capacity() {
+            true => 0,
+            false => nodes.capacity().max(4) * size_of::<N>(),
+        };
+        self.full |= self.bytes(nodes) + growth + EXPANSION > self.budget;
+        !self.full
+    }
+
+    /// Count a vector a node has taken ownership of.
+    pub fn add<T>(&mut self, v: &Vec<T>) {
+        self.heap += v.capacity() * size_of::<T>();
+    }
+
+    /// Push onto a node's vector, counting any growth.
+    pub fn push<T>(&mut self, v: &mut Vec<T>, value: T) {
+        let before = v.capacity();
+        v.push(value);
+        self.heap += (v.capacity() - before) * size_of::<T>();
+    }
+
+    pub fn stats<N>(&self, nodes: &Vec<N>) -> TreeStats {
+        TreeStats {
+            nodes: nodes.len(),
+            bytes: self.bytes(nodes),
+            full: self.full,
+        }
+    }
+}
+
+/// Whether a search has settled on its move: the most visited child has half
+/// as many visits again as the next one, and scores at least as well. Takes
+/// the visits and total reward of each child of the root.
+pub(crate) fn is_settled(children: impl Iterator<Item = (u32, f64)>) -> bool {
+    let mut children = children.collect::<Vec<_>>();
+    children.sort_by_key(|(visits, _)| Reverse(*visits));
+    let mean = |(visits, reward): (u32, f64)| reward / visits.max(1) as f64;
+    match children[..] {
+        [] => false,
+        [_] => true,
+        [best, second, ..] => best.0 as f64 >= 1.5 * second.0 as f64 && mean(best) >= mean(second),
+    }
+}
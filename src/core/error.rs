@@ -1,3 +1,4 @@
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Error {
     InvalidRank,
     InvalidSquare,
@@ -5,4 +6,13 @@ pub enum Error {
     InvalidOrientation,
     InvalidFormat,
     LUTEntryNotFound,
+    InvalidPosition,
+    InconsistentPosition,
+    InvalidRecord,
+    IllegalMove,
+    InvalidRules,
+    InvalidBook,
+    InvalidAgent,
+    InvalidConfig,
+    Io(std::io::ErrorKind),
 }
@@ -1,11 +1,13 @@
 use crate::core::{
     bitboard::{BitBoard, EMPTY},
     coord::{ALL_X, ALL_Y},
+    error::Error,
     move_tab::LUTEntry,
     orientation::Orientation,
     pieces::{PieceId, PieceList},
     r#move::{HasMoves, Move, MoveAccumulator, MoveVisitor},
     ray::Rays,
+    rules::{Capture, Connection, Rules},
     square::*,
     zobrist,
 };
@@ -46,7 +48,8 @@ fn is_captured(area: BitBoard, group: BitBoard) -> bool {
         && area.get_adjacent_mask().intersects(group)
 }
 
-fn find_territory(b: BitBoard, group: BitBoard) -> BitBoard {
+/// Empty areas of `b` which are enclosed by `group`.
+pub fn find_territory(b: BitBoard, group: BitBoard, connection: Connection) -> BitBoard {
     let mut seen = EMPTY;
     let mut territory = BitBoard(0);
     while seen != !EMPTY {
@@ -62,7 +65,7 @@ fn find_territory(b: BitBoard, group: BitBoard) -> BitBoard {
         if !b.test_square(pos) {
             // TODO: remove check - floodfill8 has a bailout
             // Ge the connected group
-            let area = (!b).floodfill8(pos);
+            let area = connection.area(!b, pos);
 
             if is_captured(area, group) {
                 territory |= area;
@@ -85,7 +88,35 @@ impl PlayerState {
         }
     }
 
-    pub fn place(&mut self, m: Move, other: &mut PlayerState) -> bool {
+    #[inline]
+    pub fn place(&mut self, m: Move, other: &mut PlayerState, rules: &Rules) -> bool {
+        self.place_recording(m, other, rules, &mut Vec::new())
+    }
+
+    // Opponent groups next to `m` which have no liberties left
+    fn dead_groups(&self, m: Move, other: &PlayerState, connection: Connection) -> BitBoard {
+        let empty = !(self.occupied | other.occupied);
+        let mut candidates = other.occupied & m.mask().get_adjacent_mask();
+        let mut dead = EMPTY;
+        while !candidates.is_empty() {
+            let group = connection.group(other.occupied, candidates.to_square());
+            if !group.get_adjacent_mask().intersects(empty) {
+                dead |= group;
+            }
+            candidates &= !group;
+        }
+        dead
+    }
+
+    // Same as `place`, but also records each captured move along with its
+    // index in the opponent's move list so that it can be restored later.
+    fn place_recording(
+        &mut self,
+        m: Move,
+        other: &mut PlayerState,
+        rules: &Rules,
+        captured: &mut Vec<(usize, Move)>,
+    ) -> bool {
         let piece = m.get_piece();
         self.hand.remove(piece);
         self.move_list.push(m);
@@ -93,21 +124,51 @@ impl PlayerState {
 
         let mut capture_flag = false;
 
-        let group = self.occupied.floodfill4(m.to_square());
-        let territory = find_territory(self.occupied, group);
-        if territory != EMPTY {
+        let group = rules.connection.group(self.occupied, m.to_square());
+        let mut territory = find_territory(self.occupied, group, rules.connection);
+
+        // Without extension, surrounding our own territory again claims nothing
+        if !rules.territory_extension && territory.is_subset(self.owned) {
+            territory = EMPTY;
+        }
+
+        // With liberties, capture doesn't depend on forming territory
+        let dead = match rules.capture {
+            Capture::Territory => EMPTY,
+            Capture::Liberties => self.dead_groups(m, other, rules.connection),
+        };
+
+        if territory != EMPTY || dead != EMPTY {
             // The new territory is the potential territory minus any existing territory
-            let new = (group | territory) & !(self.owned | other.owned);
+            let mut new = EMPTY;
+            if territory != EMPTY {
+                new = (group | territory) & !(self.owned | other.owned);
+            }
+
+            let remove = match rules.capture {
+                Capture::Territory => new,
+                Capture::Liberties => {
+                    // Living opponent pieces keep their squares
+                    new &= !(other.occupied & !dead);
+                    dead
+                }
+            };
 
             // Look for pieces to remove
+            let mut index = 0;
             other.move_list.retain_mut(|x| {
-                // If this isn't a boss and move was on the acquired territory
-                if x.get_piece() != PieceId::Boss && new.intersects(x.mask()) {
+                index += 1;
+
+                // If this isn't a boss and move was on the captured area
+                if x.get_piece() != PieceId::Boss && remove.intersects(x.mask()) {
+                    // Remember where it was so we can undo the capture
+                    captured.push((index - 1, *x));
+
                     // Remove from occupied map
                     other.occupied &= !x.mask();
 
                     // Add piece back to hand
-                    other.hand.add(piece);
+                    other.hand.add(x.get_piece());
 
                     // Remember to rehash the move list
                     capture_flag = true;
@@ -128,18 +189,18 @@ impl PlayerState {
                     self.owned |= x.mask();
                 }
             });
-        } else if self.owned.intersects(m.mask().get_adjacent_mask()) {
+        }
+
+        if territory == EMPTY
+            && rules.territory_extension
+            && self.owned.intersects(m.mask().get_adjacent_mask())
+        {
             self.owned |= m.mask();
         }
 
         capture_flag
     }
 
-    #[inline]
-    pub fn points(&self) -> u32 {
-        ((!self.owned & self.occupied).popcnt() >> 1) + self.occupied.popcnt()
-    }
-
     pub fn moves_str(&self) -> String {
         self.move_list
             .iter()
@@ -149,6 +210,24 @@ impl PlayerState {
     }
 }
 
+#[inline]
+pub(crate) fn draw_boss_rays(rays: &mut Rays, m: Move) {
+    use Orientation::*;
+    let nw = m.position();
+    let sw = nw.udown();
+    let se = sw.uright();
+    let ne = se.uup();
+
+    rays.draw(nw, N);
+    rays.draw(nw, W);
+    rays.draw(sw, S);
+    rays.draw(sw, W);
+    rays.draw(ne, N);
+    rays.draw(ne, E);
+    rays.draw(se, S);
+    rays.draw(se, E);
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Board {
     pub black: PlayerState,
@@ -220,7 +299,7 @@ impl Board {
         println!("   A B C D E F G H");
     }
 
-    fn redraw_rays(&mut self) {
+    pub(crate) fn redraw_rays(&mut self) {
         self.rays.clear();
         let mut ms = self.black.move_list.clone();
         ms.extend(self.white.move_list.clone());
@@ -235,24 +314,22 @@ impl Board {
 
     #[inline]
     fn draw_boss_rays(&mut self, m: Move) {
-        use Orientation::*;
-        let nw = m.position();
-        let sw = nw.udown();
-        let se = sw.uright();
-        let ne = se.uup();
-
-        self.rays.draw(nw, N);
-        self.rays.draw(nw, W);
-        self.rays.draw(sw, S);
-        self.rays.draw(sw, W);
-        self.rays.draw(ne, N);
-        self.rays.draw(ne, E);
-        self.rays.draw(se, S);
-        self.rays.draw(se, E);
+        draw_boss_rays(&mut self.rays, m);
+    }
+
+    #[inline]
+    pub fn place(&mut self, color: Color, m: Move, rules: &Rules) -> bool {
+        self.place_recording(color, m, rules, &mut Vec::new())
     }
 
     #[inline]
-    pub fn place(&mut self, color: Color, m: Move) -> bool {
+    fn place_recording(
+        &mut self,
+        color: Color,
+        m: Move,
+        rules: &Rules,
+        captured: &mut Vec<(usize, Move)>,
+    ) -> bool {
         // x decrement hand
         // x add to move_list
         // x draw rays (extra for boss)
@@ -260,8 +337,12 @@ impl Board {
         // x if boss, add to boss
         // x if connection: capture and mark territory
         let capture_flag = match color {
-            Color::Black => self.black.place(m, &mut self.white),
-            Color::White => self.white.place(m, &mut self.black),
+            Color::Black => self
+                .black
+                .place_recording(m, &mut self.white, rules, captured),
+            Color::White => self
+                .white
+                .place_recording(m, &mut self.black, rules, captured),
         };
         if m.get_piece() == PieceId::Boss {
             self.boss |= m.mask();
@@ -274,11 +355,11 @@ impl Board {
     }
 
     #[inline]
-    fn has_opposite_connection(&self, m: Move, occupied: BitBoard) -> bool {
+    pub(crate) fn has_opposite_connection(&self, m: Move, occupied: BitBoard) -> bool {
         (occupied | m.mask()).has_opposite_connection(m.to_square())
     }
 
-    pub fn generate_moves<V>(&self, color: Color, visitor: &mut V)
+    pub fn generate_moves<V>(&self, color: Color, rules: &Rules, visitor: &mut V)
     where
         V: MoveVisitor,
     {
@@ -292,9 +373,14 @@ impl Board {
             if !hash.seen(piece) {
                 hash.add(piece);
                 let p = piece.piece_type_id().def();
+                let center = (piece == PieceId::Boss && rules.boss_on_center)
+                    .then_some(LUTEntry::CENTER_BOSS);
 
-                for i in p.lut_offset..p.lut_offset + p.moves {
-                    let m = Move::new(piece, LUTEntry(i));
+                for entry in (p.lut_offset..p.lut_offset + p.moves)
+                    .map(LUTEntry)
+                    .chain(center)
+                {
+                    let m = Move::new(piece, entry);
 
                     if self.valid(m, occupied, owned) {
                         visitor.visit(m);
@@ -305,6 +391,10 @@ impl Board {
                 }
             }
         }
+
+        if rules.allow_pass {
+            visitor.visit(Move::PASS);
+        }
     }
 
     #[inline]
@@ -327,7 +417,7 @@ impl Board {
     }
 
     #[inline]
-    fn nobi_paw_overlaps(&self, piece: PieceId, occupied: BitBoard, m: Move) -> bool {
+    pub(crate) fn nobi_paw_overlaps(&self, piece: PieceId, occupied: BitBoard, m: Move) -> bool {
         if piece != PieceId::Nobi {
             return false;
         }
@@ -346,12 +436,43 @@ impl Board {
     }
 }
 
+/// Everything needed to take back a move made with `State::make`.
+///
+/// Most of the board is small enough that we simply remember the previous
+/// values. The only heap data is the list of captured opponent moves, which
+/// is empty (and unallocated) unless the move captured something.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Undo {
+    pub m: Move,
+    color: Color,
+    hands: (PieceList, PieceList),
+    occupied: (BitBoard, BitBoard),
+    owned: (BitBoard, BitBoard),
+    boss: BitBoard,
+    rays: Rays,
+    hash: u64,
+    capture_flag: bool,
+    passes: u8,
+    captured: Vec<(usize, Move)>,
+}
+
+impl Undo {
+    // Pieces which were removed from the board by this move
+    #[inline]
+    pub fn captured(&self) -> impl Iterator<Item = Move> + '_ {
+        self.captured.iter().map(|(_, m)| *m)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct State {
     pub current: Color,
     pub board: Board,
     pub hash: u64,
     pub capture_flag: bool,
+    // Consecutive passes. The game is over after two.
+    pub passes: u8,
+    pub rules: Rules,
 }
 
 impl Default for State {
@@ -363,59 +484,209 @@ impl Default for State {
 impl State {
     #[inline]
     pub fn new() -> State {
+        Self::with_rules(Rules::standard())
+    }
+
+    #[inline]
+    pub fn with_rules(rules: Rules) -> State {
         State {
             current: Color::Black,
             board: Board::new(),
             hash: 0,
             capture_flag: false,
+            passes: 0,
+            rules,
         }
     }
 
     #[inline]
     pub fn has_moves(&self) -> bool {
+        if self.passes >= 2 {
+            return false;
+        }
         let mut hm = HasMoves(false);
-        self.board.generate_moves(self.current, &mut hm);
+        self.board
+            .generate_moves(self.current, &self.rules, &mut hm);
         hm.0
     }
 
     #[inline]
     pub fn get_moves(&self, moves: &mut Vec<Move>) {
+        if self.passes >= 2 {
+            return;
+        }
         let mut ma = MoveAccumulator::new();
-        self.board.generate_moves(self.current, &mut ma);
+        self.board
+            .generate_moves(self.current, &self.rules, &mut ma);
         moves.append(&mut ma.0)
     }
 
+    /// Parse a move for the player to move. If the notation doesn't say which
+    /// instance of a duplicated piece is played (e.g., `KB3:C4W`), it is taken
+    /// from the hand in the same order used by move generation.
+    pub fn parse_move(&self, s: &str) -> Result<Move, Error> {
+        let m = Move::parse(s)?;
+        if m.is_pass() {
+            return match self.rules.allow_pass {
+                true => Ok(m),
+                false => Err(Error::IllegalMove),
+            };
+        }
+        let hand = match self.current {
+            Color::Black => self.board.black.hand,
+            Color::White => self.board.white.hand,
+        };
+
+        let (piece_str, _) = s.split_once(':').ok_or(Error::InvalidFormat)?;
+        let piece = if piece_str == m.get_piece().notation() {
+            Some(m.get_piece())
+        } else {
+            let piece_type = m.get_piece().piece_type_id();
+            hand.into_iter().find(|p| p.piece_type_id() == piece_type)
+        };
+
+        match piece {
+            Some(piece) if hand.holding(piece) => Ok(Move::new(piece, m.get_lut_entry())),
+            _ => Err(Error::IllegalMove),
+        }
+    }
+
     #[inline]
     pub fn apply(&mut self, m: Move) {
         self.place(m);
         self.update_hash(m);
-        self.current = self.current.next();
+        self.end_turn(m);
     }
 
+    // Passing doesn't change the board
     #[inline]
     pub fn place(&mut self, m: Move) {
-        self.capture_flag = self.board.place(self.current, m);
+        if !m.is_pass() {
+            self.capture_flag = self.board.place(self.current, m, &self.rules);
+        }
+    }
+
+    #[inline]
+    fn end_turn(&mut self, m: Move) {
+        self.hash ^= Self::hash_turn(self.current, self.passes);
+        self.passes = match m.is_pass() {
+            true => self.passes + 1,
+            false => 0,
+        };
+        self.current = self.current.next();
+        self.hash ^= Self::hash_turn(self.current, self.passes);
+    }
+
+    /// Apply a move in place, returning the information required to take it
+    /// back with `State::undo`.
+    pub fn make(&mut self, m: Move) -> Undo {
+        let board = &self.board;
+        let mut undo = Undo {
+            m,
+            color: self.current,
+            hands: (board.black.hand, board.white.hand),
+            occupied: (board.black.occupied, board.white.occupied),
+            owned: (board.black.owned, board.white.owned),
+            boss: board.boss,
+            rays: board.rays.clone(),
+            hash: self.hash,
+            capture_flag: self.capture_flag,
+            passes: self.passes,
+            captured: Vec::new(),
+        };
+
+        if !m.is_pass() {
+            self.capture_flag =
+                self.board
+                    .place_recording(self.current, m, &self.rules, &mut undo.captured);
+        }
+        self.update_hash(m);
+        self.end_turn(m);
+        undo
+    }
+
+    /// Take back the move recorded by `undo`. Moves must be undone in the
+    /// reverse order that they were made.
+    pub fn undo(&mut self, undo: Undo) {
+        let board = &mut self.board;
+        let (player, other) = match undo.color {
+            Color::Black => (&mut board.black, &mut board.white),
+            Color::White => (&mut board.white, &mut board.black),
+        };
+
+        if !undo.m.is_pass() {
+            let last = player.move_list.pop();
+            debug_assert_eq!(last, Some(undo.m));
+        }
+
+        // Indices were recorded in ascending order, so reinserting them in the
+        // same order puts each move back in its original position.
+        for (i, x) in undo.captured {
+            other.move_list.insert(i, x);
+        }
+
+        (board.black.hand, board.white.hand) = undo.hands;
+        (board.black.occupied, board.white.occupied) = undo.occupied;
+        (board.black.owned, board.white.owned) = undo.owned;
+        board.boss = undo.boss;
+        board.rays = undo.rays;
+
+        self.hash = undo.hash;
+        self.capture_flag = undo.capture_flag;
+        self.passes = undo.passes;
+        self.current = undo.color;
     }
 
     #[inline]
     pub fn hash(&self, m: Move) -> u64 {
+        Self::hash_for(self.current, m)
+    }
+
+    // Equivalent positions under rotation or reflection hash differently. See
+    // `State::canonical_hash` for a hash which merges them.
+    #[inline]
+    pub(crate) fn hash_for(color: Color, m: Move) -> u64 {
         let index = m.get_lut_entry().0;
-        let color = self.current as usize & 1;
-        // rotational symmetry can't be leveraged because bosses are immobile
+        let color = color as usize & 1;
         zobrist::HASHES[(index << 1) | color]
     }
 
     #[inline]
-    fn hash_move_list(&self, init: u64, move_list: &[Move]) -> u64 {
-        move_list.iter().fold(init, |h, &p| h ^ self.hash(p))
+    fn hash_move_list(init: u64, color: Color, move_list: &[Move]) -> u64 {
+        move_list
+            .iter()
+            .fold(init, |h, &p| h ^ Self::hash_for(color, p))
+    }
+
+    // The side to move and the number of consecutive passes
+    #[inline]
+    pub(crate) fn hash_turn(color: Color, passes: u8) -> u64 {
+        let side = match color {
+            Color::Black => 0,
+            Color::White => zobrist::SIDE,
+        };
+        side ^ zobrist::PASSES[passes.min(2) as usize]
     }
 
+    // The hash of the pieces on the board, independent of move order, along
+    // with whose turn it is
+    #[inline]
+    pub(crate) fn rehash(&self) -> u64 {
+        let hash_black = Self::hash_move_list(0, Color::Black, &self.board.black.move_list);
+        let hash = Self::hash_move_list(hash_black, Color::White, &self.board.white.move_list);
+        hash ^ Self::hash_turn(self.current, self.passes)
+    }
+
+    // Update the hash for the pieces after a move is placed, but before the
+    // turn ends
     #[inline]
     pub fn update_hash(&mut self, m: Move) {
+        if m.is_pass() {
+            return;
+        }
         if self.capture_flag {
             // Upon capture, we need to rehash everything since we removed pieces
-            let hash_black = self.hash_move_list(0, &self.board.black.move_list);
-            self.hash = self.hash_move_list(hash_black, &self.board.white.move_list);
+            self.hash = self.rehash();
             self.board.redraw_rays();
             self.capture_flag = false;
         } else {
@@ -433,14 +704,16 @@ impl State {
         println!("Owned:");
         self.board.print_owner_map();
 
-        println!("Black [{}]", self.board.black.points());
+        let score = self.score();
+        println!("Black [{}]", score.black.score);
         print!("- hand: ");
         self.board.black.hand.dump();
         println!("- moves: {}", self.board.black.moves_str());
 
-        println!("White [{}]", self.board.white.points());
+        println!("White [{}]", score.white.score);
         print!("- hand: ");
         self.board.white.hand.dump();
         println!("- moves: {}", self.board.white.moves_str());
+        println!("Score: {}", score);
     }
 }
@@ -0,0 +1,212 @@
+use crate::core::{
+    game::{draw_boss_rays, Board, Color, State},
+    move_tab::LUTEntry,
+    pieces::PieceId,
+    r#move::Move,
+    ray::Rays,
+};
+
+use std::fmt;
+
+// The rules a placement may violate. `Board::generate_moves` only checks the
+// board related rules, since it already knows which pieces are in hand.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Violation {
+    NotInHand,
+    BossFirst,
+    Overlap,
+    OwnedTerritory,
+    BossOnCenter,
+    EyeContact,
+    FacingBoss,
+    NobiPaw,
+    OppositeConnection,
+    Pass,
+}
+
+pub const ALL_VIOLATIONS: [Violation; 10] = [
+    Violation::NotInHand,
+    Violation::BossFirst,
+    Violation::Overlap,
+    Violation::OwnedTerritory,
+    Violation::BossOnCenter,
+    Violation::EyeContact,
+    Violation::FacingBoss,
+    Violation::NobiPaw,
+    Violation::OppositeConnection,
+    Violation::Pass,
+];
+
+impl Violation {
+    pub fn description(self) -> &'static str {
+        match self {
+            Violation::NotInHand => "the piece is not in hand",
+            Violation::BossFirst => "the boss must be placed first",
+            Violation::Overlap => "overlaps another piece",
+            Violation::OwnedTerritory => "inside the opponent's territory",
+            Violation::BossOnCenter => "the boss may not be placed on the center",
+            Violation::EyeContact => "makes eye contact with another neko",
+            Violation::FacingBoss => "looks at a boss",
+            Violation::NobiPaw => "the nobi's paw touches its own boss",
+            Violation::OppositeConnection => "connects opposite sides of the board",
+            Violation::Pass => "passing is not allowed",
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.description())
+    }
+}
+
+/// The set of rules violated by an illegal placement.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct IllegalReason(u16);
+
+impl IllegalReason {
+    #[inline]
+    fn mask(violation: Violation) -> u16 {
+        1 << violation as u8
+    }
+
+    #[inline]
+    pub fn contains(self, violation: Violation) -> bool {
+        self.0 & Self::mask(violation) != 0
+    }
+
+    #[inline]
+    fn add(&mut self, violation: Violation) {
+        self.0 |= Self::mask(violation);
+    }
+
+    #[inline]
+    fn remove(&mut self, violation: Violation) {
+        self.0 &= !Self::mask(violation);
+    }
+
+    pub fn violations(self) -> impl Iterator<Item = Violation> {
+        ALL_VIOLATIONS
+            .into_iter()
+            .filter(move |v| self.contains(*v))
+    }
+}
+
+impl fmt::Debug for IllegalReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.violations()).finish()
+    }
+}
+
+impl fmt::Display for IllegalReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let descriptions = self
+            .violations()
+            .map(Violation::description)
+            .collect::<Vec<&str>>();
+        write!(f, "{}", descriptions.join(", "))
+    }
+}
+
+impl Board {
+    // Rays drawn by the bosses and by the other pieces, separately.
+    fn split_rays(&self) -> (Rays, Rays) {
+        let mut boss = Rays::new();
+        let mut other = Rays::new();
+        self.black
+            .move_list
+            .iter()
+            .chain(self.white.move_list.iter())
+            .for_each(|m| {
+                if m.get_piece() == PieceId::Boss {
+                    draw_boss_rays(&mut boss, *m);
+                } else {
+                    other.draw(m.gaze().to_square(), m.orientation());
+                }
+            });
+        (boss, other)
+    }
+
+    /// Explain why a move is illegal for the given player under the standard
+    /// rules. This makes the same checks as move generation, but reports every
+    /// rule which is violated rather than stopping at the first one. It is too
+    /// slow for searching.
+    pub fn check_move(&self, color: Color, m: Move) -> Result<(), IllegalReason> {
+        let (player, other) = match color {
+            Color::Black => (&self.black, &self.white),
+            Color::White => (&self.white, &self.black),
+        };
+
+        let mut reason = IllegalReason(0);
+        if m.is_pass() {
+            reason.add(Violation::Pass);
+            return Err(reason);
+        }
+
+        let piece = m.get_piece();
+
+        if !player.hand.holding(piece) {
+            reason.add(Violation::NotInHand);
+        }
+        if piece != PieceId::Boss && player.hand.holding(PieceId::Boss) {
+            reason.add(Violation::BossFirst);
+        }
+        if m.mask().intersects(self.occupied()) {
+            reason.add(Violation::Overlap);
+        }
+        if m.mask().intersects(other.owned) {
+            reason.add(Violation::OwnedTerritory);
+        }
+        if m.get_lut_entry() == LUTEntry::CENTER_BOSS {
+            reason.add(Violation::BossOnCenter);
+        }
+
+        if piece != PieceId::Boss {
+            let (boss_rays, other_rays) = self.split_rays();
+            let facing = m.orientation().opposite();
+
+            if m.gaze().intersects(other_rays.get(facing)) {
+                reason.add(Violation::EyeContact);
+            }
+            if m.gaze().intersects(boss_rays.get(facing)) {
+                reason.add(Violation::FacingBoss);
+            }
+            if self.nobi_paw_overlaps(piece, player.occupied, m) {
+                reason.add(Violation::NobiPaw);
+            }
+            if self.has_opposite_connection(m, player.occupied) {
+                reason.add(Violation::OppositeConnection);
+            }
+        }
+
+        if reason.0 == 0 {
+            Ok(())
+        } else {
+            Err(reason)
+        }
+    }
+}
+
+impl State {
+    /// Explain why a move is illegal for the player to move, taking the
+    /// position's rule variants into account.
+    pub fn check_move(&self, m: Move) -> Result<(), IllegalReason> {
+        match self.board.check_move(self.current, m) {
+            Err(mut reason) => {
+                if self.rules.boss_on_center {
+                    reason.remove(Violation::BossOnCenter);
+                }
+                if self.rules.allow_pass {
+                    reason.remove(Violation::Pass);
+                }
+                if reason.0 == 0 {
+                    Ok(())
+                } else {
+                    Err(reason)
+                }
+            }
+            Ok(()) => Ok(()),
+        }
+    }
+}
@@ -2,10 +2,17 @@ pub mod bitboard;
 pub mod coord;
 pub mod error;
 pub mod game;
+pub mod legality;
 pub mod r#move;
 pub mod move_tab;
 pub mod orientation;
+pub mod perft;
 pub mod pieces;
+pub mod position;
 pub mod ray;
+pub mod record;
+pub mod rules;
+pub mod score;
 pub mod square;
+pub mod symmetry;
 pub mod zobrist;
@@ -16,6 +16,11 @@ use std::str::FromStr;
 pub struct Move(u16);
 
 impl Move {
+    /// Passing instead of placing a piece. It is only generated when the rules
+    /// allow it, and it has no piece or placement: check `is_pass` before
+    /// asking for them.
+    pub const PASS: Move = Move(u16::MAX);
+
     #[inline]
     pub fn new(piece: PieceId, entry: LUTEntry) -> Move {
         let p = (piece as u16) & 0b1111;
@@ -23,17 +28,30 @@ impl Move {
         Move(e | p)
     }
 
+    #[inline]
+    pub fn is_pass(self) -> bool {
+        self == Move::PASS
+    }
+
     #[inline]
     pub fn get_raw_value(self) -> u16 {
         self.0
     }
 
+    /// The inverse of `get_raw_value`. The value isn't checked.
+    #[inline]
+    pub fn from_raw_value(raw: u16) -> Move {
+        Move(raw)
+    }
+
     #[inline]
     pub fn get_piece(self) -> PieceId {
+        debug_assert!(!self.is_pass(), "a pass has no piece");
         PieceId::from_index(self.0 & 0b1111).unwrap()
     }
 
     pub fn get_lut_entry(self) -> LUTEntry {
+        debug_assert!(!self.is_pass(), "a pass has no placement");
         LUTEntry(self.0.wrapping_shr(4) as usize)
     }
 
@@ -63,21 +81,30 @@ impl Move {
         self.get_lut_entry().gaze()
     }
 
+    // Duplicated pieces include their instance (e.g., KB3a or KB3b) so that the
+    // notation identifies the move exactly.
     pub fn notation(&self) -> String {
+        if self.is_pass() {
+            return "PASS".to_string();
+        }
         format!(
             "{}:{}{:?}",
-            self.get_piece().piece_type_id().notation(),
+            self.get_piece().notation(),
             self.position().to_string().to_uppercase(),
             self.orientation()
         )
         .to_string()
     }
 
+    // Parse a move in the format produced by `Move::notation`. If the instance
+    // of a duplicated piece is omitted (e.g., KB3 rather than KB3a), the first
+    // instance is assumed. Use `State::parse_move` to resolve it from the hand.
     pub fn parse(s: &str) -> Result<Move, Error> {
-        if s.len() != 8 {
-            return Err(Error::InvalidFormat);
+        if s == "PASS" {
+            return Ok(Move::PASS);
         }
-        if s.as_bytes()[4] != b':' {
+        let (piece_str, placement) = s.split_once(':').ok_or(Error::InvalidFormat)?;
+        if placement.len() != 3 || !placement.is_ascii() {
             return Err(Error::InvalidFormat);
         }
 
@@ -97,24 +124,16 @@ impl Move {
             }
         }
 
-        let piece_type = parse_piece_type(&s[0..4])?;
-        let x = X::from_str(&s.as_bytes()[5].to_string())?;
-        let y = Y::from_str(&s.as_bytes()[6].to_string())?;
-        let position = Square::make_square(x, y);
-        let orientation = Orientation::from_str(&s.as_bytes()[7].to_string())?;
-
-        let piece = match piece_type {
-            PieceTypeId::Boss => PieceId::Boss,
-            PieceTypeId::Mame => PieceId::Mame,
-            PieceTypeId::Nobi => PieceId::Nobi,
-            PieceTypeId::Koubaku1 => PieceId::Koubaku1,
-            PieceTypeId::Koubaku2 => PieceId::Koubaku2,
-            PieceTypeId::Koubaku3 => PieceId::Koubaku3a, // may need reindexing
-            PieceTypeId::Kunoji1 => PieceId::Kunoji1a,   // may need reindexing
-            PieceTypeId::Kunoji2 => PieceId::Kunoji2,
-            PieceTypeId::Kunoji3 => PieceId::Kunoji3,
-            PieceTypeId::Kunoji4 => PieceId::Kunoji4,
+        let piece = match PieceId::from_str(piece_str) {
+            Ok(piece) => piece,
+            Err(_) => parse_piece_type(piece_str)?.to_piece_id(),
         };
+        let piece_type = piece.piece_type_id();
+
+        let x = X::from_str(&placement[0..1])?;
+        let y = Y::from_str(&placement[1..2])?;
+        let position = Square::make_square(x, y);
+        let orientation = Orientation::from_str(&placement[2..3])?;
 
         let entry =
             LUTEntry::lookup(piece_type, position, orientation).ok_or(Error::LUTEntryNotFound)?;
@@ -33,6 +33,10 @@ impl LUTEntry {
         &MOVE_TAB[self.0]
     }
 
+    /// The boss on the center of the board. It follows the other entries so
+    /// that it is never produced by iterating over the boss moves.
+    pub const CENTER_BOSS: LUTEntry = LUTEntry(1724);
+
     #[inline]
     pub fn lookup(
         piece: PieceTypeId,
@@ -49,11 +53,18 @@ impl LUTEntry {
                 return Some(LUTEntry(i));
             }
         }
+        let center = LUTEntry::CENTER_BOSS;
+        if piece == PieceTypeId::Boss
+            && center.orientation() == orientation
+            && center.position() == position
+        {
+            return Some(center);
+        }
         None
     }
 }
 
-const MOVE_TAB: [Entry; 1724] = [
+const MOVE_TAB: [Entry; 1725] = [
     // Boss moves (48)
     (0, 0x00, 0x0000000000000303, 0x0000000000000001), // BOS A1S
     (0, 0x01, 0x0000000000000606, 0x0000000000000002), // BOS B1S
@@ -1789,4 +1800,6 @@ const MOVE_TAB: [Entry; 1724] = [
     (3, 0x34, 0x3010000000000000, 0x0010000000000000), // KJ4 E7E
     (3, 0x35, 0x6020000000000000, 0x0020000000000000), // KJ4 F7E
     (3, 0x36, 0xc040000000000000, 0x0040000000000000), // KJ4 G7E
+    // Boss on the center, only allowed by `Rules::boss_on_center`
+    (0, 0x1b, 0x0000001818000000, 0x0000000008000000), // BOS D4S
 ];
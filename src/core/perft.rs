@@ -0,0 +1,111 @@
+use crate::core::{
+    bitboard::BitBoard,
+    game::{Color, State},
+    r#move::Move,
+};
+
+use std::ops::AddAssign;
+
+// Perft
+//
+// Counts the positions reachable at a given depth by walking the full game
+// tree. The numbers are only useful for comparison: if they change, then move
+// generation or move application has changed. Moves at the last ply are also
+// classified so that changes to capture and territory can be seen separately.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PerftStats {
+    /// Positions at the requested depth.
+    pub nodes: u64,
+    /// Moves at the last ply which captured opponent pieces.
+    pub captures: u64,
+    /// Moves at the last ply which claimed new territory.
+    pub territory: u64,
+    /// Positions at the requested depth which end the game.
+    pub terminal: u64,
+}
+
+impl AddAssign for PerftStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.nodes += rhs.nodes;
+        self.captures += rhs.captures;
+        self.territory += rhs.territory;
+        self.terminal += rhs.terminal;
+    }
+}
+
+#[inline]
+fn owned(state: &State, color: Color) -> BitBoard {
+    match color {
+        Color::Black => state.board.black.owned,
+        Color::White => state.board.white.owned,
+    }
+}
+
+// Apply `m` and count it as a leaf
+fn perft_leaf(state: &mut State, m: Move) -> PerftStats {
+    let color = state.current;
+    let before = owned(state, color);
+    let undo = state.make(m);
+
+    // Extending territory only adds the piece itself
+    let claimed = owned(state, color) & !before;
+    let stats = PerftStats {
+        nodes: 1,
+        captures: undo.captured().next().is_some() as u64,
+        territory: (!m.is_pass() && !claimed.is_subset(m.mask())) as u64,
+        terminal: !state.has_moves() as u64,
+    };
+
+    state.undo(undo);
+    stats
+}
+
+/// Walk the game tree to `depth`, restoring `state` afterwards.
+pub fn perft(state: &mut State, depth: usize) -> PerftStats {
+    if depth == 0 {
+        return PerftStats {
+            nodes: 1,
+            terminal: !state.has_moves() as u64,
+            ..Default::default()
+        };
+    }
+
+    let mut moves = Vec::new();
+    state.get_moves(&mut moves);
+
+    let mut stats = PerftStats::default();
+    for m in moves {
+        if depth == 1 {
+            stats += perft_leaf(state, m);
+        } else {
+            let undo = state.make(m);
+            stats += perft(state, depth - 1);
+            state.undo(undo);
+        }
+    }
+    stats
+}
+
+/// Perft for each root move, in move generation order.
+pub fn perft_divide(state: &mut State, depth: usize) -> Vec<(Move, PerftStats)> {
+    assert!(depth > 0);
+
+    let mut moves = Vec::new();
+    state.get_moves(&mut moves);
+
+    moves
+        .into_iter()
+        .map(|m| {
+            let stats = if depth == 1 {
+                perft_leaf(state, m)
+            } else {
+                let undo = state.make(m);
+                let stats = perft(state, depth - 1);
+                state.undo(undo);
+                stats
+            };
+            (m, stats)
+        })
+        .collect()
+}
@@ -1,4 +1,6 @@
-use crate::core::orientation::Orientation;
+use crate::core::{error::Error, orientation::Orientation};
+
+use std::str::FromStr;
 
 pub struct PieceType {
     pub name_en: &'static str,
@@ -151,6 +153,17 @@ impl PieceId {
         }
     }
 
+    // Like the piece type notation, but distinguishes between the two copies
+    // of the duplicated pieces (e.g., KB3a and KB3b.)
+    pub fn notation(self) -> String {
+        let type_notation = self.piece_type_id().notation();
+        match self {
+            PieceId::Koubaku3a | PieceId::Kunoji1a => format!("{}a", type_notation),
+            PieceId::Koubaku3b | PieceId::Kunoji1b => format!("{}b", type_notation),
+            _ => type_notation,
+        }
+    }
+
     #[inline]
     pub fn piece_type_id(self) -> PieceTypeId {
         match self {
@@ -170,6 +183,17 @@ impl PieceId {
     }
 }
 
+impl FromStr for PieceId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ALL_PIECES_IDS
+            .into_iter()
+            .find(|piece| piece.notation() == s)
+            .ok_or(Error::InvalidPiece)
+    }
+}
+
 pub struct PieceSeenHash(u16);
 
 impl PieceSeenHash {
@@ -204,6 +228,20 @@ impl PieceList {
         self.0 == 0
     }
 
+    #[inline]
+    pub fn from_bits(bits: u16) -> Option<PieceList> {
+        if bits & !0xfff == 0 {
+            Some(PieceList(bits))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn bits(&self) -> u16 {
+        self.0
+    }
+
     #[inline]
     pub fn available(self) -> PieceList {
         if self.holding(PieceId::Boss) {
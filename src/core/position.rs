@@ -0,0 +1,194 @@
+use crate::core::{
+    bitboard::{BitBoard, EMPTY},
+    error::Error,
+    game::{Board, Color, PlayerState, State},
+    pieces::{PieceId, PieceList},
+    r#move::Move,
+};
+
+// Position notation
+//
+// A compact, FEN-like description of a `State`. There are three fields
+// separated by spaces, and a fourth when the last move was a pass:
+//
+// 1. Moves on the board in the order they were played, black then white,
+//    separated by a `/`. Each side is a comma separated list of moves, or `-`
+//    if it has none.
+// 2. Owned territory as hexadecimal bitboards, black then white, separated by `/`.
+//    It depends on the order pieces were played in and what was captured, so
+//    it can't be worked out from the moves.
+// 3. The side to move, either `b` or `w`.
+// 4. The number of consecutive passes, omitted when there are none.
+//
+// The hands and the boss mask follow from the moves: every piece which isn't
+// on the board is in hand.
+//
+// The starting position is written as: `-/- 0/0 b`
+
+fn moves_to_notation(moves: &[Move]) -> String {
+    if moves.is_empty() {
+        return "-".to_string();
+    }
+    moves
+        .iter()
+        .map(|m| m.notation())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn parse_moves(s: &str) -> Result<Vec<Move>, Error> {
+    if s == "-" {
+        return Ok(Vec::new());
+    }
+    s.split(',').map(Move::parse).collect()
+}
+
+fn parse_pair(s: &str) -> Result<(&str, &str), Error> {
+    s.split_once('/').ok_or(Error::InvalidPosition)
+}
+
+fn parse_bitboard(s: &str) -> Result<BitBoard, Error> {
+    u64::from_str_radix(s, 16)
+        .map(BitBoard)
+        .map_err(|_| Error::InvalidPosition)
+}
+
+fn parse_player(moves: &str, owned: &str) -> Result<PlayerState, Error> {
+    let mut player = PlayerState::new();
+    player.move_list = parse_moves(moves)?;
+    for m in &player.move_list {
+        // A piece played twice is caught by `validate`
+        player.hand.remove(m.get_piece());
+    }
+    player.occupied = player
+        .move_list
+        .iter()
+        .fold(EMPTY, |occupied, m| occupied | m.mask());
+    player.owned = parse_bitboard(owned)?;
+    Ok(player)
+}
+
+impl State {
+    pub fn to_notation(&self) -> String {
+        let black = &self.board.black;
+        let white = &self.board.white;
+        let mut notation = format!(
+            "{}/{} {:x}/{:x} {}",
+            moves_to_notation(&black.move_list),
+            moves_to_notation(&white.move_list),
+            black.owned.0,
+            white.owned.0,
+            match self.current {
+                Color::Black => "b",
+                Color::White => "w",
+            }
+        );
+        if self.passes > 0 {
+            notation += &format!(" {}", self.passes);
+        }
+        notation
+    }
+
+    pub fn from_notation(s: &str) -> Result<State, Error> {
+        let fields = s.split_whitespace().collect::<Vec<&str>>();
+        let (moves, owned, current, passes) = match fields[..] {
+            [moves, owned, current] => (moves, owned, current, "0"),
+            [moves, owned, current, passes] => (moves, owned, current, passes),
+            _ => return Err(Error::InvalidPosition),
+        };
+
+        let (black_moves, white_moves) = parse_pair(moves)?;
+        let (black_owned, white_owned) = parse_pair(owned)?;
+
+        let mut state = State::new();
+        state.current = match current {
+            "b" => Color::Black,
+            "w" => Color::White,
+            _ => return Err(Error::InvalidPosition),
+        };
+        state.passes = match passes.parse() {
+            Ok(n) if n <= 2 => n,
+            _ => return Err(Error::InvalidPosition),
+        };
+        state.board.black = parse_player(black_moves, black_owned)?;
+        state.board.white = parse_player(white_moves, white_owned)?;
+        state.board.boss = state.board.boss_mask();
+        state.board.validate()?;
+
+        state.board.redraw_rays();
+        state.hash = state.rehash();
+        Ok(state)
+    }
+}
+
+impl PlayerState {
+    fn validate(&self, other: &PlayerState) -> Result<(), Error> {
+        // Every piece is either in hand or on the board, but not both
+        let mut placed = PieceList::from_bits(0).unwrap();
+        let mut occupied = EMPTY;
+        for m in &self.move_list {
+            let piece = m.get_piece();
+            if placed.holding(piece) || self.hand.holding(piece) {
+                return Err(Error::InconsistentPosition);
+            }
+            placed.add(piece);
+
+            if occupied.intersects(m.mask()) {
+                return Err(Error::InconsistentPosition);
+            }
+            occupied |= m.mask();
+        }
+        if (placed.bits() | self.hand.bits()) != PieceList::full().bits() {
+            return Err(Error::InconsistentPosition);
+        }
+
+        // The boss is always the first piece placed, and it can't be captured
+        if let Some(first) = self.move_list.first() {
+            if first.get_piece() != PieceId::Boss {
+                return Err(Error::InconsistentPosition);
+            }
+        }
+
+        // Territory can't contain any of the opponent's pieces, other than a boss
+        let other_pieces = other
+            .move_list
+            .iter()
+            .filter(|m| m.get_piece() != PieceId::Boss)
+            .fold(EMPTY, |b, m| b | m.mask());
+
+        if occupied != self.occupied
+            || self.owned.intersects(other.owned)
+            || self.owned.intersects(other_pieces)
+        {
+            return Err(Error::InconsistentPosition);
+        }
+
+        Ok(())
+    }
+}
+
+impl Board {
+    // The squares covered by the bosses of both players
+    fn boss_mask(&self) -> BitBoard {
+        self.black
+            .move_list
+            .iter()
+            .chain(self.white.move_list.iter())
+            .filter(|m| m.get_piece() == PieceId::Boss)
+            .fold(EMPTY, |b, m| b | m.mask())
+    }
+
+    /// Check that the board is internally consistent: the occupied masks and
+    /// hands agree with the move lists, pieces don't overlap, and territory
+    /// and the boss mask are plausible.
+    pub fn validate(&self) -> Result<(), Error> {
+        self.black.validate(&self.white)?;
+        self.white.validate(&self.black)?;
+
+        if self.black.occupied.intersects(self.white.occupied) || self.boss_mask() != self.boss {
+            return Err(Error::InconsistentPosition);
+        }
+
+        Ok(())
+    }
+}
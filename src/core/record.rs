@@ -0,0 +1,320 @@
+use crate::core::{
+    error::Error,
+    game::State,
+    r#move::Move,
+    rules::{Komi, Rules},
+};
+
+use std::fmt;
+use std::io::{self, BufRead, Lines};
+use std::iter::Peekable;
+
+// Game records
+//
+// A record is a block of PGN style tags followed by the moves, one per line.
+// A move may be followed by a comment introduced with `;`. Records are
+// separated by a blank line, so several games can be stored in one file.
+//
+//     [Black "Random"]
+//     [White "Iterative(60s)"]
+//     [Komi "0.5"]
+//     [Rules "standard"]
+//     [Date "2024-02-11"]
+//     [Result "*"]
+//
+//     BOS:D3S
+//     BOS:F6S ; a comment
+//
+// An optional `Position` tag holds a starting position in position notation.
+// The `Rules` tag describes any rule variants, while komi has its own tag.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedMove {
+    pub m: Move,
+    pub comment: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameRecord {
+    pub black: String,
+    pub white: String,
+    pub komi: Komi,
+    pub rules: String,
+    pub date: String,
+    pub result: String,
+    pub position: Option<String>,
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<RecordedMove>,
+}
+
+impl Default for GameRecord {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GameRecord {
+    pub fn new() -> GameRecord {
+        GameRecord {
+            black: "?".to_string(),
+            white: "?".to_string(),
+            komi: Komi::default(),
+            rules: "standard".to_string(),
+            date: today(),
+            result: "*".to_string(),
+            position: None,
+            tags: Vec::new(),
+            moves: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn push(&mut self, m: Move) {
+        self.moves.push(RecordedMove { m, comment: None });
+    }
+
+    pub fn push_with_comment(&mut self, m: Move, comment: &str) {
+        self.moves.push(RecordedMove {
+            m,
+            comment: Some(comment.to_string()),
+        });
+    }
+
+    /// The rules the game was played with. The `Komi` tag takes precedence
+    /// over any komi in the `Rules` tag.
+    pub fn rules(&self) -> Result<Rules, Error> {
+        let mut rules = self
+            .rules
+            .parse::<Rules>()
+            .map_err(|_| Error::InvalidRecord)?;
+        rules.komi = self.komi;
+        Ok(rules)
+    }
+
+    pub fn set_rules(&mut self, rules: &Rules) {
+        self.komi = rules.komi;
+        self.rules = Rules {
+            komi: Komi::default(),
+            ..rules.clone()
+        }
+        .to_string();
+    }
+
+    pub fn initial_state(&self) -> Result<State, Error> {
+        let mut state = match &self.position {
+            Some(position) => State::from_notation(position)?,
+            None => State::new(),
+        };
+        state.rules = self.rules()?;
+        Ok(state)
+    }
+
+    /// Replay the game from its initial position, checking that each move is
+    /// legal. Returns the final position.
+    pub fn replay(&self) -> Result<State, Error> {
+        let mut state = self.initial_state()?;
+        let mut moves = Vec::new();
+        for recorded in &self.moves {
+            moves.truncate(0);
+            state.get_moves(&mut moves);
+            if !moves.contains(&recorded.m) {
+                return Err(Error::IllegalMove);
+            }
+            state.apply(recorded.m);
+        }
+        Ok(state)
+    }
+
+    pub fn write<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        write!(w, "{}", self)
+    }
+
+    fn set_tag(&mut self, key: &str, value: &str) -> Result<(), Error> {
+        match key {
+            "Black" => self.black = value.to_string(),
+            "White" => self.white = value.to_string(),
+            "Komi" => self.komi = value.parse().map_err(|_| Error::InvalidRecord)?,
+            "Rules" => self.rules = value.to_string(),
+            "Date" => self.date = value.to_string(),
+            "Result" => self.result = value.to_string(),
+            "Position" => self.position = Some(value.to_string()),
+            _ => self.tags.push((key.to_string(), value.to_string())),
+        }
+        Ok(())
+    }
+}
+
+fn write_tag(f: &mut fmt::Formatter, key: &str, value: &str) -> fmt::Result {
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+    writeln!(f, "[{} \"{}\"]", key, value)
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_tag(f, "Black", &self.black)?;
+        write_tag(f, "White", &self.white)?;
+        write_tag(f, "Komi", &self.komi.to_string())?;
+        write_tag(f, "Rules", &self.rules)?;
+        write_tag(f, "Date", &self.date)?;
+        write_tag(f, "Result", &self.result)?;
+        if let Some(position) = &self.position {
+            write_tag(f, "Position", position)?;
+        }
+        for (key, value) in &self.tags {
+            write_tag(f, key, value)?;
+        }
+        writeln!(f)?;
+
+        for recorded in &self.moves {
+            match &recorded.comment {
+                Some(comment) => {
+                    let comment = comment.replace(['\r', '\n'], " ");
+                    writeln!(f, "{} ; {}", recorded.m.notation(), comment)?
+                }
+                None => writeln!(f, "{}", recorded.m.notation())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_tag(line: &str) -> Result<(String, String), Error> {
+    let inner = line
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .ok_or(Error::InvalidRecord)?;
+    let (key, value) = inner.split_once(' ').ok_or(Error::InvalidRecord)?;
+    let value = value
+        .trim()
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or(Error::InvalidRecord)?;
+
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            unescaped.push(chars.next().ok_or(Error::InvalidRecord)?);
+        } else {
+            unescaped.push(c);
+        }
+    }
+    Ok((key.to_string(), unescaped))
+}
+
+fn parse_move_line(line: &str) -> Result<RecordedMove, Error> {
+    let (m, comment) = match line.split_once(';') {
+        Some((m, comment)) => (m, Some(comment.trim().to_string())),
+        None => (line, None),
+    };
+    Ok(RecordedMove {
+        m: Move::parse(m.trim())?,
+        comment,
+    })
+}
+
+/// Reads game records one at a time from a buffered reader.
+pub struct RecordReader<R: BufRead> {
+    lines: Peekable<Lines<R>>,
+}
+
+impl<R: BufRead> RecordReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines().peekable(),
+        }
+    }
+
+    fn next_line(&mut self) -> Result<Option<String>, Error> {
+        match self.lines.next() {
+            Some(Ok(line)) => Ok(Some(line.trim().to_string())),
+            Some(Err(e)) => Err(Error::Io(e.kind())),
+            None => Ok(None),
+        }
+    }
+
+    fn peek_line(&mut self) -> Option<&str> {
+        match self.lines.peek() {
+            Some(Ok(line)) => Some(line.trim()),
+            _ => None,
+        }
+    }
+
+    fn read_record(&mut self) -> Result<Option<GameRecord>, Error> {
+        // Skip any blank lines between records
+        loop {
+            match self.lines.peek() {
+                None => return Ok(None),
+                Some(Err(_)) => return self.next_line().map(|_| None),
+                Some(Ok(line)) if line.trim().is_empty() => _ = self.lines.next(),
+                Some(Ok(_)) => break,
+            }
+        }
+
+        let mut record = GameRecord::new();
+        record.date = "?".to_string();
+
+        // Tags
+        while self.peek_line().is_some_and(|line| line.starts_with('[')) {
+            let line = self.next_line()?.unwrap();
+            let (key, value) = parse_tag(&line)?;
+            record.set_tag(&key, &value)?;
+        }
+
+        // A blank line separates the tags from the moves
+        while self.peek_line() == Some("") {
+            self.next_line()?;
+        }
+
+        // Moves, until a blank line, the next record or the end of input
+        while let Some(line) = self.peek_line() {
+            if line.is_empty() || line.starts_with('[') {
+                break;
+            }
+            let line = self.next_line()?.unwrap();
+            record.moves.push(parse_move_line(&line)?);
+        }
+
+        Ok(Some(record))
+    }
+}
+
+impl<R: BufRead> Iterator for RecordReader<R> {
+    type Item = Result<GameRecord, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+impl std::str::FromStr for GameRecord {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        RecordReader::new(s.as_bytes())
+            .next()
+            .unwrap_or(Err(Error::InvalidRecord))
+    }
+}
+
+// The current UTC date as YYYY-MM-DD
+pub fn today() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    // Convert days since the epoch to a civil date. See:
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = (secs / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
@@ -0,0 +1,256 @@
+use crate::core::{
+    bitboard::BitBoard,
+    error::Error,
+    game::{Board, Color},
+    square::Square,
+};
+
+use std::fmt;
+use std::str::FromStr;
+
+// Rule variants
+//
+// The published rules leave a number of questions open (see
+// `doc/QUESTIONS.md`). The answers we chose are the defaults, and each of them
+// can be changed here to experiment with variants.
+//
+// Rules are written as `standard`, or as a comma separated list of the
+// settings which differ from it, e.g., `capture=liberties,komi=1.5`.
+
+/// How pieces connect to each other to form a group.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Connection {
+    /// Only orthogonally adjacent pieces are connected.
+    Orthogonal,
+    /// Diagonally adjacent pieces are also connected.
+    Diagonal,
+}
+
+impl Connection {
+    // The group of pieces connected to `start`
+    #[inline]
+    pub fn group(self, occupied: BitBoard, start: Square) -> BitBoard {
+        match self {
+            Connection::Orthogonal => occupied.floodfill4(start),
+            Connection::Diagonal => occupied.floodfill8(start),
+        }
+    }
+
+    // The empty area containing `start`. Empty squares leak through any gap
+    // which doesn't connect two pieces, so this is the dual of `group`.
+    #[inline]
+    pub fn area(self, empty: BitBoard, start: Square) -> BitBoard {
+        match self {
+            Connection::Orthogonal => empty.floodfill8(start),
+            Connection::Diagonal => empty.floodfill4(start),
+        }
+    }
+}
+
+/// How opponent pieces are removed from the board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Capture {
+    /// Pieces inside newly surrounded territory are captured.
+    Territory,
+    /// As in go, a group is captured when it has no adjacent empty squares.
+    /// Surrounding territory claims only the empty squares.
+    Liberties,
+}
+
+/// What counts towards a player's score at the end of the game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scoring {
+    /// Squares covered by the player's pieces.
+    Occupied,
+    /// Squares covered by the player's pieces, plus their empty territory.
+    Area,
+}
+
+/// Komi in half points, so that it can be compared exactly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Komi(pub i16);
+
+impl Komi {
+    #[inline]
+    pub fn half_points(self) -> i32 {
+        self.0 as i32
+    }
+}
+
+impl Default for Komi {
+    fn default() -> Self {
+        Komi(1)
+    }
+}
+
+impl fmt::Display for Komi {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        match abs % 2 {
+            0 => write!(f, "{}{}", sign, abs / 2),
+            _ => write!(f, "{}{}.5", sign, abs / 2),
+        }
+    }
+}
+
+impl FromStr for Komi {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let halves = s.parse::<f32>().map_err(|_| Error::InvalidRules)? * 2.;
+        if halves.fract() != 0. || halves.abs() > i16::MAX as f32 {
+            return Err(Error::InvalidRules);
+        }
+        Ok(Komi(halves as i16))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rules {
+    pub connection: Connection,
+    pub capture: Capture,
+    /// A piece placed next to owned territory becomes part of it.
+    pub territory_extension: bool,
+    /// A player may pass instead of placing a piece.
+    pub allow_pass: bool,
+    /// Added to white's score.
+    pub komi: Komi,
+    /// The boss may be placed on the center of the board.
+    pub boss_on_center: bool,
+    pub scoring: Scoring,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+impl Rules {
+    pub fn standard() -> Rules {
+        Rules {
+            connection: Connection::Orthogonal,
+            capture: Capture::Territory,
+            territory_extension: true,
+            allow_pass: false,
+            komi: Komi::default(),
+            boss_on_center: false,
+            scoring: Scoring::Occupied,
+        }
+    }
+
+    /// The score for one player, not including komi.
+    pub fn score(&self, board: &Board, color: Color) -> u32 {
+        let (player, other) = match color {
+            Color::Black => (&board.black, &board.white),
+            Color::White => (&board.white, &board.black),
+        };
+        match self.scoring {
+            Scoring::Occupied => player.occupied.popcnt(),
+            Scoring::Area => (player.occupied | (player.owned & !other.occupied)).popcnt(),
+        }
+    }
+
+    /// Black's lead over white in half points, after komi.
+    pub fn margin(&self, board: &Board) -> i32 {
+        let b = self.score(board, Color::Black) as i32 * 2;
+        let w = self.score(board, Color::White) as i32 * 2;
+        b - w - self.komi.half_points()
+    }
+}
+
+fn yes_no(b: bool) -> &'static str {
+    if b {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+fn parse_yes_no(s: &str) -> Result<bool, Error> {
+    match s {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err(Error::InvalidRules),
+    }
+}
+
+impl fmt::Display for Rules {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let standard = Rules::standard();
+        let mut settings = Vec::new();
+        if self.connection != standard.connection {
+            settings.push(format!("connection={:?}", self.connection).to_lowercase());
+        }
+        if self.capture != standard.capture {
+            settings.push(format!("capture={:?}", self.capture).to_lowercase());
+        }
+        if self.territory_extension != standard.territory_extension {
+            settings.push(format!("extension={}", yes_no(self.territory_extension)));
+        }
+        if self.allow_pass != standard.allow_pass {
+            settings.push(format!("pass={}", yes_no(self.allow_pass)));
+        }
+        if self.komi != standard.komi {
+            settings.push(format!("komi={}", self.komi));
+        }
+        if self.boss_on_center != standard.boss_on_center {
+            settings.push(format!("center={}", yes_no(self.boss_on_center)));
+        }
+        if self.scoring != standard.scoring {
+            settings.push(format!("scoring={:?}", self.scoring).to_lowercase());
+        }
+
+        if settings.is_empty() {
+            write!(f, "standard")
+        } else {
+            write!(f, "{}", settings.join(","))
+        }
+    }
+}
+
+impl FromStr for Rules {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = Rules::standard();
+        if s.trim() == "standard" {
+            return Ok(rules);
+        }
+
+        for setting in s.split(',') {
+            let (key, value) = setting.split_once('=').ok_or(Error::InvalidRules)?;
+            let value = value.trim();
+            match key.trim() {
+                "connection" => {
+                    rules.connection = match value {
+                        "orthogonal" => Connection::Orthogonal,
+                        "diagonal" => Connection::Diagonal,
+                        _ => return Err(Error::InvalidRules),
+                    }
+                }
+                "capture" => {
+                    rules.capture = match value {
+                        "territory" => Capture::Territory,
+                        "liberties" => Capture::Liberties,
+                        _ => return Err(Error::InvalidRules),
+                    }
+                }
+                "extension" => rules.territory_extension = parse_yes_no(value)?,
+                "pass" => rules.allow_pass = parse_yes_no(value)?,
+                "komi" => rules.komi = value.parse()?,
+                "center" => rules.boss_on_center = parse_yes_no(value)?,
+                "scoring" => {
+                    rules.scoring = match value {
+                        "occupied" => Scoring::Occupied,
+                        "area" => Scoring::Area,
+                        _ => return Err(Error::InvalidRules),
+                    }
+                }
+                _ => return Err(Error::InvalidRules),
+            }
+        }
+        Ok(rules)
+    }
+}
@@ -0,0 +1,136 @@
+use crate::core::{
+    game::{Color, State},
+    rules::Komi,
+};
+
+use std::fmt;
+
+/// One player's share of the score.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlayerScore {
+    /// Squares covered by the player's pieces.
+    pub pieces: u32,
+    /// Empty squares in the player's territory.
+    pub territory: u32,
+    /// The score according to the rules, not including komi.
+    pub score: u32,
+}
+
+/// How the score was reached. Scores are counted the same way whether or not
+/// the game is over.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScoreBreakdown {
+    pub black: PlayerScore,
+    pub white: PlayerScore,
+    pub komi: Komi,
+    /// Black's lead over white in half points, after komi.
+    pub margin: i32,
+}
+
+impl ScoreBreakdown {
+    pub fn new(state: &State) -> ScoreBreakdown {
+        let board = &state.board;
+        let rules = &state.rules;
+        let empty = !board.occupied();
+        let player = |color: Color| {
+            let owned = match color {
+                Color::Black => board.black.owned,
+                Color::White => board.white.owned,
+            };
+            let pieces = match color {
+                Color::Black => board.black.occupied,
+                Color::White => board.white.occupied,
+            };
+            PlayerScore {
+                pieces: pieces.popcnt(),
+                territory: (owned & empty).popcnt(),
+                score: rules.score(board, color),
+            }
+        };
+
+        ScoreBreakdown {
+            black: player(Color::Black),
+            white: player(Color::White),
+            komi: rules.komi,
+            margin: rules.margin(board),
+        }
+    }
+
+    #[inline]
+    pub fn leader(&self) -> Option<Color> {
+        match self.margin {
+            m if m > 0 => Some(Color::Black),
+            m if m < 0 => Some(Color::White),
+            _ => None,
+        }
+    }
+}
+
+// The margin as used in game records, e.g., `B+2.5`, `W+0.5` or `0`
+fn write_margin(f: &mut fmt::Formatter, margin: i32) -> fmt::Result {
+    let points = Komi(margin.unsigned_abs() as i16);
+    match margin {
+        m if m > 0 => write!(f, "B+{}", points),
+        m if m < 0 => write!(f, "W+{}", points),
+        _ => write!(f, "0"),
+    }
+}
+
+impl fmt::Display for ScoreBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, player) in [("black", self.black), ("white", self.white)] {
+            write!(
+                f,
+                "{} {} ({} pieces, {} territory), ",
+                name, player.score, player.pieces, player.territory
+            )?;
+        }
+        write!(f, "komi {}: ", self.komi)?;
+        write_margin(f, self.margin)
+    }
+}
+
+/// The outcome of a finished game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GameResult {
+    /// `None` for a draw.
+    pub winner: Option<Color>,
+    pub score: ScoreBreakdown,
+}
+
+impl GameResult {
+    #[inline]
+    pub fn is_draw(&self) -> bool {
+        self.winner.is_none()
+    }
+}
+
+// Written as in game records
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.winner {
+            Some(_) => write_margin(f, self.score.margin),
+            None => write!(f, "Draw"),
+        }
+    }
+}
+
+impl State {
+    /// The current score, whether or not the game is over.
+    #[inline]
+    pub fn score(&self) -> ScoreBreakdown {
+        ScoreBreakdown::new(self)
+    }
+
+    /// The result of the game, or `None` if it isn't over yet.
+    pub fn result(&self) -> Option<GameResult> {
+        if self.has_moves() {
+            return None;
+        }
+        let score = self.score();
+        Some(GameResult {
+            winner: score.leader(),
+            score,
+        })
+    }
+}
@@ -0,0 +1,299 @@
+use crate::core::{
+    bitboard::BitBoard,
+    game::{Board, Color, PlayerState, State},
+    move_tab::LUTEntry,
+    orientation::{Orientation, ALL_ORIENTATIONS},
+    pieces::{PieceId, PieceList, PieceTypeId, ALL_PIECE_TYPE_IDS},
+    r#move::Move,
+    square::Square,
+};
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+// Symmetry
+//
+// The board is unchanged by the eight rotations and reflections of a square.
+// Two positions related by one of them are equivalent, as long as every piece
+// turns into a piece the player has. Rotations turn each piece into another
+// placement of itself. Reflections turn Koubaku 1 into Koubaku 2 and Kunoji 3
+// into Kunoji 4, and the other way around, but the Nobi has no mirror image,
+// and there are two Kunoji 1 but only one Kunoji 2. So a reflection only
+// applies once those pieces are out of the game, and in practice positions
+// are equivalent under rotation.
+//
+// Since `State::hash` depends on where pieces are, equivalent positions hash
+// differently. `State::canonical_hash` is the same for all of them.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Symmetry {
+    Identity,
+    Rot90,
+    Rot180,
+    Rot270,
+    FlipVertical,
+    FlipHorizontal,
+    FlipDiagonal,
+    FlipAntiDiagonal,
+}
+
+pub const ALL_SYMMETRIES: [Symmetry; 8] = [
+    Symmetry::Identity,
+    Symmetry::Rot90,
+    Symmetry::Rot180,
+    Symmetry::Rot270,
+    Symmetry::FlipVertical,
+    Symmetry::FlipHorizontal,
+    Symmetry::FlipDiagonal,
+    Symmetry::FlipAntiDiagonal,
+];
+
+// Marks table entries without a counterpart
+const NONE: u16 = u16::MAX;
+
+// The image of each LUT entry under each symmetry
+static ENTRIES: OnceLock<Vec<[u16; 8]>> = OnceLock::new();
+
+#[inline]
+fn step(square: Square, orientation: Orientation) -> Option<Square> {
+    match orientation {
+        Orientation::S => square.down(),
+        Orientation::W => square.left(),
+        Orientation::N => square.up(),
+        Orientation::E => square.right(),
+    }
+}
+
+fn piece_type(entry: usize) -> PieceTypeId {
+    *ALL_PIECE_TYPE_IDS
+        .iter()
+        .find(|p| (p.def().lut_offset..p.def().lut_offset + p.def().moves).contains(&entry))
+        .unwrap_or(&PieceTypeId::Boss)
+}
+
+fn build_entries() -> Vec<[u16; 8]> {
+    let count = LUTEntry::CENTER_BOSS.0 + 1;
+    let key = |e: LUTEntry| (e.mask(), e.gaze(), e.orientation() as u8);
+    let index = (0..count)
+        .map(LUTEntry)
+        .filter(|e| piece_type(e.0) != PieceTypeId::Boss)
+        .map(|e| (key(e), e.0))
+        .collect::<HashMap<_, _>>();
+
+    (0..count)
+        .map(|i| {
+            let entry = LUTEntry(i);
+            let piece = piece_type(i);
+            ALL_SYMMETRIES.map(|symmetry| {
+                let image = match piece {
+                    // Bosses don't face anywhere, and their entries are
+                    // looked up by their top left corner
+                    PieceTypeId::Boss => LUTEntry::lookup(
+                        PieceTypeId::Boss,
+                        symmetry.apply(entry.mask()).to_square(),
+                        Orientation::S,
+                    )
+                    .map(|e| e.0),
+                    _ => index
+                        .get(&(
+                            symmetry.apply(entry.mask()),
+                            symmetry.apply(entry.gaze()),
+                            symmetry.orientation(entry.orientation()) as u8,
+                        ))
+                        .copied(),
+                };
+                image.map_or(NONE, |image| image as u16)
+            })
+        })
+        .collect()
+}
+
+impl Symmetry {
+    pub fn apply(self, b: BitBoard) -> BitBoard {
+        match self {
+            Symmetry::Identity => b,
+            Symmetry::Rot90 => b.rot90(),
+            Symmetry::Rot180 => b.rot180(),
+            Symmetry::Rot270 => b.rot270(),
+            Symmetry::FlipVertical => b.flip_vertical(),
+            Symmetry::FlipHorizontal => b.flip_vertical().rot180(),
+            Symmetry::FlipDiagonal => b.flip_diag_a1h8(),
+            Symmetry::FlipAntiDiagonal => b.flip_diag_a1h8().rot180(),
+        }
+    }
+
+    pub fn inverse(self) -> Symmetry {
+        match self {
+            Symmetry::Rot90 => Symmetry::Rot270,
+            Symmetry::Rot270 => Symmetry::Rot90,
+            symmetry => symmetry,
+        }
+    }
+
+    #[inline]
+    pub fn is_reflection(self) -> bool {
+        !matches!(
+            self,
+            Symmetry::Identity | Symmetry::Rot90 | Symmetry::Rot180 | Symmetry::Rot270
+        )
+    }
+
+    #[inline]
+    pub fn square(self, square: Square) -> Square {
+        self.apply(BitBoard::from_square(square)).to_square()
+    }
+
+    /// The direction `orientation` faces after the transform.
+    pub fn orientation(self, orientation: Orientation) -> Orientation {
+        // Follow a step from a square in the middle of the board
+        let from = Square::new(27);
+        let to = step(from, orientation).unwrap();
+        ALL_ORIENTATIONS
+            .into_iter()
+            .find(|o| step(self.square(from), *o) == Some(self.square(to)))
+            .unwrap()
+    }
+
+    /// The piece which takes the place of `piece`, if there is one.
+    pub fn piece(self, piece: PieceId) -> Option<PieceId> {
+        if !self.is_reflection() {
+            return Some(piece);
+        }
+        match piece {
+            PieceId::Koubaku1 => Some(PieceId::Koubaku2),
+            PieceId::Koubaku2 => Some(PieceId::Koubaku1),
+            PieceId::Kunoji3 => Some(PieceId::Kunoji4),
+            PieceId::Kunoji4 => Some(PieceId::Kunoji3),
+            PieceId::Nobi | PieceId::Kunoji1a | PieceId::Kunoji1b | PieceId::Kunoji2 => None,
+            piece => Some(piece),
+        }
+    }
+
+    /// The placement `entry` turns into, if the piece has one.
+    pub fn entry(self, entry: LUTEntry) -> Option<LUTEntry> {
+        let image = ENTRIES.get_or_init(build_entries)[entry.0][self as usize];
+        (image != NONE).then_some(LUTEntry(image as usize))
+    }
+
+    /// The move `m` turns into, if the piece has a counterpart.
+    pub fn map_move(self, m: Move) -> Option<Move> {
+        if m.is_pass() {
+            return Some(m);
+        }
+        let piece = self.piece(m.get_piece())?;
+        Some(Move::new(piece, self.entry(m.get_lut_entry())?))
+    }
+
+    /// Whether every piece in `state` has a counterpart.
+    pub fn applies(self, state: &State) -> bool {
+        let players = [&state.board.black, &state.board.white];
+        players.iter().all(|player| {
+            player.hand.into_iter().all(|p| self.piece(p).is_some())
+                && (player.move_list.iter()).all(|m| self.piece(m.get_piece()).is_some())
+        })
+    }
+
+    fn player(self, player: &PlayerState) -> PlayerState {
+        let hand = player
+            .hand
+            .into_iter()
+            .fold(0, |bits, p| bits | 1 << self.piece(p).unwrap() as u16);
+        PlayerState {
+            hand: PieceList::from_bits(hand).unwrap(),
+            move_list: (player.move_list.iter())
+                .map(|m| self.map_move(*m).unwrap())
+                .collect(),
+            occupied: self.apply(player.occupied),
+            owned: self.apply(player.owned),
+        }
+    }
+}
+
+/// The canonical form of a position: the lowest hash among the positions
+/// equivalent to it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Canonical {
+    pub hash: u64,
+    /// The symmetries which take the position to the canonical one. There is
+    /// more than one when the position is symmetric itself.
+    pub symmetries: Vec<Symmetry>,
+}
+
+impl Canonical {
+    /// `m` as played in the canonical position. Moves which are equivalent
+    /// in this position give the same result.
+    pub fn to_canonical(&self, m: Move) -> Move {
+        self.symmetries
+            .iter()
+            .filter_map(|symmetry| symmetry.map_move(m))
+            .min_by_key(|m| m.get_raw_value())
+            .unwrap()
+    }
+
+    /// A move in this position equivalent to `m` in the canonical position.
+    pub fn from_canonical(&self, m: Move) -> Option<Move> {
+        self.symmetries[0].inverse().map_move(m)
+    }
+}
+
+impl State {
+    /// The position after transforming the board, or `None` if some piece has
+    /// no counterpart.
+    pub fn transform(&self, symmetry: Symmetry) -> Option<State> {
+        if !symmetry.applies(self) {
+            return None;
+        }
+        let mut board = Board {
+            black: symmetry.player(&self.board.black),
+            white: symmetry.player(&self.board.white),
+            boss: symmetry.apply(self.board.boss),
+            rays: self.board.rays.clone(),
+        };
+        board.redraw_rays();
+
+        let mut state = State {
+            board,
+            rules: self.rules.clone(),
+            ..*self
+        };
+        state.hash = state.rehash();
+        Some(state)
+    }
+
+    // The hash of the transformed position, without building it
+    fn transformed_hash(&self, symmetry: Symmetry) -> u64 {
+        let hash = |color, moves: &[Move]| {
+            moves.iter().fold(0, |h, m| {
+                let m = Move::new(m.get_piece(), symmetry.entry(m.get_lut_entry()).unwrap());
+                h ^ State::hash_for(color, m)
+            })
+        };
+        hash(Color::Black, &self.board.black.move_list)
+            ^ hash(Color::White, &self.board.white.move_list)
+            ^ State::hash_turn(self.current, self.passes)
+    }
+
+    /// The hash shared by all the positions equivalent to this one.
+    pub fn canonical_hash(&self) -> Canonical {
+        let hashes = ALL_SYMMETRIES
+            .into_iter()
+            .filter(|symmetry| symmetry.applies(self))
+            .map(|symmetry| (self.transformed_hash(symmetry), symmetry))
+            .collect::<Vec<_>>();
+        let hash = hashes.iter().map(|(hash, _)| *hash).min().unwrap();
+        Canonical {
+            hash,
+            symmetries: (hashes.into_iter())
+                .filter(|(h, _)| *h == hash)
+                .map(|(_, symmetry)| symmetry)
+                .collect(),
+        }
+    }
+
+    /// The canonical position equivalent to this one, and the symmetry which
+    /// takes this one to it.
+    pub fn canonical(&self) -> (State, Symmetry) {
+        let symmetry = self.canonical_hash().symmetries[0];
+        (self.transform(symmetry).unwrap(), symmetry)
+    }
+}
@@ -1,4 +1,10 @@
-pub const HASHES: [u64; 3448] = [
+// Toggled when white is to move
+pub const SIDE: u64 = 0x6a09e667f3bcc908;
+
+// Indexed by the number of consecutive passes
+pub const PASSES: [u64; 3] = [0, 0xbb67ae8584caa73b, 0x510e527fade682d1];
+
+pub const HASHES: [u64; 3450] = [
     0x120677b05667441e,
     0x2d5d1a4f1b3a23dd,
     0xee9f57cad7f6c60d,
@@ -3447,4 +3453,6 @@ pub const HASHES: [u64; 3448] = [
     0x9b32e84254d8f101,
     0x5e033635b86b28c6,
     0xba2c45ecd2cc9055,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
 ];
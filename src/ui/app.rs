@@ -1,13 +1,21 @@
 use comfy::{egui, is_key_pressed, EngineContext, EngineState, GameLoop};
 
 use crate::{
-    agent::Agent,
+    agent::{
+        book::{self, Book},
+        spec::{self, AgentSpec},
+        time::{Clock, TimeManager},
+        Agent, Player,
+    },
     core::{
         game::{self, Color::*},
+        move_tab::LUTEntry,
         orientation::Orientation,
         pieces::{PieceId, PieceList, ALL_PIECES_IDS},
         r#move::Move,
         ray::Rays,
+        record::GameRecord,
+        square::Square,
     },
     ui::{
         draw, piece,
@@ -15,6 +23,12 @@ use crate::{
     },
 };
 
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
 // TODO: make this a tree. For now a history stack.
 #[derive(Debug, Clone)]
 pub struct History {
@@ -58,6 +72,13 @@ pub struct Konego {
     pub worker: Worker,
 }
 
+// Closing the window shouldn't wait for a search to finish
+impl Drop for Konego {
+    fn drop(&mut self) {
+        self.worker.abort();
+    }
+}
+
 #[derive(Debug)]
 pub struct UserActivity {
     pub piece_list: PieceList,
@@ -107,12 +128,49 @@ impl UserActivity {
     }
 }
 
+// A player's chair at the board. Computer players keep their state between
+// moves, so each seat has its own instance.
+pub struct Seat {
+    agent: AgentSpec,
+    player: Option<Arc<Mutex<Box<dyn Player>>>>,
+    // Computer players play on the clock
+    clock: Option<Clock>,
+}
+
+impl Seat {
+    fn new(agent: AgentSpec, book: Option<&Arc<Book>>) -> Seat {
+        let player = agent.player_with_book(book);
+        Seat {
+            agent,
+            clock: player
+                .as_ref()
+                .map(|_| Clock::new(agent.agent.time_control())),
+            player: player.map(|player| Arc::new(Mutex::new(player))),
+        }
+    }
+
+    fn is_human(&self) -> bool {
+        self.player.is_none()
+    }
+}
+
+impl fmt::Debug for Seat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.agent)
+    }
+}
+
 #[derive(Debug)]
 pub struct UIState {
     show_spinner: bool,
-    agent_black: Agent,
-    agent_white: Agent,
+    agent_black: Seat,
+    agent_white: Seat,
     user: Option<UserActivity>,
+    reason: Option<String>,
+    time: TimeManager,
+    turn_start: Instant,
+    // The player who ran out of time
+    flagged: Option<game::Color>,
 }
 
 impl Default for UIState {
@@ -123,12 +181,33 @@ impl Default for UIState {
 
 impl UIState {
     fn new() -> Self {
+        // The book is optional; see `bin/book.rs` to build one
+        let book = match Book::load(book::DEFAULT_PATH) {
+            Ok(book) => {
+                log::info!("loaded {} book positions", book.len());
+                Some(Arc::new(book))
+            }
+            Err(e) => {
+                log::info!("no opening book: {:?}", e);
+                None
+            }
+        };
+        let book = book.as_ref();
+        // Either side can be changed in `nego.toml`, e.g. `white = "human"`
+        let defaults = [
+            Agent::Mcts(std::time::Duration::from_secs(10)).into(),
+            Agent::Mcts2(std::time::Duration::from_secs(40)).into(),
+        ];
+        let [black, white] = spec::load_players(spec::DEFAULT_PATH, defaults);
         Self {
             show_spinner: false,
-            agent_white: Agent::Mcts2(std::time::Duration::from_secs(40)),
-            // agent_white: Agent::Human,
-            agent_black: Agent::Mcts(std::time::Duration::from_secs(10)),
+            agent_white: Seat::new(white, book),
+            agent_black: Seat::new(black, book),
             user: None,
+            reason: None,
+            time: TimeManager::default(),
+            turn_start: Instant::now(),
+            flagged: None,
         }
     }
 }
@@ -169,25 +248,73 @@ impl Konego {
                 if self.ui.show_spinner {
                     ui.add(egui::Spinner::new());
                 }
+                let state = self.history.last();
+                match state.result() {
+                    Some(result) => ui.label(format!("Result: {}", result)),
+                    None => ui.label(format!("Score: {}", state.score())),
+                };
+                for color in [Black, White] {
+                    if let Some(clock) = &self.seat(color).clock {
+                        ui.label(format!("{:?}: {:.1?}", color, clock.available()));
+                    }
+                }
+                if let Some(color) = self.ui.flagged {
+                    ui.label(format!("{:?} lost on time", color));
+                }
+                if let Some(reason) = &self.ui.reason {
+                    ui.label(reason);
+                }
             });
     }
 
-    fn current_agent(&self) -> Agent {
-        match self.history.last().current {
-            Black => self.ui.agent_black,
-            White => self.ui.agent_white,
+    fn seat(&self, color: game::Color) -> &Seat {
+        match color {
+            Black => &self.ui.agent_black,
+            White => &self.ui.agent_white,
+        }
+    }
+
+    fn seat_mut(&mut self, color: game::Color) -> &mut Seat {
+        match color {
+            Black => &mut self.ui.agent_black,
+            White => &mut self.ui.agent_white,
+        }
+    }
+
+    fn current_agent(&self) -> &Seat {
+        self.seat(self.history.last().current)
+    }
+
+    fn record(&self) -> GameRecord {
+        let mut record = GameRecord::new();
+        record.black = format!("{:?}", self.ui.agent_black);
+        record.white = format!("{:?}", self.ui.agent_white);
+        record.set_rules(&self.history.last().rules);
+        self.history.moves.iter().for_each(|m| record.push(*m));
+        if let Some(result) = self.history.last().result() {
+            record.result = result.to_string();
         }
+        record
     }
 
     fn finalize_work(&mut self) {
-        self.history.push(self.worker.set_idle_and_fetch());
+        let elapsed = self.ui.turn_start.elapsed();
+        let (state, m) = self.worker.set_idle_and_fetch();
+        // Charge the player who just moved
+        let mover = state.current.next();
+        if let Some(clock) = &mut self.seat_mut(mover).clock {
+            if !clock.spend(elapsed) {
+                log::info!("{:?} lost on time", mover);
+                self.ui.flagged = Some(mover);
+            }
+        }
+        // The player to move now has just seen its opponent's move
+        if let (Some(m), Some(player)) = (m, &self.seat(state.current).player) {
+            player.lock().unwrap().opponent_moved(&state, m);
+        }
+        self.history.push((state, m));
         self.history.last().dump();
-        print!("history");
-        self.history
-            .moves
-            .iter()
-            .for_each(|m| print!(" {}", m.notation()));
-        println!();
+        println!("{}", self.record());
 
         if self.current_agent().is_human() {
             let piece_list = match self.history.current.current {
@@ -208,7 +335,15 @@ impl Konego {
             let state = self.worker.get_state();
             self.ui.show_spinner = state == WorkerState::Working;
             match state {
-                WorkerState::Idle => self.worker.spawn(self.history.last(), self.current_agent()),
+                WorkerState::Idle if self.ui.flagged.is_none() => {
+                    let seat = self.current_agent();
+                    if let (Some(player), Some(clock)) = (seat.player.clone(), seat.clock) {
+                        let budget = self.ui.time.budget(&clock, self.history.last());
+                        self.ui.turn_start = Instant::now();
+                        self.worker.spawn(self.history.last(), player, budget);
+                    }
+                }
+                WorkerState::Idle => (),
                 WorkerState::Working => (),
                 WorkerState::Ready => self.finalize_work(),
                 WorkerState::Done => (),
@@ -216,6 +351,20 @@ impl Konego {
         }
     }
 
+    // Describe why the current piece can't be placed at the hovered square
+    fn explain_placement(&mut self, x: usize, y: usize) {
+        self.ui.reason = self.ui.user.as_ref().and_then(|user| {
+            let state = self.history.last();
+            let entry = LUTEntry::lookup(
+                user.current_piece.piece_type_id(),
+                Square::from_indices(x, y),
+                user.current_orientation,
+            )?;
+            let m = Move::new(user.current_piece, entry);
+            state.check_move(m).err().map(|reason| reason.to_string())
+        });
+    }
+
     fn user_input(&mut self) {
         if !self.current_agent().is_human() {
             return;
@@ -236,6 +385,9 @@ impl Konego {
                 comfy::Color::rgba8(0x10, 0xff, 0x11, 0x88),
                 1,
             );
+            self.explain_placement(ix as usize, iy as usize);
+        } else {
+            self.ui.reason = None;
         }
 
         if is_key_pressed(comfy::KeyCode::A) {
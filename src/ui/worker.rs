@@ -1,4 +1,8 @@
-use crate::{agent::Agent, core::game, core::r#move::Move};
+use crate::{
+    agent::{self, stop::StopToken, time::Budget, Player},
+    core::game,
+    core::r#move::Move,
+};
 
 use std::sync::{Arc, Mutex};
 
@@ -16,6 +20,8 @@ struct ThreadData {
     new_state: game::State,
     last_move: Option<Move>,
     worker_state: WorkerState,
+    // Cancels the search running now
+    stop: StopToken,
 }
 
 impl ThreadData {
@@ -24,6 +30,7 @@ impl ThreadData {
             new_state: game::State::new(),
             worker_state: WorkerState::Idle,
             last_move: None,
+            stop: StopToken::new(),
         }
     }
 }
@@ -52,35 +59,59 @@ impl Worker {
         (lock.new_state.clone(), lock.last_move)
     }
 
-    pub fn spawn(&mut self, state: &game::State, agent: Agent) {
-        self.set_working();
+    pub fn spawn(
+        &mut self,
+        state: &game::State,
+        player: Arc<Mutex<Box<dyn Player>>>,
+        budget: Budget,
+    ) {
+        let stop = self.set_working();
         let mut game_state = state.clone();
         let worker = self.clone();
 
         _ = std::thread::spawn(move || {
-            if let Some(m) = agent.step(&mut game_state) {
-                worker.set_ready(game_state, m);
+            let mut player = player.lock().unwrap();
+            player.set_stop(stop.clone());
+            if let Some(m) = agent::step_within(player.as_mut(), &mut game_state, budget) {
+                worker.set_ready(&stop, game_state, m);
             } else {
-                println!("Score:");
-                println!("- black: {}", game_state.board.black.occupied.popcnt());
-                println!("- white: {}", game_state.board.white.occupied.popcnt());
-                worker.set_done();
+                log::info!("score: {}", game_state.score());
+                worker.set_done(&stop);
             }
         });
     }
 
-    fn set_ready(&self, state: game::State, m: Move) {
+    /// Cancel the search, if there is one, and go back to idle. The search
+    /// returns as soon as it sees the stop (see `agent/stop.rs`) and its move
+    /// is dropped; until then a new search waits for the player.
+    pub fn abort(&self) {
         let mut lock = self.0.lock().unwrap();
+        lock.stop.stop();
+        lock.worker_state = WorkerState::Idle;
+    }
+
+    // Results from a search which was aborted are dropped
+    fn set_ready(&self, stop: &StopToken, state: game::State, m: Move) {
+        let mut lock = self.0.lock().unwrap();
+        if stop.is_stopped() {
+            return;
+        }
         lock.worker_state = WorkerState::Ready;
         lock.new_state = state;
         lock.last_move = Some(m);
     }
 
-    fn set_working(&self) {
-        self.0.lock().unwrap().worker_state = WorkerState::Working;
+    fn set_working(&self) -> StopToken {
+        let mut lock = self.0.lock().unwrap();
+        lock.worker_state = WorkerState::Working;
+        lock.stop = StopToken::new();
+        lock.stop.clone()
     }
 
-    fn set_done(&self) {
-        self.0.lock().unwrap().worker_state = WorkerState::Done;
+    fn set_done(&self, stop: &StopToken) {
+        let mut lock = self.0.lock().unwrap();
+        if !stop.is_stopped() {
+            lock.worker_state = WorkerState::Done;
+        }
     }
 }
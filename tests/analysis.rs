@@ -0,0 +1,183 @@
+mod common;
+
+use nego::{
+    agent::{
+        analysis::{self, Analysis, AnalysisOptions, Analyze, Score},
+        eval::{self, Weights},
+        mcts2::Mcts2,
+        negamax::Iterative,
+        random_move,
+        solver::{Proof, Solver},
+        Agent, Player,
+    },
+    core::{game::State, r#move::Move},
+};
+
+use common::{init, ENDGAME, MIDGAME};
+
+use rand::{rngs::SmallRng, SeedableRng};
+
+use std::time::Duration;
+
+fn options(lines: usize, time: u64) -> AnalysisOptions {
+    AnalysisOptions {
+        lines,
+        time: Duration::from_millis(time),
+        interval: Duration::from_millis(50),
+    }
+}
+
+// Analyse, keeping the reports
+fn run(
+    analyzer: &mut dyn Analyze,
+    state: &State,
+    options: &AnalysisOptions,
+) -> (Analysis, Vec<Analysis>) {
+    let mut reports = Vec::new();
+    let analysis = analyzer.analyze(state, options, &mut |analysis| {
+        reports.push(analysis.clone())
+    });
+    (analysis, reports)
+}
+
+// Every line is a legal sequence of moves starting with its move, and no
+// move is shown twice
+fn check_lines(state: &State, analysis: &Analysis) {
+    let mut moves = Vec::new();
+    for (i, line) in analysis.lines.iter().enumerate() {
+        assert_eq!(line.pv[0], line.m);
+        assert!(analysis.lines[..i].iter().all(|other| other.m != line.m));
+        let mut state = state.clone();
+        for m in &line.pv {
+            moves.clear();
+            state.get_moves(&mut moves);
+            assert!(moves.contains(m), "{:?} isn't legal", m);
+            state.apply(*m);
+        }
+    }
+}
+
+#[test]
+fn analysis_mcts2() {
+    init();
+    let state = State::from_notation(MIDGAME).unwrap();
+    let mut player = Mcts2::new();
+    let (analysis, reports) = run(&mut player, &state, &options(3, 300));
+
+    assert!(!reports.is_empty());
+    assert_eq!(analysis.lines.len(), 3);
+    check_lines(&state, &analysis);
+    let visits = analysis
+        .lines
+        .iter()
+        .map(|line| line.visits.unwrap())
+        .collect::<Vec<_>>();
+    assert!(visits.windows(2).all(|pair| pair[0] >= pair[1]));
+    assert!(analysis
+        .lines
+        .iter()
+        .all(|line| matches!(line.score, Score::WinRate(rate) if (0. ..=1.).contains(&rate))));
+    assert!(analysis.depth >= 1);
+    assert!(analysis.nodes >= reports.last().unwrap().nodes);
+    assert!(analysis.nps() > 0.);
+
+    // Choosing a move carries on from the analysis
+    let m = player.choose_move(&state, Duration::from_millis(10));
+    assert!(m.is_some());
+    assert!(player.stats().reused > 0);
+}
+
+#[test]
+fn analysis_solver_proves() {
+    init();
+    let state = State::from_notation(ENDGAME).unwrap();
+    let (analysis, _) = run(&mut Solver::default(), &state, &options(2, 10_000));
+
+    // The search stops once the root is proven
+    assert!(analysis.elapsed < Duration::from_secs(10));
+    check_lines(&state, &analysis);
+    assert!(matches!(analysis.lines[0].score, Score::Proven(_)));
+    if analysis.lines.len() > 1 && analysis.lines[1].score == Score::Proven(Proof::Win) {
+        assert_eq!(analysis.lines[0].score, Score::Proven(Proof::Win));
+    }
+}
+
+// A one ply search scores each move by the evaluation after it
+#[test]
+fn analysis_negamax_one_ply() {
+    init();
+    let state = State::from_notation(MIDGAME).unwrap();
+    let (analysis, reports) = run(&mut Iterative::new(), &state, &options(4, 0));
+    assert!(reports.is_empty());
+    assert_eq!(analysis.depth, 1);
+    assert_eq!(analysis.lines.len(), 4);
+    check_lines(&state, &analysis);
+
+    let mut moves = Vec::new();
+    state.get_moves(&mut moves);
+    let mut values = moves
+        .iter()
+        .map(|m| {
+            let mut after = state.clone();
+            after.apply(*m);
+            -eval::evaluate(&after, &Weights::default())
+        })
+        .collect::<Vec<_>>();
+    values.sort_by(|a, b| b.cmp(a));
+    let scores = analysis
+        .lines
+        .iter()
+        .map(|line| line.score)
+        .collect::<Vec<_>>();
+    let expected = values[..4]
+        .iter()
+        .map(|value| Score::Points(*value))
+        .collect::<Vec<_>>();
+    assert_eq!(scores, expected);
+}
+
+#[test]
+fn analysis_negamax_deepens() {
+    init();
+    let state = State::from_notation(ENDGAME).unwrap();
+    let (analysis, reports) = run(&mut Iterative::new(), &state, &options(3, 500));
+    assert!(analysis.depth >= 2);
+    assert!(!reports.is_empty());
+    assert!(reports
+        .windows(2)
+        .all(|pair| pair[0].depth <= pair[1].depth));
+    check_lines(&state, &analysis);
+    let scores = analysis
+        .lines
+        .iter()
+        .map(|line| match line.score {
+            Score::Points(points) => points,
+            score => panic!("{:?}", score),
+        })
+        .collect::<Vec<_>>();
+    assert!(scores.windows(2).all(|pair| pair[0] >= pair[1]));
+}
+
+#[test]
+fn analysis_streams() {
+    init();
+    let state = State::from_notation(MIDGAME).unwrap();
+    let analyzer = Agent::Solver(Duration::ZERO).analyzer().unwrap();
+    let analyses = analysis::spawn(analyzer, &state, options(2, 300))
+        .iter()
+        .collect::<Vec<_>>();
+    assert!(analyses.len() > 1);
+    assert!(analyses
+        .windows(2)
+        .all(|pair| pair[0].elapsed <= pair[1].elapsed));
+    assert_eq!(analyses.last().unwrap().lines.len(), 2);
+
+    // No moves, nothing to show
+    let mut rng = SmallRng::seed_from_u64(1);
+    let mut over = State::new();
+    while let Some(m) = random_move(&over, &mut rng) {
+        over.apply(m);
+    }
+    let analysis = Iterative::new().analyze(&over, &options(2, 100), &mut |_| ());
+    assert_eq!(analysis.best(), None::<Move>);
+}
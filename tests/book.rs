@@ -0,0 +1,206 @@
+mod common;
+
+use nego::{
+    agent::{
+        book::{Book, BookMove, WithBook},
+        Player, Random,
+    },
+    core::{
+        error::Error,
+        game::{Color, State},
+        r#move::Move,
+        symmetry::Symmetry,
+    },
+};
+
+use common::init;
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use std::sync::Arc;
+use std::time::Duration;
+
+fn moves(notation: &[&str]) -> Vec<Move> {
+    notation.iter().map(|m| Move::parse(m).unwrap()).collect()
+}
+
+fn after(notation: &[&str]) -> State {
+    let mut state = State::new();
+    moves(notation).into_iter().for_each(|m| state.apply(m));
+    state
+}
+
+fn first_move(state: &State) -> Move {
+    let mut moves = Vec::new();
+    state.get_moves(&mut moves);
+    moves[0]
+}
+
+fn notation(entries: &[BookMove]) -> Vec<String> {
+    entries.iter().map(|entry| entry.m.notation()).collect()
+}
+
+#[test]
+fn book_lookup() {
+    init();
+    let mut book = Book::default();
+    let state = after(&["BOS:C3S", "BOS:E5S"]);
+    let m = first_move(&state);
+    let mut game = moves(&["BOS:C3S", "BOS:E5S"]);
+    game.push(m);
+    book.add_game(&State::new(), &game, Some(Color::Black), 3);
+    book.add_game(&State::new(), &game, None, 2);
+    assert_eq!(book.len(), 3);
+
+    let entries = book.lookup(&state).unwrap();
+    assert_eq!(notation(&entries), [m.notation()]);
+    assert_eq!((entries[0].games, entries[0].wins), (1, 1));
+
+    // White lost the first game and drew the second
+    let entries = book.lookup(&after(&["BOS:C3S"])).unwrap();
+    assert_eq!(notation(&entries), ["BOS:E5S"]);
+    assert_eq!(
+        (entries[0].games, entries[0].wins, entries[0].draws),
+        (2, 0, 1)
+    );
+    assert_eq!(entries[0].score(), 0.25);
+
+    assert_eq!(book.lookup(&after(&["BOS:C3S", "BOS:F6S"])), None);
+}
+
+// Boss placements in the four corners are the same opening
+#[test]
+fn book_symmetric_openings() {
+    init();
+    let mut book = Book::default();
+    let state = State::new();
+    for m in moves(&["BOS:A1S", "BOS:G1S", "BOS:A7S", "BOS:G7S"]) {
+        book.add(&state, m, Some(Color::Black));
+    }
+    book.add(&state, Move::parse("BOS:D3S").unwrap(), None);
+
+    let entries = book.lookup(&state).unwrap();
+    assert_eq!(book.len(), 1);
+    assert_eq!(entries.len(), 2);
+    assert!(entries
+        .iter()
+        .any(|entry| entry.games == 4 && entry.wins == 4));
+}
+
+#[test]
+fn book_symmetric_replies() {
+    init();
+    let mut book = Book::default();
+    let game = moves(&["BOS:A1S", "BOS:C3S"]);
+    book.add_game(&State::new(), &game, Some(Color::White), 2);
+
+    // The same position turned half way round
+    let entries = book.lookup(&after(&["BOS:G7S"])).unwrap();
+    assert_eq!(notation(&entries), ["BOS:E5S"]);
+    assert_eq!(entries[0].wins, 1);
+
+    // Later moves turn with the board
+    let m = first_move(&after(&["BOS:A1S", "BOS:C3S"]));
+    let mut game = moves(&["BOS:A1S", "BOS:C3S"]);
+    game.push(m);
+    book.add_game(&State::new(), &game, None, 3);
+    let entries = book.lookup(&after(&["BOS:G7S", "BOS:E5S"])).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(Some(entries[0].m), Symmetry::Rot180.map_move(m));
+}
+
+#[test]
+fn book_reweight() {
+    init();
+    let mut book = Book::default();
+    let state = State::new();
+    let (a, b, c) = (
+        Move::parse("BOS:A1S").unwrap(),
+        Move::parse("BOS:C3S").unwrap(),
+        Move::parse("BOS:B2S").unwrap(),
+    );
+    [Some(Color::Black), Some(Color::Black), None]
+        .into_iter()
+        .for_each(|winner| book.add(&state, a, winner));
+    [Some(Color::White), Some(Color::White)]
+        .into_iter()
+        .for_each(|winner| book.add(&state, b, winner));
+    book.add(&state, c, Some(Color::Black));
+
+    // Unweighted moves are never chosen
+    let mut rng = StdRng::seed_from_u64(0);
+    assert_eq!(book.choose(&state, &mut rng), None);
+
+    book.reweight(2);
+    let entries = book.lookup(&state).unwrap();
+    let weight = |m| entries.iter().find(|entry| entry.m == m).unwrap().weight;
+    assert_eq!(weight(a), 833);
+    assert_eq!(weight(b), 0);
+    assert_eq!(weight(c), 0);
+    for _ in 0..10 {
+        assert_eq!(book.choose(&state, &mut rng), Some(a));
+    }
+}
+
+#[test]
+fn book_read_write() {
+    init();
+    let mut book = Book::new("komi=1.5".parse().unwrap());
+    let mut state = State::new();
+    state.rules = book.rules.clone();
+    let mut game = Vec::new();
+    for _ in 0..4 {
+        let m = first_move(&state);
+        state.apply(m);
+        game.push(m);
+    }
+    book.add_game(
+        &State {
+            rules: book.rules.clone(),
+            ..State::new()
+        },
+        &game,
+        Some(Color::White),
+        4,
+    );
+    book.reweight(1);
+
+    let mut bytes = Vec::new();
+    book.write(&mut bytes).unwrap();
+    assert_eq!(Book::read(&mut bytes.as_slice()).unwrap(), book);
+
+    // The book only applies to the rules it was built with
+    let state = State {
+        rules: book.rules.clone(),
+        ..State::new()
+    };
+    assert!(book.lookup(&state).is_some());
+    assert_eq!(book.lookup(&State::new()), None);
+
+    let truncated = &bytes[..bytes.len() - 1];
+    assert_eq!(Book::read(&mut &truncated[..]), Err(Error::InvalidBook));
+    bytes[0] = b'X';
+    assert_eq!(Book::read(&mut bytes.as_slice()), Err(Error::InvalidBook));
+}
+
+#[test]
+fn book_player() {
+    init();
+    let mut book = Book::default();
+    let game = moves(&["BOS:C3S"]);
+    book.add_game(&State::new(), &game, Some(Color::Black), 1);
+    book.reweight(1);
+
+    let mut player = WithBook::new(Box::new(Random::new()), Arc::new(book));
+    assert_eq!(player.name(), "Random (book)");
+    let state = State::new();
+    let m = player.choose_move(&state, Duration::ZERO).unwrap();
+    // Any of the equivalent placements
+    assert!(moves(&["BOS:C3S", "BOS:E3S", "BOS:C5S", "BOS:E5S"]).contains(&m));
+
+    // Out of the book, the other player moves
+    let mut state = state;
+    state.apply(m);
+    assert_eq!(player.book.lookup(&state), None);
+    assert!(player.choose_move(&state, Duration::ZERO).is_some());
+}
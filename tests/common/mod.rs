@@ -0,0 +1,24 @@
+// Shared by the integration tests. Each test crate uses only some of it.
+#![allow(dead_code)]
+
+use nego::core::ray::Rays;
+
+use std::sync::Once;
+
+static INIT: Once = Once::new();
+
+// Move generation, mobility and boss rays depend on the rays being drawn
+pub fn init() {
+    INIT.call_once(Rays::build_lut);
+}
+
+// Reached by a fixed sequence of moves, with captures and territory available
+pub const MIDGAME: &str = "BOS:G2S,KB3a:E3W,KJ2:E4E,KB3b:H6S,MAM:D2S,KB1:E6S,KJ1a:C6S/\
+                           BOS:B7S,KJ1a:C4W,MAM:B1E,KJ3:G4E,KB3a:A3N,KJ2:E1S \
+                           0/0 w";
+
+// A few moves later, with games ending at depth 2
+pub const ENDGAME: &str =
+    "BOS:G2S,KB3a:E3W,KJ2:E4E,KB3b:H6S,MAM:D2S,KB1:E6S,KJ1a:C6S,KJ4:G7E,KJ3:E7N/\
+                           BOS:B7S,KJ1a:C4W,MAM:B1E,KB3a:A3N,KJ2:E1S,NOB:C1W,KJ4:A1E \
+                           f0f8fcf0f0f0c000/0 w";
@@ -0,0 +1,131 @@
+mod common;
+
+use nego::{
+    agent::{
+        alphabeta::{AlphaBeta, Leaves, TO_THE_END},
+        endgame::{pieces_in_hand, solve, WithEndgame},
+        time::Budget,
+        Player, Random,
+    },
+    core::game::{Color, State},
+};
+
+use common::{init, ENDGAME};
+
+use std::time::Duration;
+
+// The final margin for `color`
+fn margin(state: &State, color: Color) -> i32 {
+    let margin = state.rules.margin(&state.board);
+    match color {
+        Color::Black => margin,
+        Color::White => -margin,
+    }
+}
+
+// The exact margin for the player to move, by searching every line
+fn brute_force(state: &mut State) -> i32 {
+    let mut moves = Vec::new();
+    state.get_moves(&mut moves);
+    if moves.is_empty() {
+        return margin(state, state.current);
+    }
+
+    let mut best = i32::MIN;
+    for m in moves {
+        let undo = state.make(m);
+        best = best.max(-brute_force(state));
+        state.undo(undo);
+    }
+    best
+}
+
+#[test]
+fn endgame_solves_margin() {
+    init();
+    let mut state = State::from_notation(ENDGAME).unwrap();
+    let solution = solve(&state, Duration::from_secs(60)).unwrap();
+    assert_eq!(solution.margin, brute_force(&mut state));
+    assert!(solution.nodes > 0);
+}
+
+#[test]
+fn endgame_line_reaches_margin() {
+    init();
+    let state = State::from_notation(ENDGAME).unwrap();
+    let solution = solve(&state, Duration::from_secs(60)).unwrap();
+
+    let mut end = state.clone();
+    for m in &solution.line {
+        let mut moves = Vec::new();
+        end.get_moves(&mut moves);
+        assert!(moves.contains(m), "{}", m.notation());
+        end.apply(*m);
+    }
+    assert!(end.result().is_some());
+    assert_eq!(margin(&end, state.current), solution.margin);
+}
+
+#[test]
+fn endgame_children() {
+    init();
+    let mut state = State::from_notation(ENDGAME).unwrap();
+    let mut moves = Vec::new();
+    state.get_moves(&mut moves);
+
+    for m in moves {
+        let undo = state.make(m);
+        let solution = solve(&state, Duration::from_secs(60)).unwrap();
+        assert_eq!(solution.margin, brute_force(&mut state), "{}", m.notation());
+        state.undo(undo);
+    }
+}
+
+#[test]
+fn endgame_out_of_time() {
+    init();
+    let state = State::new();
+    assert_eq!(pieces_in_hand(&state), 24);
+    assert_eq!(solve(&state, Duration::ZERO), None);
+}
+
+// A fixed number of iterations has no time limit, so the solver is bounded by
+// nodes instead
+#[test]
+fn endgame_fixed_iterations() {
+    init();
+    let state = State::from_notation(ENDGAME).unwrap();
+    let solution = solve(&state, Duration::from_secs(60)).unwrap();
+
+    let mut player = WithEndgame::new(Box::new(Random::new()));
+    let m = player.choose_move_within(&state, Budget::iterations(1));
+    assert_eq!(m, solution.line.first().copied());
+}
+
+// Searches to the end of the game without giving up
+struct ToTheEnd;
+
+impl Leaves for ToTheEnd {
+    fn terminal(&self, state: &State) -> i32 {
+        margin(state, state.current)
+    }
+
+    fn evaluate(&self, state: &State) -> i32 {
+        margin(state, state.current)
+    }
+
+    fn out_of_time(&mut self, _nodes: u64) -> bool {
+        false
+    }
+}
+
+// With so few entries most are replaced, which doesn't change the result
+#[test]
+fn endgame_small_table() {
+    init();
+    let mut state = State::from_notation(ENDGAME).unwrap();
+    let solution = solve(&state, Duration::from_secs(60)).unwrap();
+    let mut search = AlphaBeta::with_table_entries(ToTheEnd, 16);
+    let margin = search.search(&mut state, TO_THE_END, i32::MIN + 1, i32::MAX);
+    assert_eq!(margin, Some(solution.margin));
+}
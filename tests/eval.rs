@@ -0,0 +1,75 @@
+mod common;
+
+use nego::{
+    agent::eval::{evaluate, mobility, Features, Weights},
+    core::game::{Color, State},
+};
+
+use common::{init, ENDGAME, MIDGAME};
+
+#[test]
+fn eval_start() {
+    init();
+    let mut state = State::new();
+    assert_eq!(
+        Features::new(&state, Color::Black),
+        Features::new(&state, Color::White)
+    );
+    assert_eq!(mobility(&state, Color::Black), 48);
+
+    // Only komi separates the players
+    assert_eq!(evaluate(&state, &Weights::default()), -50);
+    state.current = Color::White;
+    assert_eq!(evaluate(&state, &Weights::default()), 50);
+}
+
+#[test]
+fn eval_score_only() {
+    init();
+    for notation in [MIDGAME, ENDGAME] {
+        let mut state = State::from_notation(notation).unwrap();
+        let margin = state.score().margin * 50;
+
+        // Both players must be counted, whoever is to move
+        assert_eq!(evaluate(&state, &Weights::score_only()), -margin);
+        state.current = Color::Black;
+        assert_eq!(evaluate(&state, &Weights::score_only()), margin);
+    }
+}
+
+#[test]
+fn eval_features() {
+    init();
+    let state = State::from_notation(ENDGAME).unwrap();
+    let black = Features::new(&state, Color::Black);
+    let white = Features::new(&state, Color::White);
+
+    // Black has surrounded most of the right side of the board
+    assert!(black.territory > 0);
+    assert_eq!(white.territory, 0);
+    assert!(black.secure > white.secure);
+
+    // Bosses are always secure
+    assert!(white.secure >= 4);
+    assert_eq!(
+        black.at_risk + black.secure,
+        state.board.black.occupied.popcnt()
+    );
+
+    assert_eq!(white.mobility, 25);
+    assert!(black.hand < white.hand);
+    assert!(black.boss_rays > 0 && white.boss_rays > 0);
+}
+
+#[test]
+fn eval_weights() {
+    init();
+    let state = State::from_notation(MIDGAME).unwrap();
+    let weights = Weights {
+        mobility: 0,
+        ..Weights::default()
+    };
+    let black = weights.apply(&Features::without_mobility(&state, Color::Black));
+    let white = weights.apply(&Features::without_mobility(&state, Color::White));
+    assert_eq!(evaluate(&state, &weights), white - black + 50);
+}
@@ -1,6 +1,11 @@
 use nego::core::{
-    game::State, move_tab::LUTEntry, orientation::Orientation, pieces::PieceId, r#move::Move,
+    game::{Color, State},
+    move_tab::LUTEntry,
+    orientation::Orientation,
+    pieces::{PieceId, ALL_PIECES_IDS},
+    r#move::Move,
     square::Square,
+    zobrist,
 };
 
 fn mk_move(piece: PieceId, pos: (u8, u8), orientation: Orientation) -> Move {
@@ -73,3 +78,99 @@ fn snapshot() {
         insta::assert_debug_snapshot!(history);
     }
 }
+
+#[test]
+fn undo_restores_state() {
+    use rand::{seq::SliceRandom, SeedableRng};
+    let mut rng = rand::rngs::StdRng::seed_from_u64(456);
+
+    for _ in 0..50 {
+        let mut state = State::new();
+        let mut moves = Vec::new();
+        let mut stack = Vec::new();
+        loop {
+            moves.truncate(0);
+            state.get_moves(&mut moves);
+            if moves.is_empty() {
+                break;
+            }
+            let m = *moves.choose(&mut rng).unwrap();
+            let before = state.clone();
+            let undo = state.make(m);
+
+            // make must agree with apply
+            let mut applied = before.clone();
+            applied.apply(m);
+            assert_eq!(state, applied);
+
+            stack.push((before, undo));
+        }
+
+        while let Some((before, undo)) = stack.pop() {
+            state.undo(undo);
+            assert_eq!(state, before);
+        }
+        assert_eq!(state, State::new());
+    }
+}
+
+// Plays random games from `seed`, calling `check` after every move. Returns
+// the number of moves which captured something.
+fn random_games(seed: u64, mut check: impl FnMut(&State)) -> usize {
+    use rand::{seq::SliceRandom, SeedableRng};
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+
+    let mut captures = 0;
+    for _ in 0..50 {
+        let mut state = State::new();
+        let mut moves = Vec::new();
+        loop {
+            moves.truncate(0);
+            state.get_moves(&mut moves);
+            if moves.is_empty() {
+                break;
+            }
+            let pieces = state.board.black.move_list.len() + state.board.white.move_list.len();
+            state.apply(*moves.choose(&mut rng).unwrap());
+            if state.board.black.move_list.len() + state.board.white.move_list.len() <= pieces {
+                captures += 1;
+            }
+            check(&state);
+        }
+    }
+    captures
+}
+
+// A captured piece goes back to its owner's hand, so each piece is either
+// on the board or in hand
+#[test]
+fn capture_returns_piece_to_hand() {
+    let captures = random_games(789, |state| {
+        for player in [&state.board.black, &state.board.white] {
+            for piece in ALL_PIECES_IDS {
+                let placed = player.move_list.iter().any(|m| m.get_piece() == piece);
+                assert_ne!(placed, player.hand.holding(piece), "{:?}", piece);
+            }
+        }
+    });
+    assert!(captures > 0);
+}
+
+// After a capture the hash is computed again with each player's own colour,
+// so it only depends on the pieces on the board and whose turn it is
+#[test]
+fn capture_rehash() {
+    let hash = |color: Color, moves: &[Move]| {
+        moves.iter().fold(0, |h, m| {
+            h ^ zobrist::HASHES[(m.get_lut_entry().0 << 1) | (color as usize & 1)]
+        })
+    };
+    let mut turns = [None; 2];
+    let captures = random_games(789, |state| {
+        let pieces = hash(Color::Black, &state.board.black.move_list)
+            ^ hash(Color::White, &state.board.white.move_list);
+        let turn = turns[state.current as usize].get_or_insert(state.hash ^ pieces);
+        assert_eq!(state.hash ^ pieces, *turn);
+    });
+    assert!(captures > 0);
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use nego::{
+    agent::{
+        mcts::{tree_rollouts, Mcts},
+        time::Budget,
+        tree::DEFAULT_MEMORY,
+        Player,
+    },
+    core::game::State,
+};
+
+use common::{init, MIDGAME};
+
+use std::time::Duration;
+
+#[test]
+fn mcts_tree_rollouts() {
+    assert!(tree_rollouts(DEFAULT_MEMORY) > 90_000);
+    assert!(tree_rollouts(1 << 20) < tree_rollouts(2 << 20));
+    assert_eq!(tree_rollouts(0), 1);
+}
+
+// A budget which only holds a few expansions splits the search between trees
+#[test]
+fn mcts_memory_budget() {
+    init();
+    let state = State::from_notation(MIDGAME).unwrap();
+    let mut moves = Vec::new();
+    state.get_moves(&mut moves);
+    let mut player = Mcts::default();
+    player.memory = 1 << 20;
+    let most = tree_rollouts(player.memory);
+
+    let m = player.choose_move_within(&state, Budget::iterations(300));
+    assert!(moves.contains(&m.unwrap()));
+    let stats = player.stats();
+    assert_eq!(stats.rollouts, 300);
+    assert!(stats.tree_rollouts <= most);
+    assert!(stats.trees >= 300 / most);
+
+    let m = player.choose_move(&state, Duration::from_millis(300));
+    assert!(moves.contains(&m.unwrap()));
+    let stats = player.stats();
+    assert!(stats.tree_rollouts <= most);
+    assert!(stats.trees > 1);
+}
@@ -0,0 +1,181 @@
+mod common;
+
+use nego::{
+    agent::{mcts2::Mcts2, playout::Playout, solver::Proof, Player, Random},
+    core::{game::State, r#move::Move},
+};
+
+use common::{init, ENDGAME, MIDGAME};
+
+use std::time::{Duration, Instant};
+
+// Black wins with 3 of their 8 replies to this move
+fn won_endgame() -> State {
+    let mut state = State::from_notation(ENDGAME).unwrap();
+    let mut moves = Vec::new();
+    state.get_moves(&mut moves);
+    let m = moves
+        .into_iter()
+        .find(|m| m.notation() == "KJ3:A5E")
+        .unwrap();
+    state.apply(m);
+    state
+}
+
+// The exact outcome for the player to move, by searching every line
+fn brute_force(state: &mut State) -> Proof {
+    let mut moves = Vec::new();
+    state.get_moves(&mut moves);
+    if moves.is_empty() {
+        let result = state.result().unwrap();
+        return match result.winner {
+            None => Proof::Draw,
+            Some(winner) if winner == state.current => Proof::Win,
+            Some(_) => Proof::Loss,
+        };
+    }
+
+    let mut best = Proof::Loss;
+    for m in moves {
+        let undo = state.make(m);
+        let reply = brute_force(state);
+        state.undo(undo);
+        match reply {
+            Proof::Loss => return Proof::Win,
+            Proof::Draw => best = Proof::Draw,
+            _ => (),
+        }
+    }
+    best
+}
+
+#[test]
+fn mcts2_plays_legal_moves() {
+    init();
+    let mut players: [Box<dyn Player>; 2] = [Box::new(Mcts2::new()), Box::new(Random::new())];
+    let mut state = State::new();
+    let mut moves = Vec::new();
+    for ply in 0.. {
+        moves.clear();
+        state.get_moves(&mut moves);
+        let Some(m) = players[ply % 2].choose_move(&state, Duration::from_millis(5)) else {
+            break;
+        };
+        assert!(moves.contains(&m), "{}", m.notation());
+        state.apply(m);
+    }
+    assert!(state.result().is_some());
+    assert_eq!(Mcts2::new().choose_move(&state, Duration::ZERO), None);
+}
+
+// With a few hundred milliseconds the search finds a winning move in a small
+// endgame
+#[test]
+fn mcts2_finds_endgame_win() {
+    init();
+    let mut state = won_endgame();
+    assert_eq!(brute_force(&mut state), Proof::Win);
+
+    let m = Mcts2::new()
+        .choose_move(&state, Duration::from_millis(500))
+        .unwrap();
+    let undo = state.make(m);
+    let reply = brute_force(&mut state);
+    state.undo(undo);
+    assert_eq!(reply, Proof::Loss, "{}", m.notation());
+}
+
+// The reply the search expands first, which has the highest playout weight
+fn reply(state: &State) -> Move {
+    let playout = Playout::default();
+    let mut moves = Vec::new();
+    state.get_moves(&mut moves);
+    moves
+        .into_iter()
+        .max_by_key(|m| playout.weight(state, *m))
+        .unwrap()
+}
+
+#[test]
+fn mcts2_reuses_tree() {
+    init();
+    let mut player = Mcts2::new();
+    let mut state = State::from_notation(MIDGAME).unwrap();
+    player.new_game(&state);
+
+    let m = player
+        .choose_move(&state, Duration::from_millis(200))
+        .unwrap();
+    assert_eq!(player.stats().reused, 0);
+    state.apply(m);
+    let reply = reply(&state);
+    state.apply(reply);
+    player.opponent_moved(&state, reply);
+
+    player
+        .choose_move(&state, Duration::from_millis(50))
+        .unwrap();
+    let stats = player.stats();
+    assert!(stats.reused > 0);
+    assert!(stats.nodes > stats.reused);
+    assert_eq!(stats.pondered, 0);
+
+    // A position which isn't in the tree starts over
+    player.choose_move(&State::new(), Duration::ZERO).unwrap();
+    assert_eq!(player.stats().reused, 0);
+}
+
+#[test]
+fn mcts2_ponders() {
+    init();
+    let mut player = Mcts2::pondering();
+    let mut state = State::from_notation(MIDGAME).unwrap();
+
+    let m = player
+        .choose_move(&state, Duration::from_millis(500))
+        .unwrap();
+    assert!(player.is_pondering());
+    state.apply(m);
+    std::thread::sleep(Duration::from_millis(50));
+
+    // The opponent's move stops the background search promptly
+    let reply = reply(&state);
+    state.apply(reply);
+    let start = Instant::now();
+    player.opponent_moved(&state, reply);
+    assert!(start.elapsed() < Duration::from_millis(100));
+    assert!(!player.is_pondering());
+
+    player
+        .choose_move(&state, Duration::from_millis(10))
+        .unwrap();
+    let stats = player.stats();
+    assert!(stats.pondered > 0);
+    assert!(stats.reused > 0);
+
+    player.stop();
+    assert!(!player.is_pondering());
+}
+
+#[test]
+fn mcts2_memory_budget() {
+    init();
+    let mut player = Mcts2::new();
+    player.memory = 8 << 10;
+    let mut state = State::new();
+
+    let m = player.choose_move(&state, Duration::from_secs(1)).unwrap();
+    let stats = player.stats();
+    assert!(stats.full);
+    assert!(stats.bytes <= 8 << 10);
+
+    // The search carries on with the tree it kept, which stays in budget
+    state.apply(m);
+    let reply = reply(&state);
+    state.apply(reply);
+    player.opponent_moved(&state, reply);
+    player.choose_move(&state, Duration::from_secs(1)).unwrap();
+    let again = player.stats();
+    assert!(again.iterations > 0);
+    assert!(again.bytes <= 8 << 10);
+}
@@ -0,0 +1,251 @@
+use nego::core::{
+    error::Error,
+    game::State,
+    pieces::PieceId,
+    r#move::Move,
+    record::{GameRecord, RecordReader},
+};
+
+use std::str::FromStr;
+
+#[test]
+fn move_round_trip() {
+    let state = State::new();
+    let mut moves = Vec::new();
+    state.get_moves(&mut moves);
+    for m in moves {
+        assert_eq!(Move::parse(&m.notation()), Ok(m));
+    }
+
+    assert_eq!(
+        Move::parse("BOS:A1S").map(|m| m.get_piece()),
+        Ok(PieceId::Boss)
+    );
+    assert_eq!(Move::parse("BOS:A1"), Err(Error::InvalidFormat));
+    assert_eq!(Move::parse("XYZ:A1S"), Err(Error::InvalidPiece));
+    assert_eq!(Move::parse("BOS:H8S"), Err(Error::LUTEntryNotFound));
+
+    assert_eq!(Move::parse("PASS"), Ok(Move::PASS));
+    assert_eq!(Move::PASS.notation(), "PASS");
+}
+
+#[test]
+fn position_start() {
+    let state = State::new();
+    assert_eq!(state.to_notation(), "-/- 0/0 b");
+    assert_eq!(State::from_notation("-/- 0/0 b"), Ok(state));
+}
+
+#[test]
+fn position_round_trip() {
+    use rand::{seq::SliceRandom, SeedableRng};
+    let mut rng = rand::rngs::StdRng::seed_from_u64(789);
+
+    for _ in 0..20 {
+        let mut state = State::new();
+        let mut moves = Vec::new();
+        loop {
+            let notation = state.to_notation();
+            assert_eq!(State::from_notation(&notation).as_ref(), Ok(&state));
+
+            moves.truncate(0);
+            state.get_moves(&mut moves);
+            if moves.is_empty() {
+                break;
+            }
+            state.apply(*moves.choose(&mut rng).unwrap());
+        }
+    }
+}
+
+#[test]
+fn position_instances() {
+    let state = State::from_notation("BOS:A1S,KB3b:C1S/BOS:F6S 0/0 w").unwrap();
+    assert_eq!(
+        state.board.black.move_list[1].get_piece(),
+        PieceId::Koubaku3b
+    );
+    assert!(state.board.black.hand.holding(PieceId::Koubaku3a));
+    assert!(!state.board.black.hand.holding(PieceId::Koubaku3b));
+}
+
+#[test]
+fn position_invalid() {
+    use Error::*;
+
+    let cases = [
+        // Malformed
+        ("", InvalidPosition),
+        ("-/- 0/0", InvalidPosition),
+        ("-/- 0/0 x", InvalidPosition),
+        ("- 0/0 b", InvalidPosition),
+        ("-/- 0/zz b", InvalidPosition),
+        ("-/- 0/0 b 3", InvalidPosition),
+        // Hands and the boss mask are no longer written
+        ("-/- fff/fff 0/0 0 b", InvalidPosition),
+        ("BOS:A1/- 0/0 b", InvalidFormat),
+        // The same piece played twice
+        (
+            "BOS:A1S,MAM:C3S,MAM:E5S/BOS:F6S 0/0 w",
+            InconsistentPosition,
+        ),
+        // Overlapping pieces
+        ("BOS:A1S/BOS:B1S 0/0 b", InconsistentPosition),
+        // Boss must be placed first
+        ("MAM:A1S/- 0/0 w", InconsistentPosition),
+        // Territory containing opponent pieces
+        (
+            "BOS:A1S/BOS:F6S,MAM:H8N 8000000000000000/0 b",
+            InconsistentPosition,
+        ),
+    ];
+
+    for (notation, err) in cases {
+        assert_eq!(State::from_notation(notation), Err(err), "{}", notation);
+    }
+}
+
+fn sample_record(plies: usize) -> GameRecord {
+    // Avoid the duplicated pieces so that the game is the same as before
+    // instances were written
+    random_record(plies, |m| {
+        !matches!(
+            m.get_piece(),
+            PieceId::Koubaku3a | PieceId::Koubaku3b | PieceId::Kunoji1a | PieceId::Kunoji1b
+        )
+    })
+}
+
+fn random_record(plies: usize, keep: impl Fn(&Move) -> bool) -> GameRecord {
+    use rand::{seq::SliceRandom, SeedableRng};
+    let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+
+    let mut record = GameRecord::new();
+    record.black = "Random".to_string();
+    record.white = "Human \"Guest\"".to_string();
+
+    let mut state = State::new();
+    let mut moves = Vec::new();
+    for i in 0..plies {
+        moves.truncate(0);
+        state.get_moves(&mut moves);
+        moves.retain(&keep);
+        if moves.is_empty() {
+            break;
+        }
+        let m = *moves.choose(&mut rng).unwrap();
+        state.apply(m);
+        if i % 3 == 0 {
+            record.push_with_comment(m, &format!("comment {}", i));
+        } else {
+            record.push(m);
+        }
+    }
+    record
+}
+
+#[test]
+fn record_round_trip() {
+    let record = sample_record(10);
+    let text = record.to_string();
+    assert!(text.contains("[White \"Human \\\"Guest\\\"\"]"));
+
+    let parsed = GameRecord::from_str(&text).unwrap();
+    assert_eq!(parsed, record);
+    assert_eq!(parsed.replay(), record.replay());
+    assert_eq!(parsed.replay().unwrap().board.black.move_list.len(), 5);
+}
+
+#[test]
+fn record_stream() {
+    let mut first = sample_record(6);
+    first.result = "B+2.5".to_string();
+    first
+        .tags
+        .push(("Event".to_string(), "Self-play".to_string()));
+
+    let mut second = sample_record(3);
+    second.position = Some(State::new().to_notation());
+
+    let mut empty = GameRecord::new();
+    empty.black = "Nobody".to_string();
+
+    let mut buf = Vec::new();
+    for record in [&first, &empty, &second] {
+        record.write(&mut buf).unwrap();
+        buf.push(b'\n');
+    }
+
+    let records = RecordReader::new(buf.as_slice())
+        .collect::<Result<Vec<GameRecord>, Error>>()
+        .unwrap();
+    assert_eq!(records, vec![first, empty, second]);
+}
+
+#[test]
+fn record_invalid() {
+    assert_eq!(
+        GameRecord::from_str("[Komi \"lots\"]\n"),
+        Err(Error::InvalidRecord)
+    );
+    assert_eq!(
+        GameRecord::from_str("[Black \"X\"\n"),
+        Err(Error::InvalidRecord)
+    );
+    assert_eq!(GameRecord::from_str("BOS:A1"), Err(Error::InvalidFormat));
+
+    // Both players can't place a boss on the same spot
+    let record = GameRecord::from_str("BOS:A1S\nBOS:A1S\n").unwrap();
+    assert_eq!(record.replay(), Err(Error::IllegalMove));
+}
+
+#[test]
+fn record_full_games() {
+    for plies in [20, 40, 100] {
+        let record = random_record(plies, |_| true);
+        let parsed = GameRecord::from_str(&record.to_string()).unwrap();
+        assert_eq!(parsed, record);
+        assert!(parsed.replay().is_ok());
+    }
+}
+
+#[test]
+fn move_instances() {
+    let m = Move::parse("KB3b:C1S").unwrap();
+    assert_eq!(m.get_piece(), PieceId::Koubaku3b);
+    assert_eq!(m.notation(), "KB3b:C1S");
+
+    // Without an instance, the first one is assumed
+    let m = Move::parse("KJ1:C1S").unwrap();
+    assert_eq!(m.get_piece(), PieceId::Kunoji1a);
+    assert_eq!(m.notation(), "KJ1a:C1S");
+
+    assert_eq!(Move::parse("BOS:A1S").unwrap().notation(), "BOS:A1S");
+    assert_eq!(Move::parse("BOSa:A1S"), Err(Error::InvalidPiece));
+}
+
+#[test]
+fn parse_move_from_hand() {
+    // Black played the second koubaku 3 after the first was captured and
+    // returned to hand.
+    let state = State::from_notation("BOS:A1S,KB3b:C1S/BOS:F6S 0/0 b").unwrap();
+    let m = state.parse_move("KB3:E1S").unwrap();
+    assert_eq!(m.get_piece(), PieceId::Koubaku3a);
+    assert_eq!(state.parse_move("KB3a:E1S"), Ok(m));
+    assert_eq!(state.parse_move("KB3b:E1S"), Err(Error::IllegalMove));
+    assert_eq!(state.parse_move("BOS:C3S"), Err(Error::IllegalMove));
+
+    // The first instance is played first
+    let mut state = State::new();
+    state.apply(state.parse_move("BOS:A1S").unwrap());
+    state.apply(state.parse_move("BOS:F6S").unwrap());
+    let m = state.parse_move("KB3:E1S").unwrap();
+    assert_eq!(m.get_piece(), PieceId::Koubaku3a);
+    state.apply(m);
+    state.apply(state.parse_move("MAM:H8N").unwrap());
+    assert_eq!(
+        state.parse_move("KB3:H1S").map(|m| m.get_piece()),
+        Ok(PieceId::Koubaku3b)
+    );
+    assert_eq!(state.parse_move("KB3a:H1S"), Err(Error::IllegalMove));
+}
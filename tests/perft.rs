@@ -0,0 +1,71 @@
+mod common;
+
+use nego::core::{
+    game::State,
+    perft::{perft, perft_divide, PerftStats},
+    rules::Rules,
+};
+
+use common::{init, ENDGAME, MIDGAME};
+
+fn stats(nodes: u64, captures: u64, territory: u64, terminal: u64) -> PerftStats {
+    PerftStats {
+        nodes,
+        captures,
+        territory,
+        terminal,
+    }
+}
+
+#[test]
+fn perft_start() {
+    init();
+    let mut state = State::new();
+    assert_eq!(perft(&mut state, 0), stats(1, 0, 0, 0));
+    assert_eq!(perft(&mut state, 1), stats(48, 0, 0, 0));
+    assert_eq!(perft(&mut state, 2), stats(1960, 0, 0, 0));
+    assert_eq!(state, State::new());
+}
+
+#[test]
+fn perft_midgame() {
+    init();
+    let mut state = State::from_notation(MIDGAME).unwrap();
+    let before = state.clone();
+    assert_eq!(perft(&mut state, 1), stats(117, 0, 20, 0));
+    assert_eq!(perft(&mut state, 2), stats(6701, 1727, 1913, 0));
+    assert_eq!(state, before);
+}
+
+#[test]
+fn perft_endgame() {
+    init();
+    let mut state = State::from_notation(ENDGAME).unwrap();
+    assert_eq!(perft(&mut state, 1), stats(25, 0, 17, 0));
+    assert_eq!(perft(&mut state, 2), stats(312, 0, 0, 56));
+    assert_eq!(perft(&mut state, 3), stats(1332, 2, 500, 758));
+}
+
+#[test]
+fn perft_passing() {
+    init();
+    let mut state = State::from_notation(MIDGAME).unwrap();
+    state.rules = Rules {
+        allow_pass: true,
+        ..Rules::standard()
+    };
+    assert_eq!(perft(&mut state, 1), stats(118, 0, 20, 0));
+    assert_eq!(perft(&mut state, 2), stats(6895, 1746, 1934, 1));
+}
+
+#[test]
+fn perft_divide_total() {
+    init();
+    let mut state = State::from_notation(MIDGAME).unwrap();
+    let divide = perft_divide(&mut state, 2);
+    assert_eq!(divide.len(), 117);
+
+    let mut total = PerftStats::default();
+    divide.iter().for_each(|(_, stats)| total += *stats);
+    assert_eq!(total, perft(&mut state, 2));
+}
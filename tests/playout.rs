@@ -0,0 +1,131 @@
+mod common;
+
+use nego::{
+    agent::playout::{play_match, Playout, PlayoutWeights},
+    core::{game::State, r#move::Move},
+};
+
+use common::{init, MIDGAME};
+
+use rand::{rngs::StdRng, SeedableRng};
+
+fn only(weights: PlayoutWeights) -> Playout {
+    Playout {
+        weights,
+        epsilon: 0.,
+    }
+}
+
+const ZERO: PlayoutWeights = PlayoutWeights {
+    base: 0,
+    territory: 0,
+    capture: 0,
+    support: 0,
+    contact: 0,
+    size: 0,
+};
+
+// Captures only become available a move later
+#[test]
+fn playout_capture_weight() {
+    init();
+    let mut state = State::from_notation(MIDGAME).unwrap();
+    let policy = only(PlayoutWeights { capture: 1, ..ZERO });
+
+    let mut roots = Vec::new();
+    state.get_moves(&mut roots);
+    let mut captures = 0;
+    for root in roots {
+        let root_undo = state.make(root);
+        let mut moves = Vec::new();
+        state.get_moves(&mut moves);
+        for m in moves {
+            let predicted = policy.weight(&state, m);
+            let undo = state.make(m);
+            let captured: u32 = undo.captured().map(|m| m.mask().popcnt()).sum();
+            state.undo(undo);
+
+            assert_eq!(predicted, captured, "{} {}", root.notation(), m.notation());
+            captures += (captured > 0) as u32;
+        }
+        state.undo(root_undo);
+    }
+    assert_eq!(captures, 1727);
+}
+
+#[test]
+fn playout_territory_weight() {
+    init();
+    let mut state = State::from_notation(MIDGAME).unwrap();
+    let policy = only(PlayoutWeights {
+        territory: 1,
+        ..ZERO
+    });
+
+    let mut moves = Vec::new();
+    state.get_moves(&mut moves);
+    for m in moves {
+        let owned = state.board.white.owned;
+        let undo = state.make(m);
+        let claimed = state.board.white.owned & !owned & !state.board.occupied();
+        state.undo(undo);
+
+        assert_eq!(
+            policy.weight(&state, m),
+            claimed.popcnt(),
+            "{}",
+            m.notation()
+        );
+    }
+}
+
+#[test]
+fn playout_choose() {
+    init();
+    let state = State::from_notation(MIDGAME).unwrap();
+    let mut moves = Vec::new();
+    state.get_moves(&mut moves);
+
+    let mut rng = StdRng::seed_from_u64(1);
+    for policy in [Playout::default(), Playout::uniform()] {
+        assert_eq!(policy.choose(&state, &[], &mut rng), None);
+        let m = policy.choose(&state, &moves, &mut rng).unwrap();
+        assert!(moves.contains(&m));
+
+        let end = policy.playout(state.clone(), &mut rng);
+        assert!(end.result().is_some());
+    }
+}
+
+// Passing is the only move once nothing can be placed, and nothing may
+// weigh anything
+#[test]
+fn playout_choose_unweighted() {
+    init();
+    let state = State::from_notation(MIDGAME).unwrap();
+    let mut moves = Vec::new();
+    state.get_moves(&mut moves);
+
+    let mut rng = StdRng::seed_from_u64(1);
+    for policy in [Playout::default(), only(ZERO)] {
+        let pass = policy.choose(&state, &[Move::PASS], &mut rng);
+        assert_eq!(pass, Some(Move::PASS));
+    }
+    let m = only(ZERO).choose(&state, &moves, &mut rng).unwrap();
+    assert!(moves.contains(&m));
+}
+
+#[test]
+fn playout_beats_uniform() {
+    init();
+    let mut rng = StdRng::seed_from_u64(0);
+    let result = play_match(
+        &Playout::default(),
+        &Playout::uniform(),
+        &State::new(),
+        200,
+        &mut rng,
+    );
+    assert_eq!(result.wins + result.losses + result.draws, 200);
+    assert!(result.win_rate() > 0.55, "{:?}", result);
+}
@@ -0,0 +1,612 @@
+mod common;
+
+use nego::core::{
+    error::Error,
+    game::{Color, State},
+    legality::Violation,
+    move_tab::LUTEntry,
+    orientation::Orientation,
+    pieces::{PieceId, PieceList},
+    r#move::Move,
+    rules::{Capture, Connection, Komi, Rules, Scoring},
+    square::Square,
+};
+
+use common::init;
+
+fn mk_move(piece: PieceId, pos: (u8, u8), orientation: Orientation) -> Move {
+    let entry = LUTEntry::lookup(
+        piece.piece_type_id(),
+        Square::from_indices(pos.0 as usize, pos.1 as usize),
+        orientation,
+    )
+    .unwrap();
+    Move::new(piece, entry)
+}
+
+fn play_moves(state: &mut State, moves: &[(PieceId, (u8, u8), Orientation)]) {
+    moves.iter().for_each(|(a, b, c)| {
+        state.place(mk_move(*a, *b, *c));
+        state.current = state.current.next();
+    });
+}
+
+fn violations(state: &State, color: Color, m: Move) -> Vec<Violation> {
+    match state.board.check_move(color, m) {
+        Ok(()) => Vec::new(),
+        Err(reason) => reason.violations().collect(),
+    }
+}
+
+#[test]
+fn check_move_agrees_with_generate_moves() {
+    use rand::{seq::SliceRandom, SeedableRng};
+    init();
+    let mut rng = rand::rngs::StdRng::seed_from_u64(1234);
+
+    for _ in 0..3 {
+        let mut state = State::new();
+        let mut moves = Vec::new();
+        loop {
+            moves.truncate(0);
+            state.get_moves(&mut moves);
+
+            let hand = match state.current {
+                Color::Black => state.board.black.hand,
+                Color::White => state.board.white.hand,
+            };
+
+            // Generation only considers the first instance of each piece type
+            let mut seen = PieceList::piece_seen_hash();
+            for piece in hand.available() {
+                if seen.seen(piece) {
+                    continue;
+                }
+                seen.add(piece);
+                let def = piece.piece_type_id().def();
+                for i in def.lut_offset..def.lut_offset + def.moves {
+                    let m = Move::new(piece, LUTEntry(i));
+                    assert_eq!(
+                        state.board.check_move(state.current, m).is_ok(),
+                        moves.contains(&m),
+                        "{} in {}",
+                        m,
+                        state.to_notation()
+                    );
+                }
+            }
+
+            if moves.is_empty() {
+                break;
+            }
+            state.apply(*moves.choose(&mut rng).unwrap());
+        }
+    }
+}
+
+#[test]
+fn hand_violations() {
+    use Orientation::*;
+    use PieceId::*;
+    init();
+
+    let mut state = State::new();
+    assert_eq!(
+        violations(&state, Color::Black, mk_move(Mame, (3, 3), S)),
+        vec![Violation::BossFirst]
+    );
+
+    play_moves(&mut state, &[(Boss, (0, 0), S), (Boss, (6, 6), S)]);
+    assert_eq!(
+        violations(&state, Color::Black, mk_move(Boss, (3, 0), S)),
+        vec![Violation::NotInHand]
+    );
+    assert_eq!(
+        violations(&state, Color::Black, mk_move(Mame, (1, 1), S)),
+        vec![Violation::Overlap]
+    );
+}
+
+#[test]
+fn gaze_violations() {
+    use Orientation::*;
+    use PieceId::*;
+    init();
+
+    let mut state = State::new();
+    play_moves(
+        &mut state,
+        &[
+            (Boss, (0, 0), S), // B
+            (Boss, (6, 6), S), // W
+            (Mame, (3, 3), S), // B
+        ],
+    );
+
+    // Looking up at the black mame, which is looking down
+    let m = mk_move(Mame, (3, 5), N);
+    assert_eq!(
+        violations(&state, Color::White, m),
+        vec![Violation::EyeContact]
+    );
+
+    // Looking left along the top of the black boss
+    let m = mk_move(Mame, (4, 0), W);
+    assert_eq!(
+        violations(&state, Color::White, m),
+        vec![Violation::FacingBoss]
+    );
+
+    // The paw touches its own boss, and it also looks at the boss
+    let m = mk_move(Nobi, (0, 2), N);
+    assert_eq!(
+        violations(&state, Color::White, m),
+        vec![Violation::FacingBoss]
+    );
+    assert_eq!(
+        violations(&state, Color::Black, m),
+        vec![Violation::FacingBoss, Violation::NobiPaw]
+    );
+}
+
+#[test]
+fn opposite_connection() {
+    use Orientation::*;
+    use PieceId::*;
+    init();
+
+    let mut state = State::new();
+    play_moves(
+        &mut state,
+        &[
+            (Boss, (0, 3), S), // B
+            (Boss, (6, 0), S), // W
+            (Nobi, (2, 3), S), // B
+            (Mame, (7, 7), W), // W
+        ],
+    );
+
+    let m = mk_move(Koubaku1, (6, 3), S);
+    assert_eq!(
+        violations(&state, Color::Black, m),
+        vec![Violation::OppositeConnection]
+    );
+    assert_eq!(violations(&state, Color::White, m), vec![]);
+}
+
+// From "Filled Territories" in doc/QUESTIONS.md
+#[test]
+fn filled_territory() {
+    use Orientation::*;
+    use PieceId::*;
+    init();
+
+    let mut state = State::new();
+    play_moves(
+        &mut state,
+        &[
+            (Boss, (5, 5), S),      // B
+            (Boss, (5, 0), S),      // W
+            (Koubaku3a, (2, 0), S), // B
+            (Kunoji3, (0, 0), W),   // W
+            (Koubaku1, (0, 2), S),  // B
+            (Koubaku1, (6, 3), S),  // W
+            (Mame, (2, 2), S),      // B
+        ],
+    );
+
+    // The white kunoji formed a territory at A1
+    assert!(state
+        .board
+        .white
+        .owned
+        .test_square(Square::from_indices(0, 0)));
+
+    // White may place within their own territory, but black may not
+    let m = mk_move(Mame, (0, 0), S);
+    assert_eq!(violations(&state, Color::White, m), vec![]);
+    let m = mk_move(Kunoji4, (0, 0), S);
+    assert_eq!(
+        violations(&state, Color::Black, m).first(),
+        Some(&Violation::Overlap)
+    );
+    assert!(violations(&state, Color::Black, m).contains(&Violation::OwnedTerritory));
+}
+
+#[test]
+fn reason_display() {
+    use Orientation::*;
+    use PieceId::*;
+
+    let state = State::new();
+    let reason = state
+        .board
+        .check_move(Color::Black, mk_move(Mame, (3, 3), S))
+        .unwrap_err();
+    assert_eq!(reason.to_string(), "the boss must be placed first");
+    assert!(reason.contains(Violation::BossFirst));
+    assert!(!reason.contains(Violation::Overlap));
+}
+
+#[test]
+fn rules_notation() {
+    assert_eq!(Rules::standard().to_string(), "standard");
+    assert_eq!("standard".parse(), Ok(Rules::standard()));
+
+    let rules = Rules {
+        connection: Connection::Diagonal,
+        capture: Capture::Liberties,
+        territory_extension: false,
+        allow_pass: true,
+        komi: Komi(13),
+        boss_on_center: true,
+        scoring: Scoring::Area,
+    };
+    let s = rules.to_string();
+    assert_eq!(
+        s,
+        "connection=diagonal,capture=liberties,extension=no,pass=yes,komi=6.5,center=yes,scoring=area"
+    );
+    assert_eq!(s.parse(), Ok(rules));
+
+    let rules = "komi=0".parse::<Rules>().unwrap();
+    assert_eq!(rules.komi, Komi(0));
+    assert_eq!(rules.connection, Connection::Orthogonal);
+
+    assert_eq!(Komi(-3).to_string(), "-1.5");
+    assert_eq!("-1.5".parse(), Ok(Komi(-3)));
+    for s in [
+        "",
+        "komi",
+        "komi=0.3",
+        "pass=maybe",
+        "colour=red",
+        "standard,komi=1",
+    ] {
+        assert_eq!(s.parse::<Rules>(), Err(Error::InvalidRules), "{}", s);
+    }
+}
+
+#[test]
+fn boss_on_center() {
+    let center = Move::new(PieceId::Boss, LUTEntry::CENTER_BOSS);
+    assert_eq!(Move::parse("BOS:D4S"), Ok(center));
+
+    let mut moves = Vec::new();
+    let state = State::new();
+    state.get_moves(&mut moves);
+    assert!(!moves.contains(&center));
+    assert_eq!(
+        state
+            .check_move(center)
+            .map_err(|r| r.violations().collect()),
+        Err(vec![Violation::BossOnCenter])
+    );
+
+    let mut rules = Rules::standard();
+    rules.boss_on_center = true;
+    let mut state = State::with_rules(rules);
+    moves.truncate(0);
+    state.get_moves(&mut moves);
+    assert!(moves.contains(&center));
+    assert_eq!(state.check_move(center), Ok(()));
+
+    let undo = state.make(center);
+    assert_eq!(state.board.boss, center.mask());
+    assert_ne!(state.hash, 0);
+    state.undo(undo);
+    assert_eq!(state, State::with_rules(state.rules.clone()));
+}
+
+// From "Adjacent Connections" in doc/QUESTIONS.md
+#[test]
+fn diagonal_connection() {
+    use Orientation::*;
+    use PieceId::*;
+
+    let opening = [
+        (Boss, (4, 0), S),     // B
+        (Boss, (5, 3), S),     // W
+        (Mame, (0, 5), S),     // B
+        (Kunoji1a, (0, 6), N), // W
+        (Kunoji3, (1, 6), S),  // B
+    ];
+
+    // The white kunoji only counts as surrounded once black closes the gap
+    let mut state = State::new();
+    play_moves(&mut state, &opening);
+    assert_eq!(state.board.white.move_list.len(), 2);
+    play_moves(&mut state, &[(Mame, (7, 7), W), (Koubaku1, (1, 4), W)]);
+    assert_eq!(state.board.white.move_list.len(), 2);
+    assert!(state.board.white.hand.holding(Kunoji1a));
+
+    // Pieces which touch at the corners are already connected
+    let mut rules = Rules::standard();
+    rules.connection = Connection::Diagonal;
+    let mut state = State::with_rules(rules);
+    play_moves(&mut state, &opening);
+    assert_eq!(state.board.white.move_list.len(), 1);
+    assert!(state.board.white.hand.holding(Kunoji1a));
+    assert!(state
+        .board
+        .black
+        .owned
+        .test_square(Square::from_indices(0, 7)));
+}
+
+#[test]
+fn liberties_capture() {
+    use Orientation::*;
+    use PieceId::*;
+
+    let moves = [
+        (Boss, (4, 4), S),     // B
+        (Boss, (6, 0), S),     // W
+        (Koubaku1, (1, 0), S), // B
+        (Mame, (0, 0), S),     // W
+        (Mame, (0, 1), S),     // B
+    ];
+
+    // The corner isn't surrounded because B2 is open
+    let mut state = State::new();
+    play_moves(&mut state, &moves);
+    assert_eq!(state.board.white.move_list.len(), 2);
+
+    // But the white mame has no liberties
+    let mut rules = Rules::standard();
+    rules.capture = Capture::Liberties;
+    let mut state = State::with_rules(rules);
+    play_moves(&mut state, &moves[..4]);
+    let before = state.clone();
+
+    let undo = state.make(mk_move(Mame, (0, 1), S));
+    assert_eq!(state.board.white.move_list.len(), 1);
+    assert!(state.board.white.hand.holding(Mame));
+    assert_eq!(undo.captured().count(), 1);
+    assert!(!state.board.owned().test_square(Square::from_indices(0, 0)));
+
+    state.undo(undo);
+    assert_eq!(state, before);
+}
+
+// The captured piece goes back to its owner's hand, not the capturing one
+#[test]
+fn capture_returns_captured_piece() {
+    use Orientation::*;
+    use PieceId::*;
+
+    let mut rules = Rules::standard();
+    rules.capture = Capture::Liberties;
+    let mut state = State::with_rules(rules);
+    play_moves(
+        &mut state,
+        &[
+            (Boss, (4, 4), S), // B
+            (Boss, (6, 0), S), // W
+            (Mame, (0, 1), S), // B
+            (Mame, (0, 0), S), // W
+        ],
+    );
+    state.place(mk_move(Koubaku1, (1, 0), S)); // B
+    assert_eq!(state.board.white.move_list.len(), 1);
+    assert!(state.board.white.hand.holding(Mame));
+    assert!(!state.board.black.hand.holding(Koubaku1));
+}
+
+// After a capture the hash is recomputed with each player's own pieces, and
+// matches the same position reached without one
+#[test]
+fn capture_hash() {
+    use Orientation::*;
+    use PieceId::*;
+
+    let mut rules = Rules::standard();
+    rules.capture = Capture::Liberties;
+    rules.allow_pass = true;
+    let play = |moves: &[Move]| {
+        let mut state = State::with_rules(rules.clone());
+        moves.iter().for_each(|m| state.apply(*m));
+        state
+    };
+
+    let captured = play(&[
+        mk_move(Boss, (4, 4), S),
+        mk_move(Boss, (6, 0), S),
+        mk_move(Mame, (0, 1), S),
+        mk_move(Mame, (0, 0), S),
+        mk_move(Koubaku1, (1, 0), S),
+    ]);
+    assert_eq!(captured.board.white.move_list.len(), 1);
+
+    let passed = play(&[
+        mk_move(Boss, (4, 4), S),
+        mk_move(Boss, (6, 0), S),
+        mk_move(Mame, (0, 1), S),
+        Move::PASS,
+        mk_move(Koubaku1, (1, 0), S),
+    ]);
+    assert_eq!(captured.board.black.move_list, passed.board.black.move_list);
+    assert_eq!(captured.hash, passed.hash);
+}
+
+// From "Territory Extension" in doc/QUESTIONS.md
+#[test]
+fn territory_extension() {
+    use Orientation::*;
+    use PieceId::*;
+
+    let moves = [
+        (Boss, (4, 4), S),     // B
+        (Boss, (6, 6), S),     // W
+        (Mame, (7, 0), S),     // B
+        (Kunoji3, (0, 0), W),  // W
+        (Koubaku1, (6, 2), S), // B
+        (Nobi, (2, 1), S),     // W
+    ];
+    let nobi = mk_move(Nobi, (2, 1), S).mask();
+
+    let mut state = State::new();
+    play_moves(&mut state, &moves);
+    assert!(nobi.is_subset(state.board.white.owned));
+
+    let mut rules = Rules::standard();
+    rules.territory_extension = false;
+    let mut state = State::with_rules(rules);
+    play_moves(&mut state, &moves);
+    assert!(!nobi.intersects(state.board.white.owned));
+}
+
+#[test]
+fn scoring() {
+    use Orientation::*;
+    use PieceId::*;
+
+    let moves = [
+        (Boss, (4, 4), S),    // B
+        (Boss, (6, 6), S),    // W
+        (Mame, (7, 0), S),    // B
+        (Kunoji3, (0, 0), W), // W
+    ];
+
+    let mut state = State::new();
+    play_moves(&mut state, &moves);
+    let (b, w) = (5, 7);
+    assert_eq!(state.rules.score(&state.board, Color::Black), b);
+    assert_eq!(state.rules.score(&state.board, Color::White), w);
+    assert_eq!(state.rules.margin(&state.board), -5);
+
+    // The white territory at A1 counts under area scoring
+    state.rules.scoring = Scoring::Area;
+    state.rules.komi = Komi(0);
+    assert_eq!(state.rules.score(&state.board, Color::White), w + 1);
+    assert_eq!(state.rules.margin(&state.board), -6);
+}
+
+#[test]
+fn passing() {
+    use Orientation::*;
+    use PieceId::*;
+
+    // Passing isn't allowed by default
+    let mut moves = Vec::new();
+    let state = State::new();
+    state.get_moves(&mut moves);
+    assert!(!moves.contains(&Move::PASS));
+    assert_eq!(state.parse_move("PASS"), Err(Error::IllegalMove));
+    assert_eq!(
+        state
+            .check_move(Move::PASS)
+            .map_err(|r| r.violations().collect()),
+        Err(vec![Violation::Pass])
+    );
+
+    let mut rules = Rules::standard();
+    rules.allow_pass = true;
+    let mut state = State::with_rules(rules);
+    state.apply(mk_move(Boss, (0, 0), S));
+    state.apply(mk_move(Boss, (6, 6), S));
+    let start = state.clone();
+
+    moves.truncate(0);
+    state.get_moves(&mut moves);
+    assert_eq!(moves.last(), Some(&Move::PASS));
+    assert_eq!(state.parse_move("PASS"), Ok(Move::PASS));
+    assert_eq!(state.check_move(Move::PASS), Ok(()));
+
+    // A pass changes the turn, but not the board
+    let first = state.make(Move::PASS);
+    assert_eq!(state.current, Color::White);
+    assert_eq!(state.passes, 1);
+    assert_eq!(state.board, start.board);
+    assert_ne!(state.hash, start.hash);
+    assert!(state.has_moves());
+
+    // A move resets the count
+    let m = mk_move(Mame, (3, 3), S);
+    let undo = state.make(m);
+    assert_eq!(state.passes, 0);
+    state.undo(undo);
+
+    // Two passes in a row end the game
+    let second = state.make(Move::PASS);
+    assert_eq!(state.passes, 2);
+    assert!(!state.has_moves());
+    moves.truncate(0);
+    state.get_moves(&mut moves);
+    assert!(moves.is_empty());
+
+    // The hash agrees with one computed from scratch
+    let notation = state.to_notation();
+    assert!(notation.ends_with(" b 2"));
+    let mut restored = State::from_notation(&notation).unwrap();
+    restored.rules.allow_pass = true;
+    assert_eq!(restored, state);
+
+    state.undo(second);
+    state.undo(first);
+    assert_eq!(state, start);
+}
+
+#[test]
+fn game_result() {
+    use rand::{seq::SliceRandom, SeedableRng};
+    init();
+    let mut rng = rand::rngs::StdRng::seed_from_u64(99);
+
+    let mut state = State::new();
+    let mut moves = Vec::new();
+    loop {
+        moves.truncate(0);
+        state.get_moves(&mut moves);
+        match moves.choose(&mut rng) {
+            Some(m) => {
+                assert_eq!(state.result(), None);
+                state.apply(*m);
+            }
+            None => break,
+        }
+    }
+
+    let result = state.result().unwrap();
+    let score = result.score;
+    assert_eq!(score, state.score());
+    assert_eq!(score.black.pieces, state.board.black.occupied.popcnt());
+    assert_eq!(score.white.score, score.white.pieces);
+    assert_eq!(
+        score.margin,
+        (score.black.score as i32 - score.white.score as i32) * 2 - 1
+    );
+    let winner = if score.margin > 0 {
+        Color::Black
+    } else {
+        Color::White
+    };
+    assert_eq!(result.winner, Some(winner));
+}
+
+#[test]
+fn game_result_draw() {
+    use Orientation::*;
+    use PieceId::*;
+
+    let mut rules = Rules::standard();
+    rules.allow_pass = true;
+    let mut state = State::with_rules(rules);
+    state.apply(mk_move(Boss, (0, 0), S));
+    state.apply(mk_move(Boss, (6, 6), S));
+    state.apply(Move::PASS);
+    state.apply(Move::PASS);
+
+    let result = state.result().unwrap();
+    assert_eq!(result.winner, Some(Color::White));
+    assert_eq!(result.to_string(), "W+0.5");
+    assert_eq!(
+        result.score.to_string(),
+        "black 4 (4 pieces, 0 territory), white 4 (4 pieces, 0 territory), komi 0.5: W+0.5"
+    );
+
+    state.rules.komi = Komi(0);
+    let result = state.result().unwrap();
+    assert!(result.is_draw());
+    assert_eq!(result.to_string(), "Draw");
+}
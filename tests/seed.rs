@@ -0,0 +1,94 @@
+mod common;
+
+use nego::{
+    agent::{
+        mcts2::Mcts2,
+        solver::{Limits, Solver},
+        spec::AgentSpec,
+        step_within,
+        time::Budget,
+        Agent, Player, Random,
+    },
+    core::{game::State, r#move::Move},
+};
+
+use common::{init, MIDGAME};
+
+// Play both sides with seeded players on a fixed budget, for up to `plies`
+fn play(players: [&dyn Fn() -> Box<dyn Player>; 2], state: &State, plies: usize) -> Vec<Move> {
+    let mut players = [players[0](), players[1]()];
+    for (seed, player) in players.iter_mut().enumerate() {
+        player.set_seed(seed as u64 + 7);
+        player.new_game(state);
+    }
+    let mut state = state.clone();
+    let mut moves = Vec::new();
+    while moves.len() < plies {
+        let s = moves.len() % 2;
+        let Some(m) = step_within(players[s].as_mut(), &mut state, Budget::iterations(20)) else {
+            break;
+        };
+        players[1 - s].opponent_moved(&state, m);
+        moves.push(m);
+    }
+    moves
+}
+
+#[test]
+fn seed_repeats_moves() {
+    init();
+    let state = State::from_notation(MIDGAME).unwrap();
+    let mcts2 = || -> Box<dyn Player> { Box::new(Mcts2::new()) };
+    let solver = || -> Box<dyn Player> { Box::new(Solver::default()) };
+    let random = || -> Box<dyn Player> { Box::new(Random::new()) };
+    for players in [[&mcts2 as &dyn Fn() -> _, &solver], [&random, &mcts2]] {
+        let moves = play(players, &state, 6);
+        assert_eq!(moves.len(), 6);
+        assert_eq!(moves, play(players, &state, 6));
+    }
+}
+
+// Whole games with the built in agents, which use the endgame solver
+#[test]
+fn seed_repeats_games() {
+    init();
+    let mcts2 = || Agent::Mcts2(Default::default()).player().unwrap();
+    let solver = || Agent::Solver(Default::default()).player().unwrap();
+    let players = [&mcts2 as &dyn Fn() -> _, &solver];
+    let game = play(players, &State::new(), usize::MAX);
+    assert_eq!(game, play(players, &State::new(), usize::MAX));
+}
+
+#[test]
+fn seed_changes_moves() {
+    init();
+    let random = || -> Box<dyn Player> { Box::new(Random::new()) };
+    let moves = |seed| {
+        let mut player = random();
+        player.set_seed(seed);
+        let mut state = State::new();
+        (0..6)
+            .map(|_| step_within(player.as_mut(), &mut state, Budget::iterations(1)).unwrap())
+            .collect::<Vec<_>>()
+    };
+    assert_ne!(moves(1), moves(2));
+}
+
+#[test]
+fn seed_iteration_budget() {
+    init();
+    let state = State::from_notation(MIDGAME).unwrap();
+    let mut player = Mcts2::new();
+    player.choose_move_within(&state, Budget::iterations(40));
+    assert_eq!(player.stats().iterations, 40);
+
+    let result = Solver::default().search(&state, Limits::budget(Budget::iterations(40)));
+    assert_eq!(result.iterations, 40);
+}
+
+// The minimax-rs `Mcts` can't be seeded, so a spec can't give it a seed
+#[test]
+fn seed_mcts() {
+    assert!("mcts:seed=7".parse::<AgentSpec>().is_err());
+    assert!("mcts:playouts=64".parse::<AgentSpec>().is_ok());
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use nego::{
+    agent::{
+        playout::Playout,
+        solver::{Limits, Proof, Solver},
+        tree::Widening,
+    },
+    core::game::State,
+};
+
+use common::{init, ENDGAME, MIDGAME};
+
+// The exact outcome for the player to move, by searching every line
+fn brute_force(state: &mut State) -> Proof {
+    let mut moves = Vec::new();
+    state.get_moves(&mut moves);
+    if moves.is_empty() {
+        let result = state.result().unwrap();
+        return match result.winner {
+            None => Proof::Draw,
+            Some(winner) if winner == state.current => Proof::Win,
+            Some(_) => Proof::Loss,
+        };
+    }
+
+    let mut best = Proof::Loss;
+    for m in moves {
+        let undo = state.make(m);
+        let reply = brute_force(state);
+        state.undo(undo);
+        match reply {
+            Proof::Loss => return Proof::Win,
+            Proof::Draw => best = Proof::Draw,
+            _ => (),
+        }
+    }
+    best
+}
+
+#[test]
+fn solver_proves_endgame() {
+    init();
+    let mut state = State::from_notation(ENDGAME).unwrap();
+    let expected = brute_force(&mut state);
+
+    let result = Solver::default().search(&state, Limits::iterations(100_000));
+    assert_eq!(result.proof, expected);
+    assert!(result.iterations < 100_000);
+
+    // The chosen move keeps the proven result
+    let m = result.best.unwrap();
+    let undo = state.make(m);
+    let reply = brute_force(&mut state);
+    state.undo(undo);
+    let kept = match expected {
+        Proof::Win => reply == Proof::Loss,
+        Proof::Draw => reply == Proof::Draw,
+        _ => true,
+    };
+    assert!(kept, "{} leads to {:?}", m.notation(), reply);
+}
+
+#[test]
+fn solver_proves_children() {
+    init();
+    let mut state = State::from_notation(ENDGAME).unwrap();
+    let mut moves = Vec::new();
+    state.get_moves(&mut moves);
+
+    for m in moves {
+        let undo = state.make(m);
+        let result = Solver::default().search(&state, Limits::iterations(100_000));
+        assert_eq!(result.proof, brute_force(&mut state), "{}", m.notation());
+        state.undo(undo);
+    }
+}
+
+#[test]
+fn solver_game_over() {
+    init();
+    let mut state = State::from_notation(ENDGAME).unwrap();
+    while let Some(m) = Solver::default()
+        .search(&state, Limits::iterations(1000))
+        .best
+    {
+        state.apply(m);
+    }
+
+    let result = Solver::default().search(&state, Limits::iterations(1000));
+    assert_eq!(result.best, None);
+    assert_eq!(result.iterations, 0);
+    assert_ne!(result.proof, Proof::Unknown);
+}
+
+#[test]
+fn solver_opening() {
+    init();
+    let state = State::new();
+    let result = Solver::default().search(&state, Limits::iterations(200));
+    assert_eq!(result.proof, Proof::Unknown);
+    assert_eq!(result.iterations, 200);
+    assert!(result.best.is_some());
+}
+
+#[test]
+fn solver_memory_budget() {
+    init();
+    let solver = Solver {
+        memory: 8 << 10,
+        ..Solver::default()
+    };
+    let result = solver.search(&State::new(), Limits::iterations(200));
+    assert!(result.full);
+    assert!(result.bytes <= 8 << 10);
+    // The search carries on once the tree stops growing
+    assert_eq!(result.iterations, 200);
+    assert!(result.best.is_some());
+}
+
+// With one child per node, only the move with the highest playout weight is
+// ever tried at the root
+#[test]
+fn solver_widening() {
+    init();
+    let widening = Widening {
+        base: 1.,
+        exponent: 0.,
+    };
+    assert_eq!(widening.children(1000), 1);
+    assert_eq!(Widening::default().children(0), 1);
+    assert_eq!(Widening::default().children(100), 20);
+
+    let state = State::from_notation(MIDGAME).unwrap();
+    let solver = Solver {
+        widening: Some(widening),
+        ..Solver::default()
+    };
+    let result = solver.search(&state, Limits::iterations(100));
+    let playout = Playout::default();
+    let mut moves = Vec::new();
+    state.get_moves(&mut moves);
+    let best = moves.iter().map(|m| playout.weight(&state, *m)).max();
+    assert_eq!(result.best.map(|m| playout.weight(&state, m)), best);
+    assert!(result.nodes <= 101);
+}
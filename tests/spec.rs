@@ -0,0 +1,152 @@
+mod common;
+
+use nego::{
+    agent::{
+        spec::{self, AgentSpec, DEFAULT_TIME},
+        Agent,
+    },
+    core::{error::Error, game::State},
+};
+
+use common::{init, MIDGAME};
+
+use std::time::Duration;
+
+fn parse(s: &str) -> AgentSpec {
+    s.parse().unwrap()
+}
+
+#[test]
+fn spec_parse() {
+    let spec = parse("mcts2:time=5s,c=1.4,seed=7");
+    assert_eq!(spec.agent, Agent::Mcts2(Duration::from_secs(5)));
+    assert_eq!(spec.exploration, Some(1.4));
+    assert_eq!(spec.seed, Some(7));
+
+    let spec = parse("negamax:depth=4,tt=64MB");
+    assert_eq!(spec.agent, Agent::Iterative(DEFAULT_TIME));
+    assert_eq!(spec.iterations, Some(4));
+    assert_eq!(spec.table_size, Some(64 << 20));
+
+    assert_eq!(parse("random"), AgentSpec::from(Agent::Random));
+    assert_eq!(parse("iterative"), parse("negamax"));
+    assert_eq!(
+        parse("solver:time=500ms").agent,
+        Agent::Solver(Duration::from_millis(500))
+    );
+    assert_eq!(
+        parse("mcts:time=2m").agent,
+        Agent::Mcts(Duration::from_secs(120))
+    );
+    assert_eq!(
+        parse("parallel:time=1.5").agent,
+        Agent::Parallel(Duration::from_millis(1500))
+    );
+    assert_eq!(parse("solver:memory=512kb").memory, Some(512 << 10));
+    assert_eq!(parse("mcts:memory=64MB").memory, Some(64 << 20));
+}
+
+#[test]
+fn spec_display() {
+    for s in [
+        "mcts2:time=0.5s,playouts=100,c=1.4,memory=1GB,ponder=yes,seed=7,endgame=8,book=no",
+        "negamax:depth=4,tt=64MB",
+        "parallel:tt=1000",
+        "solver",
+        "random:seed=3",
+        "human",
+    ] {
+        assert_eq!(parse(s).to_string(), s);
+    }
+    assert_eq!(parse("mcts2:time=5s").to_string(), "mcts2");
+}
+
+#[test]
+fn spec_errors() {
+    for s in [
+        "",
+        "alphazero",
+        "mcts2:",
+        "mcts2:time",
+        "mcts2:time=fast",
+        "mcts2:depth=4",
+        "mcts2:budget=1s",
+        "negamax:c=1.4",
+        "mcts:c=1.4",
+        "solver:ponder=yes",
+        "mcts2:ponder=maybe",
+        "random:time=1s",
+        "human:seed=1",
+        "negamax:tt=64XB",
+    ] {
+        assert_eq!(s.parse::<AgentSpec>(), Err(Error::InvalidAgent), "{}", s);
+    }
+}
+
+#[test]
+fn spec_config() {
+    let toml = r#"
+        black = "mcts2:time=5s,c=1.4"
+
+        [white]
+        agent = "negamax"
+        depth = 4
+        tt = "64MB"
+        book = false
+    "#;
+    let config = spec::from_toml(toml).unwrap();
+    assert_eq!(config["black"], parse("mcts2:time=5s,c=1.4"));
+    assert_eq!(config["white"], parse("negamax:depth=4,tt=64MB,book=no"));
+
+    let json = r#"{
+        "black": "mcts2:time=5s,c=1.4",
+        "white": {"agent": "negamax", "depth": 4, "tt": "64MB", "book": false}
+    }"#;
+    assert_eq!(spec::from_json(json).unwrap(), config);
+
+    let json = r#"{"black": {"agent": "mcts2", "time": 2.5, "c": 1}}"#;
+    assert_eq!(
+        spec::from_json(json).unwrap()["black"],
+        parse("mcts2:time=2.5s,c=1")
+    );
+
+    for toml in ["black = \"alphazero\"", "[black]\ndepth = 4", "black = 4"] {
+        assert_eq!(spec::from_toml(toml), Err(Error::InvalidConfig), "{}", toml);
+    }
+    assert_eq!(spec::from_json("{"), Err(Error::InvalidConfig));
+
+    // Files, which are optional for the players
+    let dir = std::env::temp_dir();
+    let path = dir.join(format!("nego-spec-{}.json", std::process::id()));
+    std::fs::write(&path, r#"{"white": "human"}"#).unwrap();
+    let defaults = [parse("random"), parse("solver")];
+    let [black, white] = spec::load_players(&path, defaults);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(black, defaults[0]);
+    assert!(white.is_human());
+    assert_eq!(spec::load_players(&path, defaults), defaults);
+}
+
+#[test]
+fn spec_player() {
+    init();
+    let state = State::from_notation(MIDGAME).unwrap();
+    assert!(parse("human").player().is_none());
+
+    // A seeded spec with a fixed number of playouts repeats its moves,
+    // whatever the time
+    for s in [
+        "mcts2:playouts=20,seed=3",
+        "solver:playouts=20,seed=3",
+        "random:seed=3",
+    ] {
+        let spec = parse(s);
+        let moves = (0..2)
+            .map(|_| {
+                let mut player = spec.player().unwrap();
+                player.choose_move(&state, Duration::ZERO).unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(moves[0], moves[1], "{}", s);
+    }
+}
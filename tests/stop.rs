@@ -0,0 +1,184 @@
+mod common;
+
+use nego::{
+    agent::{
+        analysis::{AnalysisOptions, Analyze},
+        mcts::Mcts,
+        mcts2::Mcts2,
+        negamax::{Iterative, Parallel},
+        solver::Solver,
+        stop::StopToken,
+        time::Budget,
+        Agent, Player, Random,
+    },
+    core::game::State,
+    ui::worker::{Worker, WorkerState},
+};
+
+use common::{init, MIDGAME};
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Far longer than any test should take
+const FOREVER: Duration = Duration::from_secs(60);
+
+// Stop the token after `delay`
+fn stop_after(stop: &StopToken, delay: Duration) {
+    let stop = stop.clone();
+    thread::spawn(move || {
+        thread::sleep(delay);
+        stop.stop();
+    });
+}
+
+// Cancelling a long search still gives a move, promptly
+fn cancel(mut player: Box<dyn Player>) {
+    let state = State::from_notation(MIDGAME).unwrap();
+    let stop = StopToken::new();
+    player.set_stop(stop.clone());
+    stop_after(&stop, Duration::from_millis(100));
+    let start = Instant::now();
+    let m = player.choose_move(&state, FOREVER);
+    assert!(m.is_some(), "{}", player.name());
+    assert!(
+        start.elapsed() < Duration::from_secs(1),
+        "{}",
+        player.name()
+    );
+
+    // Later searches return at once, until there is a new token
+    let start = Instant::now();
+    assert!(player.choose_move(&state, FOREVER).is_some());
+    assert!(start.elapsed() < Duration::from_millis(100));
+    player.set_stop(StopToken::new());
+    assert!(player.choose_move(&state, Duration::ZERO).is_some());
+}
+
+#[test]
+fn stop_mcts2() {
+    init();
+    cancel(Box::new(Mcts2::pondering()));
+}
+
+#[test]
+fn stop_solver() {
+    init();
+    cancel(Box::new(Solver::default()));
+}
+
+#[test]
+fn stop_agents() {
+    init();
+    let agents = [
+        Agent::Mcts2(FOREVER),
+        Agent::Solver(FOREVER),
+        Agent::Iterative(FOREVER),
+        Agent::Parallel(FOREVER),
+        Agent::Mcts(FOREVER),
+    ];
+    for agent in agents {
+        cancel(agent.player().unwrap());
+    }
+}
+
+// Searches which were stopped before they began return at once
+#[test]
+fn stop_before_search() {
+    init();
+    let state = State::from_notation(MIDGAME).unwrap();
+    let players: [Box<dyn Player>; 6] = [
+        Box::new(Mcts2::new()),
+        Box::new(Solver::default()),
+        Box::new(Iterative::new()),
+        Box::new(Parallel::new()),
+        Box::new(Mcts::default()),
+        Box::new(Random::new()),
+    ];
+    for mut player in players {
+        let stop = StopToken::new();
+        stop.stop();
+        player.set_stop(stop);
+        for budget in [Budget::fixed(FOREVER), Budget::iterations(64)] {
+            let start = Instant::now();
+            assert!(player.choose_move_within(&state, budget).is_some());
+            assert!(
+                start.elapsed() < Duration::from_millis(100),
+                "{}",
+                player.name()
+            );
+        }
+    }
+}
+
+#[test]
+fn stop_analysis() {
+    init();
+    let state = State::from_notation(MIDGAME).unwrap();
+    let mut analyzer = Mcts2::new();
+    let stop = StopToken::new();
+    analyzer.set_stop(stop.clone());
+    stop_after(&stop, Duration::from_millis(200));
+    let options = AnalysisOptions {
+        time: FOREVER,
+        ..AnalysisOptions::default()
+    };
+    let start = Instant::now();
+    let analysis = analyzer.analyze(&state, &options, &mut |_| ());
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert!(!analysis.lines.is_empty());
+}
+
+#[test]
+fn stop_worker() {
+    init();
+    let state = State::from_notation(MIDGAME).unwrap();
+    let player: Box<dyn Player> = Box::new(Mcts2::new());
+    let player = Arc::new(Mutex::new(player));
+    let mut worker = Worker::new();
+    worker.spawn(&state, player.clone(), Budget::fixed(FOREVER));
+    assert_eq!(worker.get_state(), WorkerState::Working);
+    thread::sleep(Duration::from_millis(100));
+
+    // The player is free again as soon as the search returns, and its move
+    // is dropped
+    let start = Instant::now();
+    worker.abort();
+    assert_eq!(worker.get_state(), WorkerState::Idle);
+    drop(player.lock().unwrap());
+    assert!(start.elapsed() < Duration::from_secs(1));
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(worker.get_state(), WorkerState::Idle);
+
+    // A new search runs as usual
+    worker.spawn(&state, player, Budget::fixed(Duration::from_millis(10)));
+    let start = Instant::now();
+    while worker.get_state() == WorkerState::Working && start.elapsed() < FOREVER {
+        thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(worker.get_state(), WorkerState::Ready);
+}
+
+// The negamax agents cut the depth they are on short, which is the second
+// or third one here, and play the move of the one before
+#[test]
+fn stop_within_depth() {
+    init();
+    let state = State::from_notation(MIDGAME).unwrap();
+    let players: [Box<dyn Player>; 2] = [Box::new(Iterative::new()), Box::new(Parallel::new())];
+    for mut player in players {
+        let stop = StopToken::new();
+        player.set_stop(stop.clone());
+        stop_after(&stop, Duration::from_millis(100));
+        let start = Instant::now();
+        assert!(player
+            .choose_move_within(&state, Budget::iterations(64))
+            .is_some());
+        assert!(
+            start.elapsed() < Duration::from_secs(1),
+            "{}",
+            player.name()
+        );
+    }
+}
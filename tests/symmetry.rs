@@ -0,0 +1,181 @@
+mod common;
+
+use nego::core::{
+    bitboard::BitBoard,
+    game::State,
+    move_tab::LUTEntry,
+    pieces::{PieceId, PieceList, ALL_PIECES_IDS},
+    r#move::Move,
+    symmetry::{Symmetry, ALL_SYMMETRIES},
+};
+
+use common::{init, ENDGAME, MIDGAME};
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+// Hands without the Nobi and the first two Kunoji, which have no mirror image
+const NO_MIRROR: u16 = 0xc7b;
+
+// Positions from random games, including some with captures. Half of the
+// games are played without the pieces which stop reflections applying.
+fn positions() -> Vec<State> {
+    let mut rng = StdRng::seed_from_u64(0x6e65676f);
+    let mut positions = vec![
+        State::new(),
+        State::from_notation(MIDGAME).unwrap(),
+        State::from_notation(ENDGAME).unwrap(),
+    ];
+    for game in 0..20 {
+        let mut state = match game % 2 {
+            0 => State::new(),
+            _ => {
+                let mut state = State::new();
+                state.board.black.hand = PieceList::from_bits(NO_MIRROR).unwrap();
+                state.board.white.hand = PieceList::from_bits(NO_MIRROR).unwrap();
+                state
+            }
+        };
+        let mut moves = Vec::new();
+        loop {
+            moves.truncate(0);
+            state.get_moves(&mut moves);
+            let Some(m) = moves.choose(&mut rng) else {
+                break;
+            };
+            state.apply(*m);
+            positions.push(state.clone());
+        }
+    }
+    positions
+}
+
+fn sorted(mut moves: Vec<Move>) -> Vec<u16> {
+    let mut raw = moves
+        .drain(..)
+        .map(|m| m.get_raw_value())
+        .collect::<Vec<_>>();
+    raw.sort();
+    raw
+}
+
+#[test]
+fn symmetry_bitboards() {
+    // Not symmetric under any of the transforms
+    let b = BitBoard(0x0000_0000_0000_0307);
+    let images = ALL_SYMMETRIES.map(|symmetry| symmetry.apply(b));
+    for (i, image) in images.iter().enumerate() {
+        assert_eq!(image.popcnt(), 5);
+        assert!(!images[..i].contains(image));
+    }
+    for symmetry in ALL_SYMMETRIES {
+        assert_eq!(symmetry.inverse().apply(symmetry.apply(b)), b);
+    }
+}
+
+#[test]
+fn symmetry_entries() {
+    init();
+    for symmetry in ALL_SYMMETRIES {
+        for piece in ALL_PIECES_IDS {
+            let p = piece.piece_type_id().def();
+            for entry in (p.lut_offset..p.lut_offset + p.moves).map(LUTEntry) {
+                let m = Move::new(piece, entry);
+                let Some(image) = symmetry.map_move(m) else {
+                    assert!(symmetry.is_reflection(), "{:?} {}", symmetry, m);
+                    continue;
+                };
+                assert_eq!(image.mask(), symmetry.apply(m.mask()));
+                assert_eq!(symmetry.inverse().map_move(image), Some(m));
+                if piece != PieceId::Boss {
+                    assert_eq!(image.gaze(), symmetry.apply(m.gaze()));
+                    assert_eq!(image.orientation(), symmetry.orientation(m.orientation()));
+                }
+            }
+        }
+    }
+}
+
+// Transforming a position and then moving is the same as moving and then
+// transforming
+#[test]
+fn symmetry_moves() {
+    init();
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut reflected = 0;
+    for state in positions().into_iter().step_by(7) {
+        for symmetry in ALL_SYMMETRIES {
+            let Some(image) = state.transform(symmetry) else {
+                assert!(symmetry.is_reflection());
+                continue;
+            };
+            reflected += symmetry.is_reflection() as u32;
+            let mut moves = Vec::new();
+            state.get_moves(&mut moves);
+            let mut image_moves = Vec::new();
+            image.get_moves(&mut image_moves);
+            let mapped = moves
+                .iter()
+                .map(|m| symmetry.map_move(*m).unwrap())
+                .collect();
+            assert_eq!(sorted(mapped), sorted(image_moves), "{:?}", symmetry);
+
+            if let Some(m) = moves.choose(&mut rng) {
+                let mut after = state.clone();
+                after.apply(*m);
+                let mut image_after = image.clone();
+                image_after.apply(symmetry.map_move(*m).unwrap());
+                assert_eq!(after.transform(symmetry).unwrap(), image_after);
+            }
+        }
+    }
+    assert!(reflected > 0);
+}
+
+#[test]
+fn symmetry_canonical() {
+    init();
+    for state in positions().into_iter().step_by(5) {
+        let canonical = state.canonical_hash();
+        let (position, symmetry) = state.canonical();
+        assert_eq!(position.hash, canonical.hash);
+        assert_eq!(symmetry, canonical.symmetries[0]);
+        assert!(canonical.hash <= state.hash);
+
+        for symmetry in ALL_SYMMETRIES {
+            if let Some(image) = state.transform(symmetry) {
+                assert_eq!(image.canonical_hash().hash, canonical.hash);
+            }
+        }
+
+        let mut moves = Vec::new();
+        state.get_moves(&mut moves);
+        for m in moves {
+            let stored = canonical.to_canonical(m);
+            let played = canonical.from_canonical(stored).unwrap();
+            assert_eq!(canonical.to_canonical(played), stored);
+        }
+    }
+}
+
+// With every piece in hand, only the rotations apply
+#[test]
+fn symmetry_initial() {
+    init();
+    let state = State::new();
+    let canonical = state.canonical_hash();
+    assert_eq!(canonical.hash, state.hash);
+    assert_eq!(
+        canonical.symmetries,
+        [
+            Symmetry::Identity,
+            Symmetry::Rot90,
+            Symmetry::Rot180,
+            Symmetry::Rot270
+        ]
+    );
+
+    // The four corners are the same opening
+    let corners = ["BOS:A1S", "BOS:G1S", "BOS:A7S", "BOS:G7S"]
+        .map(|m| canonical.to_canonical(Move::parse(m).unwrap()));
+    assert!(corners.iter().all(|m| *m == corners[0]));
+}
@@ -0,0 +1,198 @@
+mod common;
+
+use nego::{
+    agent::{
+        negamax::{Iterative, Parallel},
+        time::{self, Budget, Clock, TimeControl, TimeManager},
+        Agent, Player,
+    },
+    core::game::State,
+};
+
+use common::{init, MIDGAME};
+
+use std::time::{Duration, Instant};
+
+fn ms(ms: u64) -> Duration {
+    Duration::from_millis(ms)
+}
+
+#[test]
+fn time_sudden_death() {
+    let mut clock = Clock::new(TimeControl::SuddenDeath(ms(1000)));
+    assert!(clock.spend(ms(400)));
+    assert_eq!(clock.available(), ms(600));
+    assert!(!clock.spend(ms(601)));
+    assert!(clock.is_flagged());
+    assert_eq!(clock.available(), Duration::ZERO);
+}
+
+#[test]
+fn time_increment() {
+    let control = TimeControl::Increment {
+        main: ms(1000),
+        increment: ms(100),
+    };
+    let mut clock = Clock::new(control);
+    assert!(clock.spend(ms(300)));
+    assert_eq!(clock.available(), ms(800));
+    assert!(clock.spend(ms(800)));
+    assert_eq!(clock.available(), ms(100));
+}
+
+#[test]
+fn time_byo_yomi() {
+    let control = TimeControl::ByoYomi {
+        main: ms(1000),
+        periods: 2,
+        period: ms(500),
+    };
+    let mut clock = Clock::new(control);
+    assert_eq!(clock.available(), ms(2000));
+
+    // Finishing within a period doesn't use it up
+    assert!(clock.spend(ms(1200)));
+    assert_eq!((clock.main, clock.periods), (Duration::ZERO, 2));
+    assert!(clock.spend(ms(700)));
+    assert_eq!(clock.periods, 1);
+    assert!(clock.spend(ms(500)));
+    assert!(!clock.spend(ms(600)));
+    assert!(clock.is_flagged());
+}
+
+#[test]
+fn time_budgets() {
+    init();
+    let manager = TimeManager::default();
+    let controls = [
+        TimeControl::PerMove(ms(2000)),
+        TimeControl::SuddenDeath(ms(60_000)),
+        TimeControl::Increment {
+            main: ms(60_000),
+            increment: ms(1000),
+        },
+        TimeControl::ByoYomi {
+            main: ms(60_000),
+            periods: 3,
+            period: ms(5000),
+        },
+        TimeControl::ByoYomi {
+            main: Duration::ZERO,
+            periods: 1,
+            period: ms(5000),
+        },
+        TimeControl::SuddenDeath(ms(10)),
+    ];
+    let states = [State::new(), State::from_notation(MIDGAME).unwrap()];
+    for control in controls {
+        let clock = Clock::new(control);
+        for state in &states {
+            let budget = manager.budget(&clock, state);
+            assert!(budget.target <= budget.max, "{:?}", control);
+            assert!(budget.max + manager.reserve <= clock.available().max(manager.reserve));
+        }
+    }
+    assert_eq!(
+        manager.budget(&Clock::new(controls[0]), &states[0]),
+        Budget::fixed(ms(1950))
+    );
+
+    // Later in the game, with fewer moves left, each move gets more
+    let clock = Clock::new(TimeControl::SuddenDeath(ms(60_000)));
+    let early = manager.budget(&clock, &states[0]);
+    let later = manager.budget(&clock, &states[1]);
+    assert!(later.target > early.target);
+}
+
+// Play whole games on the clock; no agent may run out of time
+fn play(agents: [Agent; 2], control: TimeControl) {
+    let manager = TimeManager::default();
+    let mut players = agents.map(|agent| agent.player().unwrap());
+    let mut clocks = [Clock::new(control), Clock::new(control)];
+    let mut state = State::new();
+    let mut s = 0;
+    while let Some(m) =
+        time::step_clocked(players[s].as_mut(), &mut state, &mut clocks[s], &manager)
+    {
+        assert!(!clocks[s].is_flagged(), "{:?} ran out of time", agents[s]);
+        s = 1 - s;
+        players[s].opponent_moved(&state, m);
+    }
+    assert!(state.result().is_some());
+    for (agent, clock) in agents.iter().zip(clocks) {
+        assert!(!clock.is_flagged(), "{:?}", agent);
+    }
+}
+
+#[test]
+fn time_agents_sudden_death() {
+    init();
+    let agents = [Agent::Mcts2(Duration::ZERO), Agent::Solver(Duration::ZERO)];
+    play(agents, TimeControl::SuddenDeath(ms(1500)));
+}
+
+#[test]
+fn time_agents_byo_yomi() {
+    init();
+    let agents = [Agent::Solver(Duration::ZERO), Agent::Random];
+    let control = TimeControl::ByoYomi {
+        main: ms(300),
+        periods: 1,
+        period: ms(100),
+    };
+    play(agents, control);
+}
+
+// The minimax-rs searches are cut short at their limits
+#[test]
+fn time_minimax_sudden_death() {
+    init();
+    let pairs = [
+        [
+            Agent::Iterative(Duration::ZERO),
+            Agent::Parallel(Duration::ZERO),
+        ],
+        [Agent::Mcts(Duration::ZERO), Agent::Random],
+    ];
+    for agents in pairs {
+        play(agents, TimeControl::SuddenDeath(ms(1500)));
+    }
+}
+
+#[test]
+fn time_minimax_byo_yomi() {
+    init();
+    let pairs = [
+        [
+            Agent::Parallel(Duration::ZERO),
+            Agent::Iterative(Duration::ZERO),
+        ],
+        [Agent::Random, Agent::Mcts(Duration::ZERO)],
+    ];
+    let control = TimeControl::ByoYomi {
+        main: ms(300),
+        periods: 1,
+        period: ms(100),
+    };
+    for agents in pairs {
+        play(agents, control);
+    }
+}
+
+// Negamax may finish a depth past the target, but not past the limit
+#[test]
+fn time_negamax_limit() {
+    init();
+    let state = State::from_notation(MIDGAME).unwrap();
+    let budget = Budget {
+        target: ms(20),
+        max: ms(200),
+        iterations: None,
+    };
+    let players: [Box<dyn Player>; 2] = [Box::new(Iterative::new()), Box::new(Parallel::new())];
+    for mut player in players {
+        let start = Instant::now();
+        assert!(player.choose_move_within(&state, budget).is_some());
+        assert!(start.elapsed() < ms(250), "{}", player.name());
+    }
+}
@@ -0,0 +1,181 @@
+mod common;
+
+use nego::{
+    agent::{
+        spec::AgentSpec,
+        time::TimeControl,
+        tournament::{
+            self, elo_difference, expected_score, play_game, Format, GameOutcome, Limit, Pairing,
+            Sprt, SprtResult, Tally, TournamentOptions,
+        },
+        Agent, Player, Random,
+    },
+    core::{
+        game::{Color, State},
+        r#move::Move,
+        record::GameRecord,
+    },
+};
+
+use common::{init, MIDGAME};
+
+use std::thread;
+use std::time::Duration;
+
+fn tally(wins: u32, losses: u32, draws: u32) -> Tally {
+    Tally {
+        wins,
+        losses,
+        draws,
+    }
+}
+
+#[test]
+fn tournament_elo() {
+    assert_eq!(elo_difference(0.5), 0.);
+    assert!((elo_difference(0.75) - 190.85).abs() < 0.01);
+    assert!((expected_score(elo_difference(0.3)) - 0.3).abs() < 1e-9);
+    assert_eq!(elo_difference(1.), f64::INFINITY);
+    assert_eq!(elo_difference(0.), f64::NEG_INFINITY);
+
+    let even = tally(10, 10, 5).elo();
+    assert_eq!(even.estimate, 0.);
+    let ahead = tally(30, 10, 10).elo();
+    assert!(ahead.estimate > 0.);
+    let behind = tally(30, 10, 10).reversed().elo();
+    assert!((behind.estimate + ahead.estimate).abs() < 1e-9);
+
+    // More games, a narrower interval
+    assert!(tally(300, 100, 100).elo().margin < ahead.margin / 3.);
+    assert!(tally(3, 0, 0).elo().estimate.is_infinite());
+}
+
+#[test]
+fn tournament_sprt() {
+    let sprt = Sprt::default();
+    let (lower, upper) = sprt.bounds();
+    assert!((lower + 2.944).abs() < 0.001 && (upper - 2.944).abs() < 0.001);
+
+    assert_eq!(sprt.test(&Tally::default()), SprtResult::Continue);
+    assert_eq!(sprt.test(&tally(6, 4, 2)), SprtResult::Continue);
+    assert_eq!(sprt.test(&tally(600, 400, 200)), SprtResult::Accept);
+    assert_eq!(sprt.test(&tally(4000, 4000, 2000)), SprtResult::Reject);
+    assert_eq!(sprt.test(&tally(400, 600, 200)), SprtResult::Reject);
+
+    // Every game won is still evidence
+    let sprt = Sprt { elo1: 100., ..sprt };
+    assert!(sprt.llr(&tally(2, 0, 0)) > 0.);
+    assert_eq!(sprt.test(&tally(20, 0, 0)), SprtResult::Accept);
+}
+
+#[test]
+fn tournament_schedule() {
+    let games = tournament::schedule(Format::RoundRobin, 3, 2, 2);
+    assert_eq!(games.len(), 3 * 2 * 2 * 2);
+    assert!(games.iter().enumerate().all(|(i, game)| game.game == i));
+    for pair in games.chunks(2) {
+        assert_eq!(pair[0].black, pair[1].white);
+        assert_eq!(pair[0].white, pair[1].black);
+        assert_eq!(pair[0].opening, pair[1].opening);
+    }
+    for a in 0..3 {
+        let black = games.iter().filter(|game| game.black == a).count();
+        let white = games.iter().filter(|game| game.white == a).count();
+        assert_eq!((black, white), (8, 8));
+    }
+
+    let games = tournament::schedule(Format::Gauntlet, 4, 1, 1);
+    assert_eq!(games.len(), 3 * 2);
+    assert!(games.iter().all(|game| game.black == 0 || game.white == 0));
+}
+
+// Plays at random after taking too long
+struct Slow(Random);
+
+impl Player for Slow {
+    fn name(&self) -> String {
+        "Slow".to_string()
+    }
+
+    fn choose_move(&mut self, state: &State, budget: Duration) -> Option<Move> {
+        thread::sleep(Duration::from_millis(20));
+        self.0.choose_move(state, budget)
+    }
+}
+
+#[test]
+fn tournament_play_game() {
+    init();
+    let initial = State::from_notation(MIDGAME).unwrap();
+    let outcome = play_game(
+        [&mut Random::new(), &mut Random::new()],
+        [Limit::Iterations(1); 2],
+        &initial,
+    );
+    assert!(!outcome.on_time);
+    let record = outcome.record.to_string().parse::<GameRecord>().unwrap();
+    let state = record.replay().unwrap();
+    let result = state.result().unwrap();
+    assert_eq!(result.winner, outcome.winner);
+    assert_eq!(record.result, result.to_string());
+
+    // White is to move, and loses on time
+    let outcome = play_game(
+        [&mut Random::new(), &mut Slow(Random::new())],
+        [
+            Limit::Iterations(1),
+            Limit::Clock(TimeControl::PerMove(Duration::from_millis(10))),
+        ],
+        &initial,
+    );
+    assert!(outcome.on_time);
+    assert_eq!(outcome.winner, Some(Color::Black));
+    assert_eq!(outcome.record.result, "B+Time");
+    assert!(outcome.record.moves.is_empty());
+}
+
+// Play a seeded tournament between random players, keeping the results in
+// schedule order
+fn play(games: &[Pairing], threads: usize, stop_after: usize) -> Vec<Option<GameOutcome>> {
+    let agents = [AgentSpec::from(Agent::Random); 3];
+    let openings = [State::new(), State::from_notation(MIDGAME).unwrap()];
+    let options = TournamentOptions {
+        threads,
+        iterations: Some(1),
+        seed: Some(5),
+    };
+    let mut outcomes = vec![None; games.len()];
+    let mut played = 0;
+    tournament::run(&agents, games, &openings, &options, |pairing, outcome| {
+        outcomes[pairing.game] = Some(outcome.clone());
+        played += 1;
+        played < stop_after
+    });
+    outcomes
+}
+
+#[test]
+fn tournament_run() {
+    init();
+    let games = tournament::schedule(Format::RoundRobin, 3, 2, 1);
+    let outcomes = play(&games, 3, usize::MAX);
+    for (game, outcome) in games.iter().zip(&outcomes) {
+        let record = &outcome.as_ref().unwrap().record;
+        assert_eq!(record.position.is_some(), game.opening == 1);
+        assert_eq!(
+            (record.black.as_str(), record.white.as_str()),
+            ("random", "random")
+        );
+        assert!(record
+            .tags
+            .contains(&("Game".to_string(), (game.game + 1).to_string())));
+        assert!(record.replay().unwrap().result().is_some());
+    }
+
+    // The same seed plays the same games, however many threads there are
+    assert_eq!(outcomes, play(&games, 1, usize::MAX));
+
+    // No more games are reported once it is stopped
+    let stopped = play(&games, 2, 3);
+    assert_eq!(stopped.iter().flatten().count(), 3);
+}